@@ -0,0 +1,39 @@
+use criterion::{black_box, Criterion, criterion_group, criterion_main};
+use rand::thread_rng;
+
+use charuster::dictionary::{Dictionary, SimpleDictionary, TaxonomyDictionary};
+
+const SAMPLE_SIZE: usize = 3;
+
+fn resource(name: &str) -> String {
+    format!("{}/resources/{}", env!("CARGO_MANIFEST_DIR"), name)
+}
+
+// the sampling done by the generators before `sample_distinct`: remove the terms from a clone of the dictionary
+fn clone_and_remove(dict: &dyn Dictionary) -> Vec<Vec<String>> {
+    let mut cloned = dyn_clone::clone_box(dict);
+    (0..SAMPLE_SIZE).map_while(|_| cloned.choose_path_and_remove(&mut thread_rng())).collect()
+}
+
+fn bench_dictionary(c: &mut Criterion, group_name: &str, dict: &dyn Dictionary) {
+    let mut group = c.benchmark_group(group_name);
+    group.bench_function("clone_and_remove", |b| b.iter(|| clone_and_remove(black_box(dict))));
+    group.bench_function("sample_distinct", |b| {
+        b.iter(|| black_box(dict).sample_distinct_paths(SAMPLE_SIZE, &mut thread_rng()))
+    });
+    group.finish();
+}
+
+fn sampling(c: &mut Criterion) {
+    let names = SimpleDictionary::new(resource("names").as_str());
+    bench_dictionary(c, "names", &names);
+
+    let stats = SimpleDictionary::new(resource("levels_stats").as_str());
+    bench_dictionary(c, "levels_stats", &stats);
+
+    let props = TaxonomyDictionary::new(resource("properties.json").as_str());
+    bench_dictionary(c, "properties", &props);
+}
+
+criterion_group!(benches, sampling);
+criterion_main!(benches);
@@ -1,4 +1,12 @@
+use std::collections::BTreeMap;
+
 use serde::{Serialize, Deserialize};
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+
+/// names of the built-in features of the characters
+const FEATURE_NAMES: [&str; 14] = ["name", "surname", "nickname", "birthdate", "birthplace", "description", "image",
+    "collection", "profession", "hobbies", "props", "levels", "stats", "archetype"];
 
 pub enum CharacterPropTypes {
     String,
@@ -22,10 +30,17 @@ pub enum CharacterFeature {
     PROPS(Vec<Property>),
     LEVELS(Vec<Level>),
     STATS(Vec<Stat>),
+    /// the archetype the character was generated with
+    ARCHETYPE(String),
+    /// a feature added by a custom generator: its name and value
+    CUSTOM(String, Value),
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Charuster {
+    /// position of the character in its collection, counting the characters of the earlier runs appended to
+    #[serde(default)]
+    token_id: usize,
     name: String,
     surname: String,
     nickname: String,
@@ -39,6 +54,10 @@ pub struct Charuster {
     props: Vec<Property>,
     levels: Vec<Level>,
     stats: Vec<Stat>,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    archetype: String,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    custom: BTreeMap<String, Value>,
 }
 
 impl Charuster {
@@ -48,6 +67,31 @@ impl Charuster {
         CharacterBuilder::default()
     }
 
+    /// a builder with the features of the character, to change some of them
+    pub fn into_builder(self) -> CharacterBuilder {
+        CharacterBuilder {
+            token_id: self.token_id,
+            name: self.name,
+            surname: self.surname,
+            nickname: self.nickname,
+            birthdate: self.birthdate,
+            birthplace: self.birthplace,
+            description: self.description,
+            hobbies: self.hobbies,
+            image: self.image,
+            collection: self.collection,
+            profession: self.profession,
+            props: self.props,
+            levels: self.levels,
+            stats: self.stats,
+            archetype: self.archetype,
+            custom: self.custom,
+        }
+    }
+
+    pub fn token_id(&self) -> usize {
+        self.token_id
+    }
     pub fn name(&self) -> &String {
         &self.name
     }
@@ -87,10 +131,19 @@ impl Charuster {
     pub fn stats(&self) -> &Vec<Stat> {
         &self.stats
     }
+    /// the archetype the character was generated with, empty if the config has no archetypes
+    pub fn archetype(&self) -> &String {
+        &self.archetype
+    }
+    /// features added by custom generators, by name
+    pub fn custom(&self) -> &BTreeMap<String, Value> {
+        &self.custom
+    }
 }
 
 #[derive(Default)]
 pub struct CharacterBuilder {
+    token_id: usize,
     name: String,
     surname: String,
     nickname: String,
@@ -104,11 +157,14 @@ pub struct CharacterBuilder {
     props: Vec<Property>,
     levels: Vec<Level>,
     stats: Vec<Stat>,
+    archetype: String,
+    custom: BTreeMap<String, Value>,
 }
 
 impl CharacterBuilder {
     pub fn new() -> Self {
         CharacterBuilder {
+            token_id: 0,
             name: "".to_string(),
             surname: "".to_string(),
             nickname: "".to_string(),
@@ -122,9 +178,16 @@ impl CharacterBuilder {
             props: vec![],
             levels: vec![],
             stats: vec![],
+            archetype: "".to_string(),
+            custom: BTreeMap::new(),
         }
     }
 
+    pub fn token_id(& mut self, token_id: usize) -> &Self {
+        self.token_id = token_id;
+        self
+    }
+
     pub fn name(& mut self, name: String) -> &Self {
         self.name = name.to_string();
         self
@@ -190,10 +253,109 @@ impl CharacterBuilder {
         self
     }
 
+    pub fn archetype(& mut self, archetype: String) -> &Self {
+        self.archetype = archetype;
+        self
+    }
+
+    pub fn custom(& mut self, name: String, value: Value) -> &Self {
+        self.custom.insert(name, value);
+        self
+    }
+
+    /// the value of a feature generated so far, by name; None if it wasn't generated (yet)
+    pub fn feature(&self, name: &str) -> Option<Value> {
+        let value = match name {
+            "name" => Value::from(self.name.as_str()),
+            "surname" => Value::from(self.surname.as_str()),
+            "nickname" => Value::from(self.nickname.as_str()),
+            "birthdate" => Value::from(self.birthdate.as_str()),
+            "birthplace" => Value::from(self.birthplace.as_str()),
+            "description" => Value::from(self.description.as_str()),
+            "image" => Value::from(self.image.as_str()),
+            "collection" => Value::from(self.collection.as_str()),
+            "profession" => Value::from(self.profession.as_str()),
+            "hobbies" => Value::from(self.hobbies.clone()),
+            "props" => serde_json::to_value(&self.props).unwrap(),
+            "levels" => serde_json::to_value(&self.levels).unwrap(),
+            "stats" => serde_json::to_value(&self.stats).unwrap(),
+            "archetype" => Value::from(self.archetype.as_str()),
+            _ => return self.custom.get(name).cloned(),
+        };
+        let generated = match &value {
+            Value::String(value) => !value.is_empty(),
+            Value::Array(values) => !values.is_empty(),
+            _ => true,
+        };
+        Some(value).filter(|_| generated)
+    }
+
+    /// the features set so far, by name, as they are exported; custom features are listed along with the others
+    pub fn features(&self) -> Map<String, Value> {
+        let mut features: Map<String, Value> = FEATURE_NAMES.iter()
+            .filter_map(|name| self.feature(name).map(|value| (name.to_string(), value)))
+            .collect();
+        features.extend(self.custom.iter().map(|(name, value)| (name.clone(), value.clone())));
+        features
+    }
+
+    /// set a feature by name from its JSON value, as exported; unknown names are custom features
+    pub fn set_feature(&mut self, name: &str, value: Value) -> Result<(), String> {
+        let text = |value: Value| match value {
+            Value::String(text) => Ok(text),
+            value => Err(format!("{} must be a string, found {}", name, value)),
+        };
+        match name {
+            "name" => self.name = text(value)?,
+            "surname" => self.surname = text(value)?,
+            "nickname" => self.nickname = text(value)?,
+            "birthdate" => self.birthdate = text(value)?,
+            "birthplace" => self.birthplace = text(value)?,
+            "description" => self.description = text(value)?,
+            "image" => self.image = text(value)?,
+            "collection" => self.collection = text(value)?,
+            "profession" => self.profession = text(value)?,
+            "archetype" => self.archetype = text(value)?,
+            "hobbies" => self.hobbies = from_json(name, value)?,
+            "props" => self.props = from_json(name, value)?,
+            "levels" => self.levels = from_json(name, value)?,
+            "stats" => self.stats = from_json(name, value)?,
+            _ => {
+                self.custom.insert(name.to_owned(), value);
+            }
+        }
+        Ok(())
+    }
+
+    /// replace the `{feature}` placeholders of the template with the features generated so far.
+    /// Vectors are written as lists of the names of their items; unknown placeholders are left as they are
+    pub fn fill_template(&self, template: &str) -> String {
+        let mut filled = String::with_capacity(template.len());
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            filled.push_str(&rest[..start]);
+            rest = &rest[start..];
+            let value = rest.find('}').and_then(|end| self.feature(&rest[1..end]).map(|value| (end, value)));
+            match value {
+                Some((end, value)) => {
+                    filled.push_str(&feature_text(&value));
+                    rest = &rest[end + 1..];
+                }
+                None => {
+                    filled.push('{');
+                    rest = &rest[1..];
+                }
+            }
+        }
+        filled.push_str(rest);
+        filled
+    }
+
 
 
     pub fn build(self) -> Charuster {
         Charuster {
+            token_id: self.token_id,
             name: self.name,
             surname: self.surname,
             nickname: self.nickname,
@@ -207,10 +369,27 @@ impl CharacterBuilder {
             props: self.props,
             levels: self.levels,
             stats: self.stats,
+            archetype: self.archetype,
+            custom: self.custom,
         }
     }
 }
 
+// a vector feature from its JSON value
+fn from_json<T: DeserializeOwned>(name: &str, value: Value) -> Result<T, String> {
+    serde_json::from_value(value).map_err(|e| format!("invalid {}: {}", name, e))
+}
+
+// text of a feature inside a template
+fn feature_text(value: &Value) -> String {
+    match value {
+        Value::String(value) => value.clone(),
+        Value::Array(values) => values.iter().map(feature_text).collect::<Vec<String>>().join(", "),
+        Value::Object(fields) if fields.contains_key("name") => feature_text(&fields["name"]),
+        value => value.to_string(),
+    }
+}
+
 pub trait Quirk {}
 
 // Property
@@ -218,10 +397,22 @@ pub trait Quirk {}
 pub struct Property {
     // TODO creare new fn e togliere pub
     pub prop_type: String,
-    pub name: String
+    pub name: String,
+    /// full taxonomy path of the property, from its root category to its name
+    #[serde(default)]
+    pub path: Vec<String>,
 }
 impl Quirk for Property {}
 
+impl Property {
+    /// create a property from a taxonomy path: the root category is the type, the last element is the name
+    pub fn from_path(path: Vec<String>) -> Self {
+        let prop_type = path.first().cloned().unwrap_or_default();
+        let name = path.last().cloned().unwrap_or_default();
+        Property { prop_type, name, path }
+    }
+}
+
 // Level
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Level {
@@ -250,4 +441,91 @@ mod tests {
     fn test() {
         CharacterFeature::NAME(String::from("CIA"));
     }
+
+    #[test]
+    fn should_create_property_from_taxonomy_path() {
+        let path = vec![String::from("Weapons"), String::from("Blades"), String::from("Katana")];
+        let prop = Property::from_path(path.clone());
+
+        assert_eq!(prop.prop_type, "Weapons");
+        assert_eq!(prop.name, "Katana");
+        assert_eq!(prop.path, path);
+    }
+
+    #[test]
+    fn should_return_the_features_generated_so_far() {
+        let mut builder = CharacterBuilder::new();
+        builder.name(String::from("Mario"));
+        builder.custom(String::from("luck"), Value::from(7));
+
+        assert_eq!(builder.feature("name"), Some(Value::from("Mario")));
+        assert_eq!(builder.feature("luck"), Some(Value::from(7)));
+        assert_eq!(builder.feature("surname"), None);
+        assert_eq!(builder.feature("hobbies"), None);
+        assert_eq!(builder.feature("unknown"), None);
+    }
+
+    #[test]
+    fn should_set_the_features_by_name() {
+        let mut builder = CharacterBuilder::new();
+        builder.set_feature("name", Value::from("Mario")).unwrap();
+        builder.set_feature("props", serde_json::json!([{ "prop_type": "Weapons", "name": "Katana" }])).unwrap();
+        builder.set_feature("luck", Value::from(7)).unwrap();
+
+        assert_eq!(builder.feature("name"), Some(Value::from("Mario")));
+        assert_eq!(builder.feature("luck"), Some(Value::from(7)));
+        assert_eq!(builder.build().props()[0].name, "Katana");
+        assert_eq!(CharacterBuilder::new().set_feature("name", Value::from(3)), Err(String::from("name must be a string, found 3")));
+        assert!(CharacterBuilder::new().set_feature("hobbies", Value::from("chess")).is_err());
+    }
+
+    #[test]
+    fn should_change_the_features_of_a_character() {
+        let mut builder = Charuster::builder();
+        builder.name(String::from("Mario"));
+        builder.surname(String::from("Rossi"));
+
+        let mut builder = builder.build().into_builder();
+        builder.name(String::from("Luigi"));
+        let charuster = builder.build();
+        assert_eq!((charuster.name().as_str(), charuster.surname().as_str()), ("Luigi", "Rossi"));
+    }
+
+    #[test]
+    fn should_list_the_features_set() {
+        let mut builder = CharacterBuilder::new();
+        builder.name(String::from("Mario"));
+        builder.custom(String::from("luck"), Value::from(7));
+
+        let features = builder.features();
+        assert_eq!(features.len(), 2);
+        assert_eq!(features["name"], "Mario");
+        assert_eq!(features["luck"], 7);
+    }
+
+    #[test]
+    fn should_fill_templates_with_the_features_generated_so_far() {
+        let mut builder = CharacterBuilder::new();
+        builder.name(String::from("Mario"));
+        builder.hobbies(vec![String::from("chess"), String::from("poker")]);
+        builder.props(vec![Property::from_path(vec![String::from("Weapons"), String::from("Katana")])]);
+        builder.custom(String::from("luck"), Value::from(7));
+
+        let description = builder.fill_template("{name} plays {hobbies} with a {props}, luck {luck}, {surname} {unclosed");
+        assert_eq!(description, "Mario plays chess, poker with a Katana, luck 7, {surname} {unclosed");
+    }
+
+    #[test]
+    fn should_serialize_custom_features_only_when_present() {
+        let charuster = Charuster::builder().build();
+        assert!(!serde_json::to_string(&charuster).unwrap().contains("custom"));
+
+        let mut builder = Charuster::builder();
+        builder.custom(String::from("lucky_number"), Value::from(7));
+        let json = serde_json::to_string(&builder.build()).unwrap();
+        assert!(json.ends_with(r#""custom":{"lucky_number":7}}"#));
+
+        let charuster: Charuster = serde_json::from_str(&json).unwrap();
+        assert_eq!(charuster.custom()["lucky_number"], 7);
+    }
 }
@@ -0,0 +1,134 @@
+use std::fs::{create_dir_all, File};
+use std::io::BufWriter;
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::config::Config;
+use crate::generator::{export_charusters_with, generate_charusters_with, GeneratorRegistry};
+
+/// name of the summary written next to the folders of the collections
+pub const SUMMARY_FILENAME: &str = "summary.json";
+
+/// What a run generated for each collection of the config
+#[derive(Serialize, Debug)]
+pub struct CollectionsSummary {
+    pub collections: Vec<CollectionSummary>,
+    /// characters generated across all the collections
+    pub total: usize,
+}
+
+#[derive(Serialize, Debug)]
+pub struct CollectionSummary {
+    pub name: String,
+    pub charusters: usize,
+    /// file the collection is exported to, if it's exported
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub export_file: Option<String>,
+}
+
+impl CollectionsSummary {
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        for collection in self.collections.iter() {
+            text.push_str(&format!("{}: {} characters", collection.name, collection.charusters));
+            if let Some(export_file) = &collection.export_file {
+                text.push_str(&format!(" in {}", export_file));
+            }
+            text.push('\n');
+        }
+        text.push_str(&format!("{} characters in {} collections\n", self.total, self.collections.len()));
+        text
+    }
+}
+
+/// generate every collection declared in the config, see `generate_collections_with`
+pub fn generate_collections(config: &Config) -> CollectionsSummary {
+    generate_collections_with(config, &GeneratorRegistry::new())
+}
+
+/// generate every collection declared in the config one after the other, with the custom generators of the
+/// registry. When the config exports the characters, each collection is exported to its own folder and
+/// the summary of the run is written next to the folders
+pub fn generate_collections_with(config: &Config, registry: &GeneratorRegistry) -> CollectionsSummary {
+    let collections: Vec<CollectionSummary> = config.collection_configs().into_iter().map(|collection_config| {
+        let name = collection_config.values_conf.collection_name.clone();
+        let execution_conf = &collection_config.execution_conf;
+        let export_file = execution_conf.export_to_json.then(|| execution_conf.export_to_json_file.clone());
+        let charusters = match &export_file {
+            Some(_) => export_charusters_with(&collection_config, registry),
+            None => generate_charusters_with(collection_config, registry).len(),
+        };
+        CollectionSummary { name, charusters, export_file }
+    }).collect();
+    let total = collections.iter().map(|collection| collection.charusters).sum();
+    let summary = CollectionsSummary { collections, total };
+
+    if config.execution_conf.export_to_json {
+        write_summary(&summary, &summary_filename(config));
+    }
+    summary
+}
+
+/// path of the summary of the collections of the config
+pub fn summary_filename(config: &Config) -> String {
+    let folder = Path::new(&config.execution_conf.export_to_json_file).parent().unwrap_or_else(|| Path::new(""));
+    folder.join(SUMMARY_FILENAME).into_os_string().into_string().unwrap()
+}
+
+fn write_summary(summary: &CollectionsSummary, filename: &str) {
+    if let Some(dir) = Path::new(filename).parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        create_dir_all(dir).unwrap_or_else(|e| panic!("Could not create summary folder {}: {}", dir.display(), e));
+    }
+    let file = File::create(filename).unwrap_or_else(|e| panic!("Could not create summary file {}: {}", filename, e));
+    serde_json::to_writer_pretty(BufWriter::new(file), summary).unwrap_or_else(|e| panic!("Could not write {}: {}", filename, e));
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::character::Charuster;
+    use crate::config::parse_config;
+
+    use super::*;
+
+    fn get_collections_config() -> Config {
+        let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        d.push("resources/test/test_collections_config.toml");
+        let mut config = parse_config(d.to_str().unwrap());
+        // the second collection points at a missing dictionary
+        config.collections.remove(1);
+        config
+    }
+
+    #[test]
+    fn should_export_each_collection_to_its_folder() {
+        let dir = std::env::temp_dir().join(format!("charuster_collections_{}", std::process::id()));
+        let mut config = get_collections_config();
+        config.execution_conf.export_to_json = true;
+        config.execution_conf.export_to_json_file = dir.join("charusters.json").into_os_string().into_string().unwrap();
+        config.collections[1].name = String::from("Mummies");
+
+        let summary = generate_collections(&config);
+        assert_eq!(summary.total, 5);
+        assert_eq!(summary.collections[0].charusters, 3);
+        assert_eq!(summary.collections[1].charusters, 2);
+
+        let mummies: Vec<Charuster> = serde_json::from_str(&std::fs::read_to_string(dir.join("Mummies/charusters.json")).unwrap()).unwrap();
+        assert_eq!(mummies.len(), 2);
+        assert!(mummies.iter().all(|charuster| charuster.collection() == "Mummies"));
+        let written: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(dir.join(SUMMARY_FILENAME)).unwrap()).unwrap();
+        assert_eq!(written["total"], 5);
+        assert_eq!(written["collections"][0]["name"], "Zombies");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn should_summarize_collections_kept_in_memory() {
+        let summary = generate_collections(&get_collections_config());
+        assert_eq!(summary.to_text(), "Zombies: 3 characters\nZombies: 2 characters\n5 characters in 2 collections\n");
+        assert!(summary.collections.iter().all(|collection| collection.export_file.is_none()));
+    }
+}
@@ -1,28 +1,196 @@
-use std::fs::{read_to_string};
-use std::path::{PathBuf};
+use std::collections::HashMap;
+use std::env;
+use std::fs::{canonicalize, read_to_string};
+use std::path::{Path, PathBuf};
 
-use serde::{Deserialize};
+use serde::{Deserialize, Serialize};
+use toml::value::Table;
+use toml::Value;
 
-#[derive(Deserialize)]
+use crate::export::ExportFormat;
+use crate::formats::DictionaryFormat;
+
+/// version of the config format read by this version of the crate. Configs without a `config_version`
+/// are version 1
+pub const CONFIG_VERSION: i64 = 2;
+
+/// environment variable selecting the profile of the config
+pub const PROFILE_VARIABLE: &str = "CHARUSTER_PROFILE";
+
+const ENV_PREFIX: &str = "CHARUSTER_";
+
+/// The generation config. Every section and field is optional: the defaults generate a few characters
+/// from the dictionaries bundled in the resources folder
+#[derive(Deserialize, Serialize)]
 pub struct Config {
+    #[serde(default = "default_config_version")]
+    pub config_version: i64,
+    #[serde(default)]
     pub execution_conf: ExecutionConf,
+    #[serde(default)]
     pub char_conf: CharacterConfig,
+    #[serde(default)]
     pub values_conf: ValuesConfig,
+    /// custom attributes generated along with the built-in features
+    #[serde(default)]
+    pub attributes: Vec<AttributeConfig>,
+    /// collections generated in the same run, each one on top of the sections above
+    #[serde(default)]
+    pub collections: Vec<CollectionConfig>,
+    /// kinds of characters, each one generated with its own values
+    #[serde(default)]
+    pub archetypes: Vec<ArchetypeConfig>,
+    /// number of characters having some values across the collection
+    #[serde(default)]
+    pub quotas: Vec<QuotaConfig>,
+    /// pre-authored characters placed in the collection
+    #[serde(default)]
+    pub legendaries: Vec<LegendaryConfig>,
+}
+
+/// A pre-authored character placed in the collection, with the features it doesn't set generated
+#[derive(Deserialize, Serialize, Clone)]
+pub struct LegendaryConfig {
+    /// JSON or TOML file with the features of the character, as they are exported
+    pub file: String,
+    /// index of the character in the collection, a random free one when not set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub position: Option<usize>,
+}
+
+/// The number of characters of the collection that have a value of a feature
+#[derive(Deserialize, Serialize, Clone)]
+pub struct QuotaConfig {
+    pub feature: String,
+    pub value: String,
+    pub exactly: Option<QuotaAmount>,
+    pub min: Option<QuotaAmount>,
+    pub max: Option<QuotaAmount>,
+}
+
+/// A number of characters, or a share of the collection like `"2%"`
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+#[serde(untagged)]
+pub enum QuotaAmount {
+    Count(u32),
+    Share(String),
 }
 
-#[derive(Deserialize)]
+impl QuotaAmount {
+    // the number of characters out of `total`, rounding the shares with `round`
+    fn resolve(&self, total: usize, round: fn(f64) -> f64) -> Result<usize, String> {
+        match self {
+            QuotaAmount::Count(count) => Ok(*count as usize),
+            QuotaAmount::Share(share) => share.strip_suffix('%')
+                .and_then(|percent| percent.trim().parse::<f64>().ok())
+                .filter(|percent| (0.0..=100.0).contains(percent))
+                .map(|percent| round(total as f64 * percent / 100.0) as usize)
+                .ok_or_else(|| format!("{} is neither a number of characters nor a share like \"2%\"", share)),
+        }
+    }
+}
+
+impl QuotaConfig {
+    /// the least and the most characters, out of `total`, that must have the value. Shares of the
+    /// collection are rounded towards the inside of the bounds
+    pub fn bounds(&self, total: usize) -> Result<(usize, usize), String> {
+        let (min, max) = match (&self.exactly, &self.min, &self.max) {
+            (Some(exactly), None, None) => {
+                let exactly = exactly.resolve(total, f64::round)?;
+                (exactly, exactly)
+            }
+            (Some(_), _, _) => return Err(String::from("exactly can't be set along with min or max")),
+            (None, None, None) => return Err(String::from("the quota has no exactly, min nor max")),
+            (None, min, max) => (
+                min.as_ref().map(|min| min.resolve(total, f64::ceil)).transpose()?.unwrap_or(0),
+                max.as_ref().map(|max| max.resolve(total, f64::floor)).transpose()?.unwrap_or(total),
+            ),
+        };
+        if min > total {
+            return Err(format!("{} characters needed, the collection has {}", min, total));
+        }
+        if min > max {
+            return Err(format!("min ({}) is greater than max ({})", min, max));
+        }
+        Ok((min, max.min(total)))
+    }
+}
+
+/// A collection generated along with the others declared in the config
+#[derive(Deserialize, Serialize)]
+pub struct CollectionConfig {
+    /// name of the collection, and of the folder of its export file
+    pub name: String,
+    /// `execution_conf`, `char_conf`, `values_conf` and `attributes` overriding the ones of the config
+    #[serde(flatten)]
+    pub overrides: Table,
+}
+
+impl CollectionConfig {
+    /// dotted keys the collection sets that are not keys of the sections it can override
+    pub fn unknown_keys(&self) -> Vec<String> {
+        let mut unknown = vec![];
+        let mut ignored = |path: serde_ignored::Path| unknown.push(path.to_string());
+        let _: Result<CollectionSections, _> = serde_ignored::deserialize(Value::Table(self.overrides.clone()), &mut ignored);
+        unknown
+    }
+}
+
+// the sections a collection can override, to tell the keys it sets that are ignored
+#[derive(Deserialize, Default)]
+#[serde(default)]
+#[allow(dead_code)]
+struct CollectionSections {
+    execution_conf: ExecutionConf,
+    char_conf: CharacterConfig,
+    values_conf: ValuesConfig,
+    attributes: Vec<AttributeConfig>,
+}
+
+fn default_config_version() -> i64 {
+    CONFIG_VERSION
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(default)]
 pub struct ExecutionConf {
     pub charusters_nums: u32,
     pub export_to_json: bool,
+    /// export file, relative to the working directory
     pub export_to_json_file: String,
+    /// format of the export file
+    pub export_format: ExportFormat,
+    /// seed of the random generation: the same seed and config always generate the same characters
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    /// generate the characters across threads, with the same result as the sequential generation
+    pub parallel: bool,
+    /// add the characters to the ones already in the export file, instead of replacing them
+    pub append: bool,
+}
+
+impl Default for ExecutionConf {
+    fn default() -> Self {
+        ExecutionConf {
+            charusters_nums: 5,
+            export_to_json: true,
+            export_to_json_file: String::from("output/charusters.json"),
+            export_format: ExportFormat::default(),
+            seed: None,
+            parallel: false,
+            append: false,
+        }
+    }
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize)]
+#[serde(default)]
 pub struct CharacterConfig {
     pub gen_name: bool,
     pub gen_surname: bool,
     pub gen_nickname: bool,
     pub gen_birthdate: bool,
+    pub gen_birthplace: bool,
     pub gen_description: bool,
     pub gen_image: bool,
     pub gen_collection: bool,
@@ -31,9 +199,75 @@ pub struct CharacterConfig {
     pub gen_props: bool,
     pub gen_levels: bool,
     pub gen_stats: bool,
+    /// names of the custom generators to run, registered in the `GeneratorRegistry` passed to the generation
+    pub custom_features: Vec<String>,
+    /// features to generate before each feature, replacing its default dependencies
+    pub dependencies: HashMap<String, Vec<String>>,
+}
+
+// there are no bundled descriptions nor images, the other features are generated
+impl Default for CharacterConfig {
+    fn default() -> Self {
+        CharacterConfig {
+            gen_name: true,
+            gen_surname: true,
+            gen_nickname: true,
+            gen_birthdate: true,
+            gen_birthplace: true,
+            gen_description: false,
+            gen_image: false,
+            gen_collection: true,
+            gen_profession: true,
+            gen_hobbies: true,
+            gen_props: true,
+            gen_levels: true,
+            gen_stats: true,
+            custom_features: vec![],
+            dependencies: HashMap::new(),
+        }
+    }
+}
+
+impl CharacterConfig {
+    /// whether a built-in feature is enabled
+    pub fn is_enabled(&self, feature: &str) -> bool {
+        match feature {
+            "name" => self.gen_name,
+            "surname" => self.gen_surname,
+            "nickname" => self.gen_nickname,
+            "birthdate" => self.gen_birthdate,
+            "birthplace" => self.gen_birthplace,
+            "description" => self.gen_description,
+            "image" => self.gen_image,
+            "collection" => self.gen_collection,
+            "profession" => self.gen_profession,
+            "hobbies" => self.gen_hobbies,
+            "props" => self.gen_props,
+            "levels" => self.gen_levels,
+            "stats" => self.gen_stats,
+            _ => false,
+        }
+    }
+
+    /// disable the built-in features not in `features`
+    pub fn retain_features(&mut self, features: &[&str]) {
+        let toggles = [
+            ("name", &mut self.gen_name), ("surname", &mut self.gen_surname), ("nickname", &mut self.gen_nickname),
+            ("birthdate", &mut self.gen_birthdate), ("birthplace", &mut self.gen_birthplace),
+            ("description", &mut self.gen_description), ("image", &mut self.gen_image),
+            ("collection", &mut self.gen_collection), ("profession", &mut self.gen_profession),
+            ("hobbies", &mut self.gen_hobbies), ("props", &mut self.gen_props), ("levels", &mut self.gen_levels),
+            ("stats", &mut self.gen_stats),
+        ];
+        for (feature, toggle) in toggles {
+            *toggle = *toggle && features.contains(&feature);
+        }
+    }
 }
 
-#[derive(Deserialize)]
+/// Values of the features. Relative dictionary paths are resolved from the folder of the config file
+#[derive(Deserialize, Serialize)]
+#[serde(default)]
 pub struct ValuesConfig {
     pub collection_name: String,
     pub names_file: String,
@@ -49,17 +283,527 @@ pub struct ValuesConfig {
     pub description_files: String,
     pub birthdate_min_year: u16,
     pub birthdate_max_year: u16,
+    /// values of the stats
+    pub stats_range: RangeConfig,
+    /// values of the levels
+    pub levels_range: RangeConfig,
+    /// professions the characters can have, instead of the terms of `professions_file`
+    pub allowed_professions: Vec<String>,
+    /// root categories of `props_file` the props are chosen from, all of them if empty
+    pub property_kinds: Vec<String>,
+    pub sanitation: SanitationConfig,
+    /// read plain text dictionaries lazily from memory-mapped files instead of loading all their terms
+    pub lazy_dictionaries: bool,
 }
 
-pub fn parse_local_config() -> Config {
+impl Default for ValuesConfig {
+    fn default() -> Self {
+        ValuesConfig {
+            collection_name: String::from("Charusters"),
+            names_file: bundled_resource("names"),
+            surnames_file: bundled_resource("surnames"),
+            nicknames_file: bundled_resource("adjectives"),
+            birthplaces_file: bundled_resource("country_cities"),
+            hobbies_file: bundled_resource("hobbies"),
+            professions_file: bundled_resource("professions"),
+            props_file: bundled_resource("properties.json"),
+            stats_file: bundled_resource("levels_stats"),
+            levels_file: bundled_resource("levels_stats"),
+            images_folder: String::new(),
+            description_files: String::new(),
+            birthdate_min_year: 1920,
+            birthdate_max_year: 2010,
+            stats_range: RangeConfig::default(),
+            levels_range: RangeConfig::default(),
+            allowed_professions: vec![],
+            property_kinds: vec![],
+            sanitation: SanitationConfig::default(),
+            lazy_dictionaries: false,
+        }
+    }
+}
+
+impl Config {
+    /// the config of a collection: the sections of this config overridden by the ones of the collection,
+    /// with the name of the collection and, unless it sets its own, the export file in a folder named after it
+    pub fn collection_config(&self, index: usize) -> Result<Config, String> {
+        let collection = &self.collections[index];
+        let mut value = Value::try_from(self).map_err(|e| e.to_string())?;
+        value.as_table_mut().unwrap().remove("collections");
+        merge(&mut value, Value::Table(collection.overrides.clone()));
+
+        let mut config: Config = value.try_into().map_err(|e| format!("invalid collection {}: {}", collection.name, e))?;
+        config.values_conf.collection_name = collection.name.clone();
+        let export_file = collection.overrides.get("execution_conf").and_then(|e| e.get("export_to_json_file"));
+        if export_file.is_none() {
+            let base = Path::new(&self.execution_conf.export_to_json_file);
+            let folder = base.parent().unwrap_or_else(|| Path::new("")).join(&collection.name);
+            config.execution_conf.export_to_json_file = folder.join(base.file_name().unwrap_or_default())
+                .into_os_string().into_string().unwrap();
+        }
+        Ok(config)
+    }
+
+    /// the config of an archetype: this config with the values of the archetype, without attributes,
+    /// collections, archetypes, quotas nor legendaries
+    pub fn archetype_config(&self, index: usize) -> Result<Config, String> {
+        let archetype = &self.archetypes[index];
+        let mut value = Value::try_from(self).map_err(|e| e.to_string())?;
+        for key in ["attributes", "collections", "archetypes", "quotas", "legendaries"] {
+            value.as_table_mut().unwrap().remove(key);
+        }
+        let mut layer = Table::new();
+        layer.insert(String::from("values_conf"), Value::Table(archetype.values_conf.clone()));
+        merge(&mut value, Value::Table(layer));
+        value.try_into().map_err(|e| format!("invalid archetype {}: {}", archetype.name, e))
+    }
+
+    /// the configs of every collection declared in the config. Panics if a collection is invalid
+    pub fn collection_configs(&self) -> Vec<Config> {
+        (0..self.collections.len())
+            .map(|index| self.collection_config(index).unwrap_or_else(|e| panic!("{}", e)))
+            .collect()
+    }
+}
+
+/// A kind of character, drawn for each character according to its weight, whose features are generated
+/// with its own values
+#[derive(Deserialize, Serialize)]
+pub struct ArchetypeConfig {
+    pub name: String,
+    /// relative probability of a character having the archetype
+    #[serde(default = "default_archetype_weight")]
+    pub weight: f64,
+    /// keys of `values_conf` overriding the ones of the config for the characters of the archetype
+    #[serde(default)]
+    pub values_conf: Table,
+}
+
+fn default_archetype_weight() -> f64 {
+    1.0
+}
+
+// the features generated from each key of `values_conf`
+const VALUES_FEATURES: [(&str, &[&str]); 20] = [
+    ("collection_name", &["collection"]),
+    ("names_file", &["name"]),
+    ("surnames_file", &["surname"]),
+    ("nicknames_file", &["nickname"]),
+    ("birthplaces_file", &["birthplace"]),
+    ("hobbies_file", &["hobbies"]),
+    ("professions_file", &["profession"]),
+    ("allowed_professions", &["profession"]),
+    ("props_file", &["props"]),
+    ("property_kinds", &["props"]),
+    ("stats_file", &["stats"]),
+    ("stats_range", &["stats"]),
+    ("levels_file", &["levels"]),
+    ("levels_range", &["levels"]),
+    ("images_folder", &["image"]),
+    ("description_files", &["description"]),
+    ("birthdate_min_year", &["birthdate"]),
+    ("birthdate_max_year", &["birthdate"]),
+    ("sanitation", &DICTIONARY_FEATURES),
+    ("lazy_dictionaries", &DICTIONARY_FEATURES),
+];
+
+const DICTIONARY_FEATURES: [&str; 9] = ["name", "surname", "nickname", "birthplace", "description", "profession",
+    "hobbies", "levels", "stats"];
+
+impl ArchetypeConfig {
+    /// the built-in features generated with the values of the archetype, in the order of the config
+    pub fn features(&self) -> Vec<&'static str> {
+        let mut features = vec![];
+        for key in self.values_conf.keys() {
+            for feature in VALUES_FEATURES.iter().filter(|(k, _)| k == key).flat_map(|(_, features)| features.iter()) {
+                if !features.contains(feature) {
+                    features.push(*feature);
+                }
+            }
+        }
+        features
+    }
+
+    /// keys of `values_conf` the archetype sets that are not keys of `values_conf`
+    pub fn unknown_keys(&self) -> Vec<&str> {
+        self.values_conf.keys()
+            .filter(|key| !VALUES_FEATURES.iter().any(|(k, _)| k == key))
+            .map(|key| key.as_str())
+            .collect()
+    }
+}
+
+// keys of `values_conf` holding a path
+const PATH_KEYS: [&str; 11] = ["names_file", "surnames_file", "nicknames_file", "birthplaces_file", "hobbies_file",
+    "professions_file", "props_file", "stats_file", "levels_file", "images_folder", "description_files"];
+
+// resolve the relative dictionary paths, images folder and attribute sources of a config layer against `base`
+fn resolve_paths(layer: &mut Value, base: &Path) {
+    if let Some(values_conf) = layer.get_mut("values_conf").and_then(|v| v.as_table_mut()) {
+        for (_, path) in values_conf.iter_mut().filter(|(key, _)| PATH_KEYS.contains(&key.as_str())) {
+            if let Value::String(filename) = path {
+                *filename = resolve_path(filename, base);
+            }
+        }
+    }
+    if let Some(attributes) = layer.get_mut("attributes").and_then(|a| a.as_array_mut()) {
+        for attribute in attributes.iter_mut() {
+            if let Some(Value::String(source)) = attribute.get_mut("source") {
+                *source = resolve_path(source, base);
+            }
+        }
+    }
+    if let Some(archetypes) = layer.get_mut("archetypes").and_then(|a| a.as_array_mut()) {
+        for archetype in archetypes.iter_mut() {
+            resolve_paths(archetype, base);
+        }
+    }
+    if let Some(legendaries) = layer.get_mut("legendaries").and_then(|l| l.as_array_mut()) {
+        for legendary in legendaries.iter_mut() {
+            if let Some(Value::String(file)) = legendary.get_mut("file") {
+                *file = resolve_path(file, base);
+            }
+        }
+    }
+}
+
+fn resolve_path(filename: &str, base: &Path) -> String {
+    let (_, path) = DictionaryFormat::from_filename(filename);
+    if path.is_empty() || Path::new(path).is_absolute() {
+        return filename.to_owned();
+    }
+    let prefix = &filename[..filename.len() - path.len()];
+    let mut resolved = base.join(path).into_os_string().into_string().unwrap();
+    resolved.insert_str(0, prefix);
+    resolved
+}
+
+/// path of a dictionary bundled in the resources folder of the crate
+pub fn bundled_resource(name: &str) -> String {
+    let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    d.push("resources");
+    d.push(name);
+    d.into_os_string().into_string().unwrap()
+}
+
+/// A custom attribute declared in the config. Its values end up in the `custom` map of the characters
+#[derive(Deserialize, Serialize)]
+pub struct AttributeConfig {
+    pub name: String,
+    pub kind: AttributeKind,
+    /// dictionary of the `choose`, `choose_and_remove` and `vector` attributes
+    #[serde(default)]
+    pub source: String,
+    /// number of distinct terms of the `vector` attributes
+    #[serde(default = "default_attribute_count")]
+    pub count: u8,
+    /// lower bound of the `number` attributes (1 by default), or first year of the `date` attributes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min: Option<i64>,
+    /// upper bound of the `number` attributes (100 by default), or last year of the `date` attributes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max: Option<i64>,
+    /// distribution of the `number` and `date` attributes between their bounds
+    #[serde(default)]
+    pub distribution: Distribution,
+}
+
+fn default_attribute_count() -> u8 {
+    3
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AttributeKind {
+    /// a term of the source dictionary
+    Choose,
+    /// a term of the source dictionary, never given to another character
+    ChooseAndRemove,
+    /// `count` distinct terms of the source dictionary
+    Vector,
+    /// an integer between `min` and `max`
+    Number,
+    /// a timestamp between the first day of `min` and the first day of `max` years
+    Date,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Distribution {
+    #[default]
+    Uniform,
+    /// a normal distribution centered between the bounds, with a standard deviation of a sixth of their span
+    Normal,
+}
+
+/// Bounds (both included) and distribution of numeric values
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct RangeConfig {
+    pub min: i32,
+    pub max: i32,
+    pub distribution: Distribution,
+}
+
+impl Default for RangeConfig {
+    fn default() -> Self {
+        RangeConfig { min: 1, max: 100, distribution: Distribution::Uniform }
+    }
+}
+
+/// Clean up applied to the terms of the dictionaries when they are loaded
+#[derive(Deserialize, Serialize)]
+#[serde(default)]
+pub struct SanitationConfig {
+    /// remove leading and trailing whitespace
+    pub trim: bool,
+    /// skip lines starting with `#` in plain text dictionaries
+    pub skip_comments: bool,
+    pub skip_blank_lines: bool,
+    /// drop terms equal (ignoring case) to a previous one
+    pub deduplicate: bool,
+    /// normalize terms to unicode NFC
+    pub normalize_unicode: bool,
+}
+
+impl SanitationConfig {
+    /// the steps of the config applied to the memory-mapped dictionaries, which are only trimmed and
+    /// skip comments and blank lines
+    pub fn lazy(&self) -> Self {
+        SanitationConfig {
+            trim: self.trim,
+            skip_comments: self.skip_comments,
+            skip_blank_lines: true,
+            deduplicate: false,
+            normalize_unicode: false,
+        }
+    }
+
+    /// a config keeping the terms exactly as they are written
+    pub fn disabled() -> Self {
+        SanitationConfig {
+            trim: false,
+            skip_comments: false,
+            skip_blank_lines: false,
+            deduplicate: false,
+            normalize_unicode: false,
+        }
+    }
+}
+
+impl Default for SanitationConfig {
+    fn default() -> Self {
+        SanitationConfig {
+            trim: true,
+            skip_comments: true,
+            skip_blank_lines: true,
+            deduplicate: true,
+            normalize_unicode: true,
+        }
+    }
+}
+
+/// path of the config shipped in the resources folder
+pub fn local_config_filename() -> String {
     let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
     d.push("resources/config.toml");
-    let config_file = d.into_os_string().into_string().unwrap();
-    parse_config(&config_file)
+    d.into_os_string().into_string().unwrap()
+}
+
+pub fn parse_local_config() -> Config {
+    parse_config(&local_config_filename())
 }
 
+/// parse a config file with the profile selected by `CHARUSTER_PROFILE`, if any
 pub fn parse_config(config_filename: &str) -> Config {
-    toml::from_str(read_to_string(config_filename).unwrap().as_str()).unwrap()
+    parse_config_with_profile(config_filename, None)
+}
+
+/// parse a config file merged with the files it extends, the given profile (or the one selected by
+/// `CHARUSTER_PROFILE`) and the `CHARUSTER_*` environment overrides
+pub fn parse_config_with_profile(config_filename: &str, profile: Option<&str>) -> Config {
+    let (value, _) = read_config_value(config_filename, profile).unwrap_or_else(|e| panic!("{}", e));
+    value.try_into().unwrap()
+}
+
+/// read the layers of a config into a single value, upgraded to the current version and with its relative
+/// paths resolved, along with a note for each upgrade. The layers are applied in order:
+/// - the files listed in `extends`, each resolved from the folder of the file extending it
+/// - the file itself
+/// - the `[profiles.<name>]` table of the selected profile
+/// - the `CHARUSTER_<SECTION>__<KEY>` environment variables
+pub fn read_config_value(config_filename: &str, profile: Option<&str>) -> Result<(Value, Vec<String>), String> {
+    let mut notes = vec![];
+    let mut value = read_layer(Path::new(config_filename), &mut vec![], &mut notes, &mut vec![])?;
+
+    let profiles = value.as_table_mut().and_then(|table| table.remove("profiles"));
+    if let Some(profile) = profile.map(str::to_owned).or_else(|| env::var(PROFILE_VARIABLE).ok()) {
+        let layer = profiles.as_ref().and_then(|profiles| profiles.get(&profile))
+            .ok_or_else(|| format!("profile {} is not defined", profile))?;
+        merge(&mut value, layer.clone());
+    }
+    apply_env_overrides(&mut value, env::vars())?;
+    Ok((value, notes))
+}
+
+/// the files a config is read from, the ones whose keys take precedence first: the config file, then the
+/// files it extends from the last one
+pub fn config_files(config_filename: &str) -> Result<Vec<PathBuf>, String> {
+    let mut files = vec![];
+    read_layer(Path::new(config_filename), &mut vec![], &mut vec![], &mut files)?;
+    files.reverse();
+    Ok(files)
+}
+
+// a config file over the files it extends, `chain` holds the files extending it to detect cycles and
+// `files` gets the files read, in the order they are merged
+fn read_layer(filename: &Path, chain: &mut Vec<PathBuf>, notes: &mut Vec<String>, files: &mut Vec<PathBuf>) -> Result<Value, String> {
+    let source = read_to_string(filename).map_err(|e| format!("could not read {}: {}", filename.display(), e))?;
+    let mut layer: Value = toml::from_str(&source).map_err(|e| format!("invalid config {}: {}", filename.display(), e))?;
+    let version = layer.get("config_version").and_then(|v| v.as_integer()).unwrap_or(1);
+    if version > CONFIG_VERSION {
+        return Err(format!("{} has version {}, the latest version is {}", filename.display(), version, CONFIG_VERSION));
+    }
+    let file = if chain.is_empty() { String::from("the config") } else { filename.display().to_string() };
+    notes.extend(migrate_config(&mut layer).into_iter()
+        .map(|note| format!("{} is read as version {} and upgraded: {}", file, version, note)));
+
+    let folder = filename.parent().map(Path::to_path_buf).unwrap_or_default();
+    resolve_paths(&mut layer, &folder);
+    if let Some(profiles) = layer.get_mut("profiles").and_then(|p| p.as_table_mut()) {
+        for (_, profile) in profiles.iter_mut() {
+            resolve_paths(profile, &folder);
+        }
+    }
+    if let Some(collections) = layer.get_mut("collections").and_then(|c| c.as_array_mut()) {
+        for collection in collections.iter_mut() {
+            resolve_paths(collection, &folder);
+        }
+    }
+
+    let extends = match layer.as_table_mut().and_then(|table| table.remove("extends")) {
+        None => vec![],
+        Some(Value::String(base)) => vec![base],
+        Some(Value::Array(bases)) => bases.into_iter()
+            .map(|base| base.as_str().map(str::to_owned).ok_or_else(|| format!("extends of {} must be file names", filename.display())))
+            .collect::<Result<_, _>>()?,
+        Some(_) => return Err(format!("extends of {} must be a file name or a list of file names", filename.display())),
+    };
+    let canonical = canonicalize(filename).map_err(|e| format!("could not read {}: {}", filename.display(), e))?;
+    if chain.contains(&canonical) {
+        return Err(format!("{} extends itself", filename.display()));
+    }
+
+    let mut merged = Value::Table(Table::new());
+    chain.push(canonical);
+    for base in extends {
+        merge(&mut merged, read_layer(&folder.join(base), chain, notes, files)?);
+    }
+    chain.pop();
+    files.push(filename.to_path_buf());
+    merge(&mut merged, layer);
+    Ok(merged)
+}
+
+// tables are merged key by key, any other value of the layer replaces the previous one
+fn merge(value: &mut Value, layer: Value) {
+    match (value, layer) {
+        (Value::Table(table), Value::Table(layer)) => {
+            for (key, layer_value) in layer {
+                match table.get_mut(&key) {
+                    Some(value) => merge(value, layer_value),
+                    None => {
+                        table.insert(key, layer_value);
+                    }
+                }
+            }
+        }
+        (value, layer) => *value = layer,
+    }
+}
+
+/// override the keys of the config with the `CHARUSTER_<SECTION>__<KEY>` variables, nested tables are separated
+/// by a double underscore as well (e.g. `CHARUSTER_VALUES_CONF__SANITATION__TRIM`). The values of string keys
+/// are taken as they are, the other values are read as TOML values (or as strings if they are not valid TOML).
+/// Variables outside the sections are ignored
+pub fn apply_env_overrides<I>(config: &mut Value, vars: I) -> Result<(), String>
+where I: IntoIterator<Item = (String, String)> {
+    let mut defaults = Table::new();
+    defaults.insert(String::from("execution_conf"), Value::try_from(ExecutionConf::default()).unwrap());
+    defaults.insert(String::from("char_conf"), Value::try_from(CharacterConfig::default()).unwrap());
+    defaults.insert(String::from("values_conf"), Value::try_from(ValuesConfig::default()).unwrap());
+    let defaults = Value::Table(defaults);
+
+    for (name, raw) in vars {
+        let key = match env_override_key(&name) {
+            Some(Ok(key)) => key,
+            _ => continue,
+        };
+        let keys: Vec<&str> = key.split('.').collect();
+        // the type of the key is the one of its current value, or of its default value
+        let string_key = value_at(config, &keys).or_else(|| value_at(&defaults, &keys)).is_some_and(Value::is_str);
+        let value = match string_key {
+            true => Value::String(raw),
+            false => toml::from_str::<Table>(&format!("value = {}", raw)).ok()
+                .and_then(|mut table| table.remove("value"))
+                .unwrap_or(Value::String(raw)),
+        };
+
+        let mut table = config.as_table_mut().unwrap();
+        for key in &keys[..keys.len() - 1] {
+            table = table.entry(key.to_string()).or_insert_with(|| Value::Table(Table::new())).as_table_mut()
+                .ok_or_else(|| format!("{} overrides a value that is not a table", name))?;
+        }
+        table.insert(keys[keys.len() - 1].to_owned(), value);
+    }
+    Ok(())
+}
+
+// the value of a nested key, if it's set
+fn value_at<'a>(value: &'a Value, keys: &[&str]) -> Option<&'a Value> {
+    keys.iter().try_fold(value, |value, key| value.get(key))
+}
+
+/// the dotted key overridden by a `CHARUSTER_<SECTION>__<KEY>` variable, None for the other variables and
+/// an error for the variables outside `execution_conf`, `char_conf` and `values_conf`
+pub fn env_override_key(name: &str) -> Option<Result<String, String>> {
+    let path = name.strip_prefix(ENV_PREFIX).filter(|path| path.contains("__"))?.to_lowercase();
+    let keys: Vec<&str> = path.split("__").collect();
+    if !["execution_conf", "char_conf", "values_conf"].contains(&keys[0]) || keys.iter().any(|key| key.is_empty()) {
+        return Some(Err(format!("{} does not override a key of execution_conf, char_conf or values_conf", name)));
+    }
+    Some(Ok(keys.join(".")))
+}
+
+/// the effective config, with its defaults, as TOML
+pub fn to_toml(config: &Config) -> String {
+    toml::to_string(&Value::try_from(config).unwrap()).unwrap()
+}
+
+/// upgrade a config written for an older version of the format to the current one, returning a note
+/// for each change. Panics if the config is newer than the supported version
+pub fn migrate_config(config: &mut Value) -> Vec<String> {
+    let mut notes = vec![];
+    let version = config.get("config_version").and_then(|v| v.as_integer()).unwrap_or(1);
+    if version > CONFIG_VERSION {
+        panic!("Config version {} is not supported, the latest version is {}", version, CONFIG_VERSION);
+    }
+
+    if version < 2 {
+        // birthplaces were generated along with the birthdates
+        if let Some(char_conf) = config.get_mut("char_conf").and_then(|c| c.as_table_mut()) {
+            if !char_conf.contains_key("gen_birthplace") {
+                let gen_birthdate = char_conf.get("gen_birthdate").cloned().unwrap_or(Value::Boolean(false));
+                notes.push(format!("char_conf.gen_birthplace set to {} like gen_birthdate", gen_birthdate));
+                char_conf.insert(String::from("gen_birthplace"), gen_birthdate);
+            }
+        }
+    }
+
+    if let Some(table) = config.as_table_mut() {
+        table.insert(String::from("config_version"), Value::Integer(CONFIG_VERSION));
+    }
+    notes
 }
 
 #[cfg(test)]
@@ -67,21 +811,30 @@ mod tests {
 
     use super::*;
 
+    fn get_test_filename(name: &str) -> String {
+        let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        d.push("resources/test");
+        d.push(name);
+        d.into_os_string().into_string().unwrap()
+    }
+
     #[test]
     fn should_read_conf_from_toml() {
-        let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
-        d.push("resources/test/test_config.toml");
-        let config_file = d.into_os_string().into_string().unwrap();
-        let config = parse_config(config_file.as_str());
+        let config = parse_config(get_test_filename("test_config.toml").as_str());
 
+        assert_eq!(config.config_version, CONFIG_VERSION);
         assert_eq!(config.execution_conf.charusters_nums, 5);
         assert_eq!(config.execution_conf.export_to_json, true);
         assert_eq!(config.execution_conf.export_to_json_file, "output/charusters.json");
+        assert_eq!(config.execution_conf.export_format, ExportFormat::Json);
+        assert_eq!(config.execution_conf.seed, None);
+        assert!(!config.execution_conf.parallel);
 
         assert!(config.char_conf.gen_name);
         assert!(config.char_conf.gen_surname);
         assert!(config.char_conf.gen_nickname);
         assert!(config.char_conf.gen_birthdate);
+        assert!(config.char_conf.gen_birthplace);
         assert!(config.char_conf.gen_description);
         assert!(config.char_conf.gen_image);
         assert!(config.char_conf.gen_collection);
@@ -90,19 +843,268 @@ mod tests {
         assert!(config.char_conf.gen_props);
         assert!(config.char_conf.gen_levels);
         assert!(config.char_conf.gen_stats);
+        assert!(config.char_conf.custom_features.is_empty());
 
         assert_eq!(config.values_conf.collection_name, "Junkie Zombies");
-        assert_eq!(config.values_conf.names_file, "./names");
-        assert_eq!(config.values_conf.surnames_file, "./surnames");
-        assert_eq!(config.values_conf.nicknames_file, "./adjectives");
-        assert_eq!(config.values_conf.hobbies_file, "./hobbies");
-        assert_eq!(config.values_conf.professions_file, "./professions");
-        assert_eq!(config.values_conf.props_file, "./properties");
-        assert_eq!(config.values_conf.stats_file, "./stats");
-        assert_eq!(config.values_conf.levels_file, "./levels");
-        assert_eq!(config.values_conf.images_folder, "./images/");
-        assert_eq!(config.values_conf.description_files, "./descriptions");
+        let test_path = |name: &str| get_test_filename(name);
+        assert_eq!(config.values_conf.names_file, test_path("./names"));
+        assert_eq!(config.values_conf.surnames_file, test_path("./surnames"));
+        assert_eq!(config.values_conf.nicknames_file, test_path("./adjectives"));
+        assert_eq!(config.values_conf.birthplaces_file, test_path("../country_cities"));
+        assert_eq!(config.values_conf.hobbies_file, test_path("./hobbies"));
+        assert_eq!(config.values_conf.professions_file, test_path("./professions"));
+        assert_eq!(config.values_conf.props_file, test_path("./properties"));
+        assert_eq!(config.values_conf.stats_file, test_path("./stats"));
+        assert_eq!(config.values_conf.levels_file, test_path("./levels"));
+        assert_eq!(config.values_conf.images_folder, test_path("./images/"));
+        assert_eq!(config.values_conf.description_files, test_path("./descriptions"));
         assert_eq!(config.values_conf.birthdate_min_year, 1920);
         assert_eq!(config.values_conf.birthdate_max_year, 2010);
+        assert!(config.values_conf.sanitation.trim);
+        assert!(!config.values_conf.sanitation.skip_comments);
+        assert!(config.values_conf.sanitation.skip_blank_lines);
+        assert!(!config.values_conf.sanitation.deduplicate);
+        assert!(config.values_conf.sanitation.normalize_unicode);
+        assert!(!config.values_conf.lazy_dictionaries);
+
+        assert_eq!(config.attributes.len(), 3);
+        assert_eq!(config.attributes[0].name, "alignment");
+        assert_eq!(config.attributes[0].kind, AttributeKind::Choose);
+        assert_eq!(config.attributes[0].source, test_path("./alignments"));
+        assert_eq!(config.attributes[0].count, 3);
+        assert_eq!(config.attributes[0].distribution, Distribution::Uniform);
+        assert_eq!(config.attributes[1].kind, AttributeKind::Vector);
+        assert_eq!(config.attributes[1].count, 2);
+        assert_eq!(config.attributes[2].kind, AttributeKind::Number);
+        assert_eq!(config.attributes[2].min, Some(1));
+        assert_eq!(config.attributes[2].max, Some(20));
+        assert_eq!(config.attributes[2].distribution, Distribution::Normal);
+    }
+
+    fn version_1_config(char_conf: &str) -> toml::Value {
+        toml::from_str(&format!("[char_conf]\n{}", char_conf)).unwrap()
+    }
+
+    #[test]
+    fn should_migrate_birthplace_toggle_from_version_1() {
+        let mut config = version_1_config("gen_birthdate = true");
+        let notes = migrate_config(&mut config);
+
+        assert_eq!(notes, vec!["char_conf.gen_birthplace set to true like gen_birthdate"]);
+        assert_eq!(config["char_conf"]["gen_birthplace"].as_bool(), Some(true));
+        assert_eq!(config["config_version"].as_integer(), Some(CONFIG_VERSION));
+    }
+
+    #[test]
+    fn should_keep_explicit_birthplace_toggle_while_migrating() {
+        let mut config = version_1_config("gen_birthdate = true\ngen_birthplace = false");
+        assert!(migrate_config(&mut config).is_empty());
+        assert_eq!(config["char_conf"]["gen_birthplace"].as_bool(), Some(false));
+    }
+
+    #[test]
+    fn should_not_migrate_current_configs() {
+        let mut config: toml::Value = toml::from_str("config_version = 2\n[char_conf]\ngen_birthdate = true").unwrap();
+        assert!(migrate_config(&mut config).is_empty());
+        assert!(config["char_conf"].get("gen_birthplace").is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "Config version 3 is not supported")]
+    fn should_panic_on_newer_configs() {
+        let mut config: toml::Value = toml::from_str("config_version = 3").unwrap();
+        migrate_config(&mut config);
+    }
+
+    #[test]
+    fn should_default_missing_sections_and_fields() {
+        let config = parse_config(get_test_filename("test_minimal_config.toml").as_str());
+
+        assert_eq!(config.config_version, CONFIG_VERSION);
+        assert_eq!(config.execution_conf.charusters_nums, 3);
+        assert!(!config.execution_conf.export_to_json);
+        assert_eq!(config.execution_conf.export_to_json_file, "output/charusters.json");
+        assert!(config.char_conf.gen_name);
+        assert!(!config.char_conf.gen_description);
+        assert!(!config.char_conf.gen_image);
+        assert_eq!(config.values_conf.names_file, bundled_resource("names"));
+        assert_eq!(config.values_conf.props_file, bundled_resource("properties.json"));
+        assert_eq!(config.values_conf.birthdate_min_year, 1920);
+        assert!(config.values_conf.sanitation.trim);
+        assert!(config.attributes.is_empty());
+    }
+
+    #[test]
+    fn should_resolve_relative_paths_from_the_config_folder() {
+        let base = Path::new("/configs");
+        assert_eq!(resolve_path("names", base), "/configs/names");
+        assert_eq!(resolve_path("../shared/names.csv", base), "/configs/../shared/names.csv");
+        assert_eq!(resolve_path("yaml:names.txt", base), "yaml:/configs/names.txt");
+        assert_eq!(resolve_path("/dictionaries/names", base), "/dictionaries/names");
+        assert_eq!(resolve_path("", base), "");
+        assert_eq!(resolve_path("names", Path::new("")), "names");
+    }
+
+    #[test]
+    fn should_merge_the_extended_config_under_the_config() {
+        let config = parse_config_with_profile(get_test_filename("test_layered_config.toml").as_str(), None);
+
+        assert_eq!(config.execution_conf.charusters_nums, 5);
+        assert!(!config.execution_conf.export_to_json);
+        assert_eq!(config.execution_conf.seed, Some(7));
+        assert!(!config.char_conf.gen_birthplace);
+        assert!(!config.char_conf.gen_props);
+        assert!(config.char_conf.gen_name);
+        assert_eq!(config.values_conf.collection_name, "Layered");
+        assert_eq!(config.values_conf.names_file, get_test_filename("layers/../test_dictionary"));
+        assert_eq!(config.values_conf.surnames_file, get_test_filename("test_dictionary.csv"));
+        assert_eq!(config.values_conf.hobbies_file, bundled_resource("hobbies"));
+    }
+
+    #[test]
+    fn should_apply_the_selected_profile() {
+        let filename = get_test_filename("test_layered_config.toml");
+        let final_config = parse_config_with_profile(filename.as_str(), Some("final"));
+        assert_eq!(final_config.execution_conf.charusters_nums, 100);
+        assert_eq!(final_config.execution_conf.seed, Some(42));
+        assert_eq!(final_config.values_conf.names_file, get_test_filename("test_dictionary.json"));
+        assert_eq!(final_config.values_conf.collection_name, "Layered");
+
+        let preview = parse_config_with_profile(filename.as_str(), Some("preview"));
+        assert_eq!(preview.execution_conf.charusters_nums, 2);
+        assert_eq!(preview.execution_conf.seed, Some(7));
+
+        let error = read_config_value(filename.as_str(), Some("draft")).err().unwrap();
+        assert_eq!(error, "profile draft is not defined");
+    }
+
+    #[test]
+    fn should_report_configs_extending_themselves() {
+        let error = read_config_value(get_test_filename("layers/test_cyclic_config.toml").as_str(), None).err().unwrap();
+        assert!(error.ends_with("test_cyclic_config.toml extends itself"), "{}", error);
+    }
+
+    fn env_vars(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+        vars.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect()
+    }
+
+    #[test]
+    fn should_override_keys_from_the_environment() {
+        let mut value: Value = toml::from_str("[execution_conf]\ncharusters_nums = 5").unwrap();
+        apply_env_overrides(&mut value, env_vars(&[
+            ("CHARUSTER_EXECUTION_CONF__CHARUSTERS_NUMS", "12"),
+            ("CHARUSTER_CHAR_CONF__GEN_IMAGE", "true"),
+            ("CHARUSTER_VALUES_CONF__NAMES_FILE", "yaml:names.txt"),
+            ("CHARUSTER_VALUES_CONF__COLLECTION_NAME", "2024"),
+            ("CHARUSTER_VALUES_CONF__SANITATION__TRIM", "false"),
+            ("CHARUSTER_PROFILE", "final"),
+            ("HOME", "/root"),
+        ])).unwrap();
+
+        let config: Config = value.try_into().unwrap();
+        assert_eq!(config.execution_conf.charusters_nums, 12);
+        assert!(config.char_conf.gen_image);
+        assert_eq!(config.values_conf.names_file, "yaml:names.txt");
+        assert_eq!(config.values_conf.collection_name, "2024");
+        assert!(!config.values_conf.sanitation.trim);
+    }
+
+    #[test]
+    fn should_keep_the_values_of_string_keys_as_they_are() {
+        let mut value: Value = toml::from_str("[values_conf]\nnames_file = \"names\"").unwrap();
+        apply_env_overrides(&mut value, env_vars(&[
+            ("CHARUSTER_VALUES_CONF__NAMES_FILE", "1984"),
+            ("CHARUSTER_VALUES_CONF__SURNAMES_FILE", "true"),
+            ("CHARUSTER_VALUES_CONF__COLLECTION_NAME", "\"quoted\""),
+            ("CHARUSTER_EXECUTION_CONF__EXPORT_FORMAT", "csv"),
+            ("CHARUSTER_VALUES_CONF__BIRTHDATE_MIN_YEAR", "1950"),
+            ("CHARUSTER_VALUES_CONF__ALLOWED_PROFESSIONS", "[\"Cook\", \"Guard\"]"),
+        ])).unwrap();
+
+        let config: Config = value.try_into().unwrap();
+        assert_eq!(config.values_conf.names_file, "1984");
+        assert_eq!(config.values_conf.surnames_file, "true");
+        assert_eq!(config.values_conf.collection_name, "\"quoted\"");
+        assert_eq!(config.execution_conf.export_format, crate::export::ExportFormat::Csv);
+        assert_eq!(config.values_conf.birthdate_min_year, 1950);
+        assert_eq!(config.values_conf.allowed_professions, vec!["Cook", "Guard"]);
+    }
+
+    #[test]
+    fn should_ignore_environment_variables_outside_the_sections() {
+        let mut value: Value = toml::from_str("[execution_conf]\ncharusters_nums = 5").unwrap();
+        apply_env_overrides(&mut value, env_vars(&[("CHARUSTER_ATTRIBUTES__NAME", "luck"), ("CHARUSTER_FOO__BAR", "1")])).unwrap();
+        assert_eq!(value, toml::from_str("[execution_conf]\ncharusters_nums = 5").unwrap());
+
+        let error = env_override_key("CHARUSTER_ATTRIBUTES__NAME").unwrap().err().unwrap();
+        assert_eq!(error, "CHARUSTER_ATTRIBUTES__NAME does not override a key of execution_conf, char_conf or values_conf");
+        assert_eq!(env_override_key("CHARUSTER_VALUES_CONF__SANITATION__TRIM"), Some(Ok(String::from("values_conf.sanitation.trim"))));
+        assert_eq!(env_override_key("CHARUSTER_PROFILE"), None);
+    }
+
+    #[test]
+    fn should_print_the_effective_config_as_toml() {
+        let config = parse_config_with_profile(get_test_filename("test_config.toml").as_str(), None);
+        let printed = to_toml(&config);
+
+        let reparsed: Config = toml::from_str(&printed).unwrap();
+        assert_eq!(reparsed.values_conf.names_file, config.values_conf.names_file);
+        assert_eq!(reparsed.attributes.len(), 3);
+        assert_eq!(reparsed.attributes[2].max, Some(20));
+        assert!(printed.contains("[values_conf.sanitation]"));
+    }
+
+    #[test]
+    fn should_override_the_config_with_the_collections() {
+        let mut config = parse_config_with_profile(get_test_filename("test_collections_config.toml").as_str(), None);
+        config.execution_conf.export_to_json_file = String::from("output/charusters.ndjson");
+        let collections = config.collection_configs();
+
+        assert_eq!(collections.len(), 3);
+        assert_eq!(collections[0].values_conf.collection_name, "Zombies");
+        assert_eq!(collections[0].execution_conf.export_to_json_file, "output/Zombies/charusters.ndjson");
+        assert_eq!(collections[0].values_conf.names_file, get_test_filename("test_dictionary"));
+        assert_eq!(collections[1].values_conf.names_file, get_test_filename("not_existing"));
+        assert_eq!(collections[1].values_conf.surnames_file, get_test_filename("test_dictionary.csv"));
+        assert!(!collections[1].char_conf.gen_birthplace);
+        assert_eq!(collections[2].execution_conf.charusters_nums, 2);
+        assert_eq!(collections[2].values_conf.birthdate_min_year, 2020);
+        assert!(collections.iter().all(|collection| collection.collections.is_empty()));
+    }
+
+    #[test]
+    fn should_merge_the_values_of_the_archetypes() {
+        let config = parse_config_with_profile(get_test_filename("test_archetypes_config.toml").as_str(), None);
+
+        assert_eq!(config.archetypes.len(), 2);
+        assert_eq!(config.archetypes[0].weight, 3.0);
+        assert_eq!(config.archetypes[1].weight, 1.0);
+        assert_eq!(config.archetypes[0].features(), vec!["profession", "props", "stats"]);
+        assert_eq!(config.archetypes[1].features(), vec!["profession", "levels", "name"]);
+
+        let scholar = config.archetype_config(1).unwrap();
+        assert_eq!(scholar.values_conf.names_file, get_test_filename("test_dictionary"));
+        assert_eq!(scholar.values_conf.allowed_professions, vec!["Librarian"]);
+        assert_eq!(scholar.values_conf.levels_range, RangeConfig { min: 1, max: 10, distribution: Distribution::Uniform });
+        assert_eq!(scholar.values_conf.props_file, get_test_filename("test_two_levels_dictionary.json"));
+        assert!(scholar.archetypes.is_empty());
+    }
+
+    #[test]
+    fn should_resolve_the_bounds_of_the_quotas() {
+        let quota = |exactly: Option<QuotaAmount>, min: Option<QuotaAmount>, max: Option<QuotaAmount>| QuotaConfig {
+            feature: String::from("props"), value: String::from("Rabid"), exactly, min, max,
+        };
+        let share = |share: &str| Some(QuotaAmount::Share(share.to_owned()));
+
+        assert_eq!(quota(Some(QuotaAmount::Count(10)), None, None).bounds(50), Ok((10, 10)));
+        assert_eq!(quota(None, share("2.5%"), share("2.5%")).bounds(50), Err(String::from("min (2) is greater than max (1)")));
+        assert_eq!(quota(None, share("3%"), None).bounds(50), Ok((2, 50)));
+        assert_eq!(quota(None, None, share("3%")).bounds(50), Ok((0, 1)));
+        assert_eq!(quota(None, None, Some(QuotaAmount::Count(80))).bounds(50), Ok((0, 50)));
+        assert_eq!(quota(None, Some(QuotaAmount::Count(60)), None).bounds(50), Err(String::from("60 characters needed, the collection has 50")));
+        assert_eq!(quota(None, None, share("lots")).bounds(50), Err(String::from("lots is neither a number of characters nor a share like \"2%\"")));
+        assert!(quota(None, None, None).bounds(50).is_err());
+        assert!(quota(share("2%"), share("1%"), None).bounds(50).is_err());
     }
 }
@@ -0,0 +1,89 @@
+use std::collections::HashMap;
+
+/// order the features so that every one comes after the features it depends on, keeping their
+/// original order otherwise. Returns the indexes of the features in the new order.
+/// Panics when a feature depends on a feature that is not generated, or on a dependency cycle
+pub fn dependency_order(features: &[(&str, &[String])]) -> Vec<usize> {
+    let indexes: HashMap<&str, usize> = features.iter().enumerate().map(|(i, (name, _))| (*name, i)).collect();
+    let dependencies: Vec<Vec<usize>> = features.iter()
+        .map(|(name, deps)| deps.iter().map(|dep| *indexes.get(dep.as_str())
+            .unwrap_or_else(|| panic!("Feature {} depends on {}, which is not generated", name, dep)))
+            .collect())
+        .collect();
+
+    let mut placed = vec![false; features.len()];
+    let mut order = Vec::with_capacity(features.len());
+    while order.len() < features.len() {
+        let next = (0..features.len())
+            .find(|i| !placed[*i] && dependencies[*i].iter().all(|dep| placed[*dep]));
+        match next {
+            Some(i) => {
+                placed[i] = true;
+                order.push(i);
+            }
+            None => panic!("Cyclic dependency between features: {}", find_cycle(features, &dependencies, &placed)),
+        }
+    }
+    order
+}
+
+// every feature left has a dependency left, so following them from any of the features eventually
+// comes back to a feature already crossed
+fn find_cycle(features: &[(&str, &[String])], dependencies: &[Vec<usize>], placed: &[bool]) -> String {
+    let mut path = vec![placed.iter().position(|p| !p).unwrap()];
+    loop {
+        let current = *path.last().unwrap();
+        let dep = *dependencies[current].iter().find(|dep| !placed[**dep]).unwrap();
+        if let Some(start) = path.iter().position(|i| *i == dep) {
+            let names: Vec<&str> = path[start..].iter().chain([dep].iter()).map(|i| features[*i].0).collect();
+            return names.join(" -> ");
+        }
+        path.push(dep);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn deps(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn should_keep_the_order_of_independent_features() {
+        let none = deps(&[]);
+        assert_eq!(dependency_order(&[("name", &none), ("surname", &none), ("props", &none)]), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn should_order_features_after_their_dependencies() {
+        let none = deps(&[]);
+        let description = deps(&["name", "profession"]);
+        let nickname = deps(&["props"]);
+        let features = [("description", &description[..]), ("nickname", &nickname[..]), ("name", &none[..]),
+            ("profession", &none[..]), ("props", &none[..])];
+
+        assert_eq!(dependency_order(&features), vec![2, 3, 0, 4, 1]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Cyclic dependency between features: nickname -> description -> name -> nickname")]
+    fn should_panic_on_dependency_cycles() {
+        let none = deps(&[]);
+        let surname = deps(&["nickname"]);
+        let nickname = deps(&["description"]);
+        let description = deps(&["name"]);
+        let name = deps(&["nickname"]);
+        dependency_order(&[("surname", &surname), ("nickname", &nickname), ("props", &none),
+            ("description", &description), ("name", &name)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Feature description depends on profession, which is not generated")]
+    fn should_panic_on_dependencies_not_generated() {
+        let description = deps(&["profession"]);
+        dependency_order(&[("description", &description)]);
+    }
+}
@@ -1,81 +1,206 @@
 use std::{fs, io};
 use std::fs::File;
-use std::io::{BufRead, Read};
+use std::io::BufRead;
 use std::path::Path;
 
 use dyn_clone::DynClone;
 use rand::prelude::*;
 use serde::{Deserialize};
 
+use crate::config::{SanitationConfig, ValuesConfig};
+use crate::formats::{DictionaryFormat, read_structured_terms, read_taxonomies, Term};
+use crate::mapped::MappedDictionary;
+use crate::sanitation::{SanitationReport, sanitize_terms};
+use crate::syllables::{SYLLABLES_FILE_SUFFIX, SyllableDictionary};
+
 const ACCEPTED_IMAGE_FORMATS: [&'static str; 3] = ["jpg", "jpeg", "png"];
 
-pub trait Dictionary: DynClone {
-    fn choose_and_remove(&mut self) -> Option<String>;
-    fn choose(&self) -> Option<String>;
+/// separator between the elements of a taxonomy path when it's returned as a single term
+pub const TAXONOMY_PATH_SEPARATOR: &str = "+";
+
+pub trait Dictionary: DynClone + Send + Sync {
+    /// choose a term using the given random generator, remove it and return it
+    fn choose_and_remove_with(&mut self, rng: &mut dyn RngCore) -> Option<String>;
+    /// choose a term using the given random generator and return it
+    fn choose_with(&self, rng: &mut dyn RngCore) -> Option<String>;
+
+    fn choose_and_remove(&mut self) -> Option<String> {
+        self.choose_and_remove_with(&mut thread_rng())
+    }
+
+    fn choose(&self) -> Option<String> {
+        self.choose_with(&mut thread_rng())
+    }
+
+    /// choose a term, remove it and return it along with the categories it belongs to, outermost first.
+    /// Flat dictionaries return a single element path
+    fn choose_path_and_remove(&mut self, rng: &mut dyn RngCore) -> Option<Vec<String>> {
+        self.choose_and_remove_with(rng).map(|term| vec![term])
+    }
+
+    /// choose a term and return it along with the categories it belongs to, outermost first.
+    /// Flat dictionaries return a single element path
+    fn choose_path(&self, rng: &mut dyn RngCore) -> Option<Vec<String>> {
+        self.choose_with(rng).map(|term| vec![term])
+    }
+
+    /// choose up to `k` distinct terms without removing them from the dictionary.
+    /// The default implementation removes the terms from a clone of the whole dictionary
+    fn sample_distinct(&self, k: usize, rng: &mut dyn RngCore) -> Vec<String> {
+        let mut cloned = dyn_clone::clone_box(self);
+        (0..k).map_while(|_| cloned.choose_and_remove_with(rng)).collect()
+    }
+
+    /// like `sample_distinct`, returning the path of each term. Flat dictionaries return single element paths
+    fn sample_distinct_paths(&self, k: usize, rng: &mut dyn RngCore) -> Vec<Vec<String>> {
+        self.sample_distinct(k, rng).into_iter().map(|term| vec![term]).collect()
+    }
+}
+
+/// create a new dictionary from file, picking the implementation from the file name and the config:
+/// syllable inventories (`*.syllables.json`) build terms on the fly, plain text files are memory-mapped when
+/// `lazy_dictionaries` is set (only trimmed and without comments), any other file is read as a list of terms in the format given by its extension
+/// or explicit format key (see `DictionaryFormat`) and sanitized. The changes made to the terms are reported by
+/// `SimpleDictionary::new_sanitized`, by the validation of the config and by the lint of the dictionaries
+pub fn open_dictionary(filename: &str, values_conf: &ValuesConfig) -> Box<dyn Dictionary> {
+    if filename.ends_with(SYLLABLES_FILE_SUFFIX) {
+        return Box::new(SyllableDictionary::new(filename));
+    }
+
+    let (format, path) = DictionaryFormat::from_filename(filename);
+    if values_conf.lazy_dictionaries && format == DictionaryFormat::Text {
+        Box::new(MappedDictionary::new(path, &values_conf.sanitation))
+    } else {
+        Box::new(SimpleDictionary::new_sanitized(filename, &values_conf.sanitation).0)
+    }
 }
 
 // SimpleDictionary
 #[derive(Clone)]
 pub struct SimpleDictionary {
     name: String,
-    terms: Vec<String>,
+    terms: Vec<Term>,
+    // whether the terms have different weights, computed once so sampling doesn't scan all the terms
+    weighted: bool,
 }
 
 impl SimpleDictionary {
-    /// create a new dictionary from file
+    fn from_terms(name: String, terms: Vec<Term>) -> Self {
+        let weighted = terms.iter().any(|term| term.weight != terms[0].weight);
+        SimpleDictionary { name, terms, weighted }
+    }
+
+    /// create a new dictionary from file, sanitizing its terms with the default config
     pub fn new(filename: &str) -> Self {
-        let name = get_name_from_file(&filename);
-        let terms = read_dictionary(filename).expect(format!("Dictionary not found: {}", filename).as_str());
-        SimpleDictionary { name: name.unwrap().to_owned(), terms }
+        SimpleDictionary::new_sanitized(filename, &SanitationConfig::default()).0
+    }
+
+    /// create a new dictionary from file, sanitizing its terms and reporting what was changed
+    pub fn new_sanitized(filename: &str, sanitation: &SanitationConfig) -> (Self, SanitationReport) {
+        SimpleDictionary::try_new_sanitized(filename, sanitation).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// like `new_sanitized`, returning an error if the file can't be read or is not well-formatted
+    pub fn try_new_sanitized(filename: &str, sanitation: &SanitationConfig) -> Result<(Self, SanitationReport), String> {
+        let (format, path) = DictionaryFormat::from_filename(filename);
+        let name = get_name_from_file(path)?;
+        let terms = match format {
+            DictionaryFormat::Text => read_dictionary(path).map(Ok),
+            _ => read_structured_terms(path, format),
+        };
+        let terms = terms.ok_or_else(|| format!("Dictionary not found: {}", filename))?
+            .map_err(|e| format!("Dictionary {} can't be read: {}", filename, e))?;
+        let (terms, report) = sanitize_terms(filename, terms, sanitation, format == DictionaryFormat::Text);
+        Ok((SimpleDictionary::from_terms(name.to_owned(), terms), report))
     }
 
     /// create a new dictionary with a single term
     pub fn new_with_single_term(dict_name: String, term: String) -> Self {
-        let terms = vec!(term);
-        SimpleDictionary { name: dict_name, terms }
+        let terms = vec!(Term::new(term));
+        SimpleDictionary::from_terms(dict_name, terms)
+    }
+
+    /// create a new dictionary with the given terms
+    pub fn new_with_terms(dict_name: String, terms: Vec<String>) -> Self {
+        SimpleDictionary::from_terms(dict_name, terms.into_iter().map(Term::new).collect())
     }
 
     /// create a new dictionary from directory listing
     pub fn new_from_folder(dir: &str) -> Self {
-        let name = get_name_from_file(&dir);
-        let paths = fs::read_dir(dir).unwrap();
+        SimpleDictionary::try_new_from_folder(dir).unwrap_or_else(|e| panic!("{}", e))
+    }
 
-        let terms: Vec<String> = paths
-            .map(|dir_entry| dir_entry.unwrap().path())
-            .filter(|path| path.extension().is_some() &&
-                ACCEPTED_IMAGE_FORMATS.contains(&(path.extension().unwrap().to_str().unwrap())))
-            .map(|path| path.to_str().unwrap().to_owned())
-            .collect();
+    /// like `new_from_folder`, returning an error if the folder can't be listed
+    pub fn try_new_from_folder(dir: &str) -> Result<Self, String> {
+        let name = get_name_from_file(dir)?;
+        let paths = fs::read_dir(dir).map_err(|e| format!("Folder {} can't be read: {}", dir, e))?;
+
+        let mut terms: Vec<Term> = vec![];
+        for dir_entry in paths {
+            let path = dir_entry.map_err(|e| format!("Folder {} can't be read: {}", dir, e))?.path();
+            let accepted = path.extension().and_then(|ext| ext.to_str()).is_some_and(|ext| ACCEPTED_IMAGE_FORMATS.contains(&ext));
+            if accepted {
+                let path = path.to_str().ok_or_else(|| format!("File name is not valid unicode: {}", path.display()))?;
+                terms.push(Term::new(path.to_owned()));
+            }
+        }
+
+        Ok(SimpleDictionary::from_terms(name.to_owned(), terms))
+    }
+}
+
+impl SimpleDictionary {
+    pub fn terms(&self) -> &[Term] {
+        &self.terms
+    }
 
-        SimpleDictionary { name: name.unwrap().to_owned(), terms }
+    /// choose the index of a term, honoring the term weights when they are not all the same
+    fn choose_index(&self, rng: &mut dyn RngCore) -> Option<usize> {
+        if self.weighted {
+            let indexes: Vec<usize> = (0..self.terms.len()).collect();
+            indexes.choose_weighted(rng, |i| self.terms[*i].weight).ok().copied()
+        } else {
+            (0..self.terms.len()).choose(rng)
+        }
     }
 }
 
 impl Dictionary for SimpleDictionary {
     /// choose a term of the dictionary, remove it from the vector and return it
-    fn choose_and_remove(&mut self) -> Option<String> {
-        if self.terms.len() <= 0 {
-            ()
-        }
+    fn choose_and_remove_with(&mut self, rng: &mut dyn RngCore) -> Option<String> {
+        let i = self.choose_index(rng)?;
+        Some(self.terms.swap_remove(i).value)
+    }
 
-        let i = (0..self.terms.len()).choose(&mut thread_rng())?;
-        Some(self.terms.swap_remove(i))
+    /// choose a term of the dictionary and return it
+    fn choose_with(&self, rng: &mut dyn RngCore) -> Option<String> {
+        let i = self.choose_index(rng)?;
+        Some(self.terms[i].value.clone())
     }
 
-    /// choose a term of the dictionary, remove it from the vector and return it
-    fn choose(&self) -> Option<String> {
-        if self.terms.len() <= 0 {
-            ()
+    /// choose up to `k` distinct terms in O(k) (or O(n) when the terms are weighted), without copying the dictionary
+    fn sample_distinct(&self, k: usize, rng: &mut dyn RngCore) -> Vec<String> {
+        let k = k.min(self.terms.len());
+        if self.weighted {
+            self.terms.choose_multiple_weighted(rng, k, |term| term.weight)
+                .expect("Invalid term weights")
+                .map(|term| term.value.clone())
+                .collect()
+        } else {
+            rand::seq::index::sample(rng, self.terms.len(), k).into_iter()
+                .map(|i| self.terms[i].value.clone())
+                .collect()
         }
-
-        let value = self.terms.choose(&mut thread_rng())?;
-        Some(value.clone())
     }
 }
 
-// TwoLevelsDictionary
+// TaxonomyDictionary
+/// A dictionary of terms arranged in a tree of categories of any depth (category -> subcategory -> term).
+///
+/// Each node has a `kind`, its own `terms` and any number of nested `children` nodes, so the classic
+/// two levels file (`kind` -> `terms`) is just a tree of depth one.
 #[derive(Clone)]
-pub struct TwoLevelsDictionary {
+pub struct TaxonomyDictionary {
     name: String,
     taxonomies: Vec<Taxonomy>,
 }
@@ -83,53 +208,152 @@ pub struct TwoLevelsDictionary {
 #[derive(Debug, Deserialize, Clone)]
 struct Taxonomy {
     kind: String,
-    terms: Vec<String>
+    #[serde(default)]
+    terms: Vec<String>,
+    #[serde(default)]
+    children: Vec<Taxonomy>,
+}
+
+impl Taxonomy {
+    fn has_terms(&self) -> bool {
+        !self.terms.is_empty() || self.children.iter().any(|child| child.has_terms())
+    }
+
+    /// call `f` on this node and all its descendants, along with the path of their kinds
+    fn visit<F: FnMut(&[String], &Taxonomy)>(&self, path: &mut Vec<String>, f: &mut F) {
+        path.push(self.kind.clone());
+        f(path, self);
+        for child in self.children.iter() {
+            child.visit(path, f);
+        }
+        path.pop();
+    }
+
+    /// walk down the tree choosing among the terms and the non empty children of each node,
+    /// returning the kinds crossed followed by the chosen term
+    fn choose_path(&self, rng: &mut dyn RngCore) -> Option<Vec<String>> {
+        let children: Vec<&Taxonomy> = self.children.iter().filter(|child| child.has_terms()).collect();
+        let i = (0..self.terms.len() + children.len()).choose(rng)?;
+
+        let mut path = vec![self.kind.clone()];
+        if i < self.terms.len() {
+            path.push(self.terms[i].clone());
+        } else {
+            path.append(&mut children[i - self.terms.len()].choose_path(rng)?);
+        }
+        Some(path)
+    }
 }
 
-impl TwoLevelsDictionary {
+impl TaxonomyDictionary {
     /// create a new dictionary from file
     pub fn new(filename: &str) -> Self {
-        let name = get_name_from_file(&filename);
-        let terms = parse_two_levels_dictionary(filename).expect(format!("Dictionary not found: {}", filename).as_str());
-        TwoLevelsDictionary { name: name.unwrap().to_owned(), taxonomies: terms }
+        TaxonomyDictionary::try_new(filename).unwrap_or_else(|e| panic!("{}", e))
     }
-}
 
-impl Dictionary for TwoLevelsDictionary {
-    /// choose a term of the dictionary (term and subterm separated by a +), remove it from the vector and return it
-    fn choose_and_remove(&mut self) -> Option<String> {
-        if self.taxonomies.len() <= 0 {
-            ()
-        }
+    /// like `new`, returning an error if the file can't be read or is not well-formatted
+    pub fn try_new(filename: &str) -> Result<Self, String> {
+        let name = get_name_from_file(filename)?;
+        let taxonomies = parse_taxonomy_dictionary(filename).ok_or_else(|| format!("Dictionary not found: {}", filename))?
+            .map_err(|e| format!("Dictionary {} can't be read: {}", filename, e))?;
+        Ok(TaxonomyDictionary { name: name.to_owned(), taxonomies })
+    }
 
-        let kind_ind = (0..self.taxonomies.len()).choose(&mut thread_rng())?;
-        let mut taxonomy = self.taxonomies.swap_remove(kind_ind);
+    /// keep only the root categories of the given kinds
+    pub fn retain_kinds(&mut self, kinds: &[String]) {
+        self.taxonomies.retain(|taxonomy| kinds.contains(&taxonomy.kind));
+    }
 
-        let term_ind = (0..taxonomy.terms.len()).choose(&mut thread_rng())?;
-        let term = taxonomy.terms.swap_remove(term_ind);
+    // index of a root category containing at least a term, roots can share their kind
+    fn choose_root(&self, rng: &mut dyn RngCore) -> Option<usize> {
+        self.taxonomies.iter().enumerate()
+            .filter(|(_, taxonomy)| taxonomy.has_terms())
+            .choose(rng)
+            .map(|(i, _)| i)
+    }
 
-        Some(format!("{}+{}", taxonomy.kind, term))
+    /// number of root categories containing at least a term
+    pub fn kinds_with_terms(&self) -> usize {
+        self.taxonomies.iter().filter(|taxonomy| taxonomy.has_terms()).count()
     }
 
-    /// choose a term of the dictionary (term and subterm separated by a +), remove it from the vector and return it
-    fn choose(&self) -> Option<String> {
-        if self.taxonomies.len() <= 0 {
-            ()
+    /// paths of all the terms of the dictionary
+    pub fn term_paths(&self) -> Vec<Vec<String>> {
+        let mut paths = vec![];
+        for taxonomy in self.taxonomies.iter() {
+            taxonomy.visit(&mut vec![], &mut |path, node| {
+                for term in node.terms.iter() {
+                    let mut term_path = path.to_vec();
+                    term_path.push(term.clone());
+                    paths.push(term_path);
+                }
+            });
         }
+        paths
+    }
 
-        let taxonomy = self.taxonomies.choose(&mut thread_rng())?;
-        let term = taxonomy.terms.choose(&mut thread_rng())?;
+    /// paths of the categories without any term, nor in themselves nor in their children
+    pub fn empty_taxonomies(&self) -> Vec<Vec<String>> {
+        let mut paths = vec![];
+        for taxonomy in self.taxonomies.iter() {
+            taxonomy.visit(&mut vec![], &mut |path, node| {
+                if !node.has_terms() {
+                    paths.push(path.to_vec());
+                }
+            });
+        }
+        paths
+    }
+}
+
+impl Dictionary for TaxonomyDictionary {
+    /// choose a term of the dictionary (path elements separated by a +), remove its root category and return it
+    fn choose_and_remove_with(&mut self, rng: &mut dyn RngCore) -> Option<String> {
+        Some(self.choose_path_and_remove(rng)?.join(TAXONOMY_PATH_SEPARATOR))
+    }
+
+    /// choose a term of the dictionary (path elements separated by a +) and return it
+    fn choose_with(&self, rng: &mut dyn RngCore) -> Option<String> {
+        Some(self.choose_path(rng)?.join(TAXONOMY_PATH_SEPARATOR))
+    }
+
+    /// choose a term of the dictionary, remove its whole root category from the dictionary and return its path
+    fn choose_path_and_remove(&mut self, rng: &mut dyn RngCore) -> Option<Vec<String>> {
+        let root = self.choose_root(rng)?;
+        let path = self.taxonomies[root].choose_path(rng)?;
+        self.taxonomies.swap_remove(root);
+        Some(path)
+    }
+
+    /// choose a term of the dictionary and return its path
+    fn choose_path(&self, rng: &mut dyn RngCore) -> Option<Vec<String>> {
+        let root = self.choose_root(rng)?;
+        self.taxonomies[root].choose_path(rng)
+    }
 
-        Some(format!("{}+{}", taxonomy.kind, term))
+    /// choose up to `k` terms (path elements separated by a +) from distinct root categories
+    fn sample_distinct(&self, k: usize, rng: &mut dyn RngCore) -> Vec<String> {
+        self.sample_distinct_paths(k, rng).into_iter()
+            .map(|path| path.join(TAXONOMY_PATH_SEPARATOR))
+            .collect()
+    }
+
+    /// choose the paths of up to `k` terms from distinct root categories, like `choose_path_and_remove` would do
+    fn sample_distinct_paths(&self, k: usize, rng: &mut dyn RngCore) -> Vec<Vec<String>> {
+        let taxonomies: Vec<&Taxonomy> = self.taxonomies.iter().filter(|taxonomy| taxonomy.has_terms()).collect();
+        let k = k.min(taxonomies.len());
+        rand::seq::index::sample(rng, taxonomies.len(), k).into_iter()
+            .filter_map(|i| taxonomies[i].choose_path(rng))
+            .collect()
     }
 }
 
 
-fn read_dictionary(filename: &str) -> Option<Vec<String>> {
+fn read_dictionary(filename: &str) -> Option<Vec<Term>> {
     let lines = read_lines(filename);
     match lines {
         Ok(lines) => {
-            let terms = lines.map(|l| l.expect("Could not parse line"))
+            let terms = lines.map(|l| Term::new(l.expect("Could not parse line")))
                 .collect();
             Some(terms)
         },
@@ -139,12 +363,9 @@ fn read_dictionary(filename: &str) -> Option<Vec<String>> {
     }
 }
 
-fn parse_two_levels_dictionary(filename: &str) -> Option<Vec<Taxonomy>> {
-    let mut file = File::open(filename).unwrap();
-    let mut json = String::new();
-    file.read_to_string(&mut json).unwrap();
-    let terms: Vec<Taxonomy> = serde_json::from_str(json.as_str()).expect("JSON was not well-formatted");
-    Some(terms)
+fn parse_taxonomy_dictionary(filename: &str) -> Option<Result<Vec<Taxonomy>, String>> {
+    let (format, path) = DictionaryFormat::from_taxonomy_filename(filename);
+    read_taxonomies(path, format)
 }
 
 // The output is wrapped in a Result to allow matching on errors
@@ -155,10 +376,10 @@ fn read_lines<P>(filename: P) -> io::Result<io::Lines<io::BufReader<File>>>
     Ok(io::BufReader::new(file).lines())
 }
 
-fn get_name_from_file(filename: &str) -> Option<&str> {
+fn get_name_from_file(filename: &str) -> Result<&str, String> {
     Path::new(filename).file_name()
-        .expect(&format!("Trying to create dictionary from non existing file: {}", filename))
-        .to_str()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| format!("Trying to create dictionary from non existing file: {}", filename))
 }
 
 
@@ -166,6 +387,8 @@ fn get_name_from_file(filename: &str) -> Option<&str> {
 mod tests {
     use std::path::PathBuf;
 
+    use crate::config::parse_config;
+
     use super::*;
 
     const EXPECTED_TERMS: [&'static str; 3] = ["term_1", "term_2", "term_3"];
@@ -203,7 +426,7 @@ mod tests {
     fn should_read_terms_from_dictionary_file() {
         let terms = read_dictionary(get_test_dictionary_filename().as_str()).unwrap();
         for (i, term) in terms.iter().enumerate() {
-            assert_eq!(term, &EXPECTED_TERMS[i]);
+            assert_eq!(term.value, EXPECTED_TERMS[i]);
         }
     }
 
@@ -218,7 +441,7 @@ mod tests {
         let dict = SimpleDictionary::new(get_test_dictionary_filename().as_str());
         assert_eq!(dict.name, "test_dictionary");
         for (i, term) in dict.terms.iter().enumerate() {
-            assert_eq!(term, &EXPECTED_TERMS[i]);
+            assert_eq!(term.value, EXPECTED_TERMS[i]);
         }
     }
 
@@ -268,6 +491,21 @@ mod tests {
         SimpleDictionary::new("");
     }
 
+    #[test]
+    fn should_return_errors_for_unreadable_dictionaries() {
+        let not_existing = get_not_existing_dictionary_filename();
+        let error = SimpleDictionary::try_new_sanitized(not_existing.as_str(), &SanitationConfig::default()).err().unwrap();
+        assert_eq!(error, format!("Dictionary not found: {}", not_existing));
+
+        let error = SimpleDictionary::try_new_sanitized(&format!("json:{}", get_test_dictionary_filename()), &SanitationConfig::default()).err().unwrap();
+        assert!(error.contains("JSON was not well-formatted"));
+
+        let error = TaxonomyDictionary::try_new(get_test_dictionary_filename().as_str()).err().unwrap();
+        assert!(error.contains("JSON was not well-formatted"));
+
+        assert!(SimpleDictionary::try_new_from_folder(not_existing.as_str()).is_err());
+    }
+
     #[test]
     fn should_choose_a_random_term_and_remove_it_from_the_dictionary() {
         let mut dict = SimpleDictionary::new(get_test_dictionary_filename().as_str());
@@ -289,10 +527,7 @@ mod tests {
 
     #[test]
     fn should_return_none_when_choosing_from_empty_dictionary() {
-        let mut dict = SimpleDictionary{
-            name: String::from("My dic"),
-            terms: vec![]
-        };
+        let mut dict = SimpleDictionary::from_terms(String::from("My dic"), vec![]);
         assert!(dict.choose_and_remove().is_none());
     }
 
@@ -309,9 +544,138 @@ mod tests {
         assert_eq!(dict.terms.len(), 3);
     }
 
+    #[test]
+    fn should_sanitize_terms_while_creating_dictionary() {
+        let filename = get_dictionary_filename("test_dirty_dictionary");
+        let (dict, report) = SimpleDictionary::new_sanitized(filename.as_str(), &SanitationConfig::default());
+
+        let values: Vec<&str> = dict.terms.iter().map(|t| t.value.as_str()).collect();
+        assert_eq!(values, EXPECTED_TERMS);
+        assert_eq!(report.dictionary, filename);
+        assert_eq!(report.trimmed, 1);
+        assert_eq!(report.comments, 1);
+        assert_eq!(report.blanks, 1);
+        assert_eq!(report.duplicates, vec!["TERM_1"]);
+    }
+
+    #[test]
+    fn should_keep_dirty_terms_when_sanitation_is_disabled() {
+        let (dict, report) = SimpleDictionary::new_sanitized(get_dictionary_filename("test_dirty_dictionary").as_str(), &SanitationConfig::disabled());
+
+        let values: Vec<&str> = dict.terms.iter().map(|t| t.value.as_str()).collect();
+        assert_eq!(values, vec!["term_1", "  term_2  ", "# comment", "", "TERM_1", "term_3"]);
+        assert!(report.is_empty());
+    }
+
+    #[test]
+    fn should_create_dictionary_from_structured_files() {
+        for dict_name in ["test_dictionary.json", "test_dictionary.yaml", "test_dictionary.toml", "test_dictionary.csv"] {
+            let dict = SimpleDictionary::new(get_dictionary_filename(dict_name).as_str());
+            assert_eq!(dict.name, dict_name);
+            let values: Vec<&str> = dict.terms.iter().map(|t| t.value.as_str()).collect();
+            assert_eq!(values, EXPECTED_TERMS);
+            assert_eq!(dict.terms[1].weight, 3.0);
+            assert_eq!(dict.terms[1].tags, vec!["rare", "dark"]);
+            assert_eq!(dict.terms[1].locale, Some(String::from("it")));
+        }
+    }
+
+    #[test]
+    fn should_create_dictionary_with_explicit_format_key() {
+        let dict = SimpleDictionary::new(format!("yaml:{}", get_dictionary_filename("test_dictionary.yaml")).as_str());
+        assert_eq!(dict.name, "test_dictionary.yaml");
+        assert_eq!(dict.terms.len(), 3);
+        assert_eq!(dict.terms[2].locale, Some(String::from("en")));
+    }
+
+    #[test]
+    fn should_choose_terms_according_to_their_weights() {
+        let dict = SimpleDictionary::new(get_dictionary_filename("test_dictionary.json").as_str());
+        let mut terms = dict.terms.clone();
+        terms[0].weight = 0.0;
+        terms[2].weight = 0.0;
+        let mut dict = SimpleDictionary::from_terms(dict.name, terms);
+        for _ in 0..100 {
+            assert_eq!(dict.choose().unwrap(), "term_2");
+        }
+        assert_eq!(dict.choose_and_remove().unwrap(), "term_2");
+        assert_eq!(dict.terms.len(), 2);
+    }
+
+    fn get_test_values_conf() -> ValuesConfig {
+        parse_config(get_dictionary_filename("test_config.toml").as_str()).values_conf
+    }
+
+    #[test]
+    fn should_open_simple_dictionary() {
+        let dict = open_dictionary(get_test_dictionary_filename().as_str(), &get_test_values_conf());
+        let term = dict.choose().unwrap();
+        assert!(EXPECTED_TERMS.contains(&term.as_str()));
+    }
+
+    #[test]
+    fn should_open_lazy_dictionary() {
+        let mut values_conf = get_test_values_conf();
+        values_conf.lazy_dictionaries = true;
+        let mut dict = open_dictionary(get_test_dictionary_filename().as_str(), &values_conf);
+
+        let mut terms: Vec<String> = (0..3).map(|_| dict.choose_and_remove().unwrap()).collect();
+        terms.sort();
+        assert_eq!(terms, EXPECTED_TERMS);
+        assert!(dict.choose_and_remove().is_none());
+    }
+
+    #[test]
+    fn should_open_syllable_dictionary() {
+        let dict = open_dictionary(get_dictionary_filename("test_dictionary.syllables.json").as_str(), &get_test_values_conf());
+        let term = dict.choose().unwrap();
+        assert!(!EXPECTED_TERMS.contains(&term.as_str()));
+        assert!(term.chars().next().unwrap().is_uppercase());
+    }
+
+    #[test]
+    fn should_sample_distinct_terms_without_removing_them() {
+        let dict = SimpleDictionary::new(get_test_dictionary_filename().as_str());
+
+        for k in 0..=3 {
+            let mut terms = dict.sample_distinct(k, &mut thread_rng());
+            assert_eq!(terms.len(), k);
+            terms.sort();
+            terms.dedup();
+            assert_eq!(terms.len(), k);
+            assert!(terms.iter().all(|term| EXPECTED_TERMS.contains(&term.as_str())));
+        }
+        assert_eq!(dict.sample_distinct(10, &mut thread_rng()).len(), 3);
+        assert_eq!(dict.terms.len(), 3);
+    }
+
+    #[test]
+    fn should_sample_distinct_weighted_terms() {
+        let dict = SimpleDictionary::new(get_dictionary_filename("test_dictionary.json").as_str());
+        let mut terms = dict.terms.clone();
+        terms[0].weight = 100.0;
+        let dict = SimpleDictionary::from_terms(dict.name, terms);
+
+        let mut terms = dict.sample_distinct(3, &mut thread_rng());
+        terms.sort();
+        assert_eq!(terms, EXPECTED_TERMS);
+        assert_eq!(dict.sample_distinct(2, &mut thread_rng()).len(), 2);
+    }
+
+    #[test]
+    fn should_sample_distinct_terms_by_removing_them_from_a_clone() {
+        let dict = SyllableDictionary::new(get_dictionary_filename("test_dictionary.syllables.json").as_str());
+
+        let mut terms = dict.sample_distinct(20, &mut thread_rng());
+        assert_eq!(terms.len(), 20);
+        terms.sort();
+        terms.dedup();
+        assert_eq!(terms.len(), 20);
+    }
+
     #[test]
     fn should_parse_two_levels_dictionary() {
-        let taxonomies = parse_two_levels_dictionary(get_dictionary_filename("test_two_levels_dictionary.json").as_str()).unwrap();
+        let taxonomies = parse_taxonomy_dictionary(get_dictionary_filename("test_two_levels_dictionary.json").as_str()).unwrap().unwrap();
         assert_eq!(taxonomies.len(), 3);
 
         assert_eq!(taxonomies[0].kind, "term_1");
@@ -332,4 +696,131 @@ mod tests {
         assert_eq!(taxonomies[2].terms[1], "sub_term_3_2");
         assert_eq!(taxonomies[2].terms[2], "sub_term_3_3");
     }
+
+    #[test]
+    fn should_parse_two_levels_dictionary_from_yaml_toml_and_files_without_extension() {
+        for dict_name in ["test_two_levels_dictionary.yaml", "test_two_levels_dictionary.toml", "test_two_levels_dictionary"] {
+            let taxonomies = parse_taxonomy_dictionary(get_dictionary_filename(dict_name).as_str()).unwrap().unwrap();
+            assert_eq!(taxonomies.len(), 3);
+            assert_eq!(taxonomies[1].kind, "term_2");
+            assert_eq!(taxonomies[1].terms, vec!["sub_term_2_1", "sub_term_2_2", "sub_term_2_3"]);
+        }
+    }
+
+    #[test]
+    fn should_parse_nested_taxonomy_dictionary() {
+        let taxonomies = parse_taxonomy_dictionary(get_dictionary_filename("test_taxonomy_dictionary.json").as_str()).unwrap().unwrap();
+        assert_eq!(taxonomies.len(), 3);
+
+        assert_eq!(taxonomies[0].kind, "Weapons");
+        assert!(taxonomies[0].terms.is_empty());
+        assert_eq!(taxonomies[0].children.len(), 2);
+        assert_eq!(taxonomies[0].children[0].kind, "Blades");
+        assert_eq!(taxonomies[0].children[0].terms, vec!["Katana", "Dagger+1"]);
+        assert_eq!(taxonomies[0].children[1].kind, "Firearms");
+        assert_eq!(taxonomies[0].children[1].children[0].kind, "Rifles");
+        assert_eq!(taxonomies[0].children[1].children[0].terms, vec!["Sniper rifle"]);
+
+        assert_eq!(taxonomies[1].kind, "Armors");
+        assert_eq!(taxonomies[1].terms, vec!["Helmet"]);
+        assert!(taxonomies[1].children.is_empty());
+
+        assert_eq!(taxonomies[2].kind, "Empty");
+        assert!(!taxonomies[2].has_terms());
+    }
+
+    #[test]
+    fn should_choose_a_path_from_the_taxonomy_dictionary() {
+        let expected_paths = [
+            vec!["Weapons", "Blades", "Katana"],
+            vec!["Weapons", "Blades", "Dagger+1"],
+            vec!["Weapons", "Firearms", "Rifles", "Sniper rifle"],
+            vec!["Weapons", "Firearms", "Pistol"],
+            vec!["Armors", "Helmet"],
+        ];
+        let dict = TaxonomyDictionary::new(get_dictionary_filename("test_taxonomy_dictionary.json").as_str());
+
+        for _ in 0..100 {
+            let path = dict.choose_path(&mut thread_rng()).unwrap();
+            assert!(expected_paths.contains(&path.iter().map(|p| p.as_str()).collect()), "unexpected path {:?}", path);
+        }
+        assert_eq!(dict.taxonomies.len(), 3);
+    }
+
+    #[test]
+    fn should_choose_a_path_and_remove_its_root_category() {
+        let mut dict = TaxonomyDictionary::new(get_dictionary_filename("test_taxonomy_dictionary.json").as_str());
+
+        let path1 = dict.choose_path_and_remove(&mut thread_rng()).unwrap();
+        assert_eq!(dict.taxonomies.len(), 2);
+        let path2 = dict.choose_path_and_remove(&mut thread_rng()).unwrap();
+        assert_eq!(dict.taxonomies.len(), 1);
+        assert_ne!(path1[0], path2[0]);
+
+        assert!(dict.choose_path_and_remove(&mut thread_rng()).is_none());
+    }
+
+    #[test]
+    fn should_remove_the_root_category_of_the_chosen_term_among_roots_of_the_same_kind() {
+        let root = |term: &str| Taxonomy { kind: String::from("Weapons"), terms: vec![term.to_owned()], children: vec![] };
+        let mut dict = TaxonomyDictionary { name: String::from("weapons"), taxonomies: vec![root("Katana"), root("Bow")] };
+
+        let path = dict.choose_path_and_remove(&mut thread_rng()).unwrap();
+        assert_eq!(dict.taxonomies.len(), 1);
+        assert_ne!(dict.taxonomies[0].terms[0], path[1]);
+    }
+
+    #[test]
+    fn should_sample_paths_from_distinct_root_categories() {
+        let dict = TaxonomyDictionary::new(get_dictionary_filename("test_taxonomy_dictionary.json").as_str());
+
+        for _ in 0..20 {
+            let paths = dict.sample_distinct_paths(3, &mut thread_rng());
+            assert_eq!(paths.len(), 2);
+            assert_ne!(paths[0][0], paths[1][0]);
+        }
+        assert_eq!(dict.taxonomies.len(), 3);
+
+        let terms = dict.sample_distinct(1, &mut thread_rng());
+        assert_eq!(terms.len(), 1);
+        assert!(terms[0].starts_with("Weapons+") || terms[0] == "Armors+Helmet");
+    }
+
+    #[test]
+    fn should_list_term_paths_and_empty_taxonomies() {
+        let dict = TaxonomyDictionary::new(get_dictionary_filename("test_taxonomy_dictionary.json").as_str());
+
+        assert_eq!(dict.kinds_with_terms(), 2);
+        assert_eq!(dict.term_paths(), vec![
+            vec!["Weapons", "Blades", "Katana"],
+            vec!["Weapons", "Blades", "Dagger+1"],
+            vec!["Weapons", "Firearms", "Pistol"],
+            vec!["Weapons", "Firearms", "Rifles", "Sniper rifle"],
+            vec!["Armors", "Helmet"],
+        ]);
+        assert_eq!(dict.empty_taxonomies(), vec![
+            vec!["Weapons", "Firearms", "Cannons"],
+            vec!["Empty"],
+            vec!["Empty", "Nothing"],
+        ]);
+    }
+
+    #[test]
+    fn should_join_the_path_when_choosing_a_term_from_the_taxonomy_dictionary() {
+        let dict = TaxonomyDictionary::new(get_dictionary_filename("test_two_levels_dictionary.json").as_str());
+
+        let term = dict.choose().unwrap();
+        let taxonomy: Vec<&str> = term.split(TAXONOMY_PATH_SEPARATOR).collect();
+        assert_eq!(taxonomy.len(), 2);
+        assert!(taxonomy[1].starts_with(&format!("sub_{}", taxonomy[0])));
+    }
+
+    #[test]
+    fn should_return_single_element_path_from_simple_dictionary() {
+        let dict = SimpleDictionary::new(get_test_dictionary_filename().as_str());
+
+        let path = dict.choose_path(&mut thread_rng()).unwrap();
+        assert_eq!(path.len(), 1);
+        assert!(EXPECTED_TERMS.contains(&path[0].as_str()));
+    }
 }
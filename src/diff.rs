@@ -0,0 +1,234 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::character::Charuster;
+use crate::legendaries::terms;
+
+/// features whose values are counted by default when comparing the distributions of two collections
+pub const DISTRIBUTION_FEATURES: [&str; 5] = ["profession", "archetype", "collection", "hobbies", "props"];
+
+/// A feature of a character that differs between two collections
+#[derive(Serialize, Debug)]
+pub struct FieldChange {
+    pub feature: String,
+    pub old: Value,
+    pub new: Value,
+}
+
+/// The features of a character found in both collections that changed
+#[derive(Serialize, Debug)]
+pub struct CharusterDiff {
+    /// the index of the character, or the value of the key feature
+    pub key: String,
+    pub changes: Vec<FieldChange>,
+}
+
+/// How many characters have a value of a feature in each collection
+#[derive(Serialize, Debug)]
+pub struct DistributionDelta {
+    pub feature: String,
+    pub value: String,
+    pub old: usize,
+    pub new: usize,
+}
+
+/// The differences between two collections
+#[derive(Serialize, Debug, Default)]
+pub struct CollectionDiff {
+    /// keys of the characters only in the new collection
+    pub added: Vec<String>,
+    /// keys of the characters only in the old collection
+    pub removed: Vec<String>,
+    pub changed: Vec<CharusterDiff>,
+    pub unchanged: usize,
+    /// the values counted a different number of times in the two collections
+    pub distributions: Vec<DistributionDelta>,
+}
+
+impl CollectionDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+
+    /// a line per added, removed or changed feature and per distribution delta, followed by a summary line
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        for key in self.added.iter() {
+            text.push_str(&format!("+ {}\n", key));
+        }
+        for key in self.removed.iter() {
+            text.push_str(&format!("- {}\n", key));
+        }
+        for charuster in self.changed.iter() {
+            for change in charuster.changes.iter() {
+                text.push_str(&format!("~ {} {}: {} -> {}\n", charuster.key, change.feature, change.old, change.new));
+            }
+        }
+        for delta in self.distributions.iter() {
+            text.push_str(&format!("{} {}: {} -> {} ({:+})\n", delta.feature, delta.value, delta.old, delta.new,
+                                   delta.new as i64 - delta.old as i64));
+        }
+        text.push_str(&format!("{} added, {} removed, {} changed, {} unchanged\n",
+                               self.added.len(), self.removed.len(), self.changed.len(), self.unchanged));
+        text
+    }
+}
+
+/// compare two collections, matching their characters by index or, when `key` is given, by the value of
+/// that feature. The values of the `distribution_features` are counted in both collections, items by item
+/// for the vector features. Panics if a character has no key or shares it with another character
+pub fn diff_collections(old: &[Charuster], new: &[Charuster], key: Option<&str>, distribution_features: &[String]) -> CollectionDiff {
+    let old_fields = keyed_fields(old, key, "old");
+    let new_fields = keyed_fields(new, key, "new");
+    let new_index: HashMap<&str, &Map<String, Value>> = new_fields.iter().map(|(key, fields)| (key.as_str(), fields)).collect();
+
+    let mut diff = CollectionDiff::default();
+    for (key, fields) in old_fields.iter() {
+        match new_index.get(key.as_str()) {
+            None => diff.removed.push(key.clone()),
+            Some(new) => {
+                let changes = field_changes(fields, new);
+                if changes.is_empty() {
+                    diff.unchanged += 1;
+                } else {
+                    diff.changed.push(CharusterDiff { key: key.clone(), changes });
+                }
+            }
+        }
+    }
+    let old_keys: HashSet<&str> = old_fields.iter().map(|(key, _)| key.as_str()).collect();
+    diff.added = new_fields.iter().filter(|(key, _)| !old_keys.contains(key.as_str())).map(|(key, _)| key.clone()).collect();
+
+    for feature in distribution_features.iter() {
+        let old_counts = value_counts(&old_fields, feature);
+        let mut new_counts = value_counts(&new_fields, feature);
+        let mut deltas: BTreeMap<String, (usize, usize)> = BTreeMap::new();
+        for (value, count) in old_counts {
+            deltas.insert(value.clone(), (count, new_counts.remove(&value).unwrap_or(0)));
+        }
+        deltas.extend(new_counts.into_iter().map(|(value, count)| (value, (0, count))));
+        diff.distributions.extend(deltas.into_iter()
+            .filter(|(_, (old, new))| old != new)
+            .map(|(value, (old, new))| DistributionDelta { feature: feature.clone(), value, old, new }));
+    }
+    diff
+}
+
+// the features of each character, custom ones included, along with its key
+fn keyed_fields(charusters: &[Charuster], key: Option<&str>, collection: &str) -> Vec<(String, Map<String, Value>)> {
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    charusters.iter().enumerate().map(|(i, charuster)| {
+        let fields = fields_of(charuster);
+        let id = match key {
+            None => i.to_string(),
+            Some(feature) => match fields.get(feature) {
+                Some(Value::String(value)) if !value.is_empty() => value.clone(),
+                Some(value) if !value.is_null() && !value.is_string() => value.to_string(),
+                _ => panic!("Could not diff by {}, character {} of the {} collection has none", feature, i, collection),
+            },
+        };
+        if let Some(other) = seen.insert(id.clone(), i) {
+            panic!("Could not diff by {}, characters {} and {} of the {} collection share {}", key.unwrap_or("index"), other, i, collection, id);
+        }
+        (id, fields)
+    }).collect()
+}
+
+fn fields_of(charuster: &Charuster) -> Map<String, Value> {
+    let mut fields = match serde_json::to_value(charuster).unwrap() {
+        Value::Object(fields) => fields,
+        _ => unreachable!("characters are serialized as objects"),
+    };
+    if let Some(Value::Object(custom)) = fields.remove("custom") {
+        fields.extend(custom);
+    }
+    fields
+}
+
+// the features set in either character with different values, missing features are null
+fn field_changes(old: &Map<String, Value>, new: &Map<String, Value>) -> Vec<FieldChange> {
+    let features = old.keys().chain(new.keys().filter(|feature| !old.contains_key(*feature)));
+    features.filter_map(|feature| {
+        let (old, new) = (old.get(feature).unwrap_or(&Value::Null), new.get(feature).unwrap_or(&Value::Null));
+        (old != new).then(|| FieldChange { feature: feature.clone(), old: old.clone(), new: new.clone() })
+    }).collect()
+}
+
+// how many characters have each value of the feature
+fn value_counts(fields: &[(String, Map<String, Value>)], feature: &str) -> HashMap<String, usize> {
+    let mut counts = HashMap::new();
+    for value in fields.iter().filter_map(|(_, fields)| fields.get(feature)) {
+        for term in terms(value).into_iter().filter(|term| !term.is_empty()) {
+            *counts.entry(term).or_insert(0) += 1;
+        }
+    }
+    counts
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::character::Property;
+
+    use super::*;
+
+    fn charuster(name: &str, profession: &str, hobbies: &[&str]) -> Charuster {
+        let mut builder = Charuster::builder();
+        builder.name(name.to_owned());
+        builder.profession(profession.to_owned());
+        builder.hobbies(hobbies.iter().map(|hobby| hobby.to_string()).collect());
+        builder.props(vec![Property::from_path(vec![String::from("Weapons"), String::from("Katana")])]);
+        builder.build()
+    }
+
+    fn default_features() -> Vec<String> {
+        DISTRIBUTION_FEATURES.iter().map(|feature| feature.to_string()).collect()
+    }
+
+    #[test]
+    fn should_diff_the_characters_by_index() {
+        let old = vec![charuster("Ann", "Cook", &["chess"]), charuster("Bob", "Guard", &["poker"])];
+        let new = vec![charuster("Ann", "Cook", &["chess"]), charuster("Bob", "Cook", &["poker"]), charuster("Cid", "Cook", &[])];
+        let diff = diff_collections(&old, &new, None, &default_features());
+
+        assert_eq!((diff.added.clone(), diff.removed.len(), diff.unchanged), (vec![String::from("2")], 0, 1));
+        assert_eq!(diff.changed.len(), 1);
+        assert_eq!(diff.changed[0].key, "1");
+        assert_eq!(diff.changed[0].changes[0].feature, "profession");
+        assert_eq!(diff.changed[0].changes[0].new, "Cook");
+        let deltas: Vec<(&str, &str, usize, usize)> = diff.distributions.iter()
+            .map(|d| (d.feature.as_str(), d.value.as_str(), d.old, d.new))
+            .collect();
+        assert_eq!(deltas, vec![("profession", "Cook", 1, 3), ("profession", "Guard", 1, 0), ("props", "Katana", 2, 3)]);
+    }
+
+    #[test]
+    fn should_diff_the_characters_by_key() {
+        let old = vec![charuster("Ann", "Cook", &["chess"]), charuster("Bob", "Guard", &["poker"])];
+        let new = vec![charuster("Bob", "Guard", &["poker", "chess"]), charuster("Cid", "Cook", &["chess"])];
+        let diff = diff_collections(&old, &new, Some("name"), &[String::from("hobbies")]);
+
+        assert_eq!(diff.added, vec!["Cid"]);
+        assert_eq!(diff.removed, vec!["Ann"]);
+        assert_eq!(diff.changed[0].key, "Bob");
+        let delta = &diff.distributions[..];
+        assert_eq!((delta.len(), delta[0].value.as_str(), delta[0].old, delta[0].new), (1, "chess", 1, 2));
+        let text = diff.to_text();
+        assert!(text.contains("~ Bob hobbies: [\"poker\"] -> [\"poker\",\"chess\"]\n"));
+        assert!(text.ends_with("1 added, 1 removed, 1 changed, 0 unchanged\n"));
+    }
+
+    #[test]
+    #[should_panic(expected = "Could not diff by name, characters 0 and 1 of the new collection share Ann")]
+    fn should_panic_on_shared_keys() {
+        let old = vec![charuster("Ann", "Cook", &[])];
+        let new = vec![charuster("Ann", "Cook", &[]), charuster("Ann", "Guard", &[])];
+        diff_collections(&old, &new, Some("name"), &[]);
+    }
+}
@@ -0,0 +1,204 @@
+use std::borrow::Borrow;
+use std::fs::{create_dir_all, File};
+use std::io;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::character::Charuster;
+
+/// File formats the characters can be exported to. Every exporter writes each character as soon as it
+/// gets it, so a collection can be exported while it's generated without keeping it in memory
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    /// a JSON array of characters
+    #[default]
+    Json,
+    /// a JSON character per line
+    Ndjson,
+    /// a row per character, with a header row. Vector features are written as JSON cells
+    Csv,
+}
+
+impl ExportFormat {
+    /// the format of a file from its extension: `.json`, `.ndjson` (or `.jsonl`) and `.csv`
+    pub fn from_filename(filename: &str) -> Option<ExportFormat> {
+        match Path::new(filename).extension()?.to_str()? {
+            "json" => Some(ExportFormat::Json),
+            "ndjson" | "jsonl" => Some(ExportFormat::Ndjson),
+            "csv" => Some(ExportFormat::Csv),
+            _ => None,
+        }
+    }
+}
+
+/// write the characters to `writer` in the given format, returning how many were written
+pub fn export<I, W>(charusters: I, format: ExportFormat, writer: W) -> io::Result<usize>
+where I: IntoIterator, I::Item: Borrow<Charuster>, W: Write {
+    match format {
+        ExportFormat::Json => export_json(charusters, writer),
+        ExportFormat::Ndjson => export_ndjson(charusters, writer),
+        ExportFormat::Csv => export_csv(charusters, writer),
+    }
+}
+
+/// write the characters to a file in the given format, creating its folder if needed
+pub fn export_to_file<I>(charusters: I, format: ExportFormat, filename: &str) -> usize
+where I: IntoIterator, I::Item: Borrow<Charuster> {
+    if let Some(dir) = Path::new(filename).parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        create_dir_all(dir).unwrap_or_else(|e| panic!("Could not create export folder {}: {}", dir.display(), e));
+    }
+    let file = File::create(filename).unwrap_or_else(|e| panic!("Could not create export file {}: {}", filename, e));
+    export(charusters, format, BufWriter::new(file)).unwrap_or_else(|e| panic!("Export to {} failed: {}", filename, e))
+}
+
+fn export_json<I, W>(charusters: I, mut writer: W) -> io::Result<usize>
+where I: IntoIterator, I::Item: Borrow<Charuster>, W: Write {
+    let mut count = 0;
+    writer.write_all(b"[")?;
+    for charuster in charusters {
+        if count > 0 {
+            writer.write_all(b",")?;
+        }
+        serde_json::to_writer(&mut writer, charuster.borrow())?;
+        count += 1;
+    }
+    writer.write_all(b"]")?;
+    writer.flush()?;
+    Ok(count)
+}
+
+fn export_ndjson<I, W>(charusters: I, mut writer: W) -> io::Result<usize>
+where I: IntoIterator, I::Item: Borrow<Charuster>, W: Write {
+    let mut count = 0;
+    for charuster in charusters {
+        serde_json::to_writer(&mut writer, charuster.borrow())?;
+        writer.write_all(b"\n")?;
+        count += 1;
+    }
+    writer.flush()?;
+    Ok(count)
+}
+
+/// fields of the characters left out when they are empty, last in the CSV columns of every character
+const OPTIONAL_COLUMNS: [&str; 2] = ["archetype", "custom"];
+
+// the columns are the fields of the first character followed by the optional ones it doesn't have, the
+// cells of the fields a character doesn't have are empty; string fields are written as they are,
+// any other field as JSON so it can be read back
+fn export_csv<I, W>(charusters: I, writer: W) -> io::Result<usize>
+where I: IntoIterator, I::Item: Borrow<Charuster>, W: Write {
+    let mut csv_writer = csv::Writer::from_writer(writer);
+    let mut columns: Option<Vec<String>> = None;
+    let mut count = 0;
+    for charuster in charusters {
+        let fields = match serde_json::to_value(charuster.borrow())? {
+            Value::Object(fields) => fields,
+            _ => unreachable!("characters are serialized as objects"),
+        };
+        let columns = columns.get_or_insert_with(|| fields.keys()
+            .filter(|column| !OPTIONAL_COLUMNS.contains(&column.as_str()))
+            .cloned()
+            .chain(OPTIONAL_COLUMNS.iter().map(|column| column.to_string()))
+            .collect());
+        if count == 0 {
+            csv_writer.write_record(columns.iter())?;
+        }
+        let record = columns.iter().map(|column| match fields.get(column) {
+            None => String::new(),
+            Some(Value::String(value)) => value.clone(),
+            Some(value) => value.to_string(),
+        });
+        csv_writer.write_record(record)?;
+        count += 1;
+    }
+    csv_writer.flush()?;
+    Ok(count)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::character::{Level, Property};
+
+    use super::*;
+
+    fn get_test_charusters() -> Vec<Charuster> {
+        (0..3).map(|i| {
+            let mut builder = Charuster::builder();
+            builder.name(format!("Name {}", i));
+            builder.surname(String::from("Surname, \"quoted\""));
+            builder.hobbies(vec![String::from("chess"), String::from("poker")]);
+            builder.props(vec![Property::from_path(vec![String::from("Weapons"), String::from("Katana")])]);
+            builder.levels(vec![Level { name: String::from("Strength"), value: i, max_value: 100 }]);
+            builder.build()
+        }).collect()
+    }
+
+    fn export_to_string(charusters: &[Charuster], format: ExportFormat) -> String {
+        let mut output = vec![];
+        let count = export(charusters, format, &mut output).unwrap();
+        assert_eq!(count, charusters.len());
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn should_export_a_json_array() {
+        let charusters = get_test_charusters();
+        let json = export_to_string(&charusters, ExportFormat::Json);
+
+        assert_eq!(json, serde_json::to_string(&charusters).unwrap());
+        assert_eq!(export_to_string(&[], ExportFormat::Json), "[]");
+    }
+
+    #[test]
+    fn should_export_a_character_per_line_in_ndjson() {
+        let charusters = get_test_charusters();
+        let ndjson = export_to_string(&charusters, ExportFormat::Ndjson);
+
+        let lines: Vec<&str> = ndjson.lines().collect();
+        assert_eq!(lines.len(), 3);
+        let charuster: Charuster = serde_json::from_str(lines[1]).unwrap();
+        assert_eq!(charuster.name(), "Name 1");
+    }
+
+    #[test]
+    fn should_export_csv_readable_back() {
+        let charusters = get_test_charusters();
+        let csv = export_to_string(&charusters, ExportFormat::Csv);
+
+        let mut reader = csv::Reader::from_reader(csv.as_bytes());
+        let headers = reader.headers().unwrap().clone();
+        let records: Vec<csv::StringRecord> = reader.records().map(|r| r.unwrap()).collect();
+        assert_eq!(records.len(), 3);
+
+        let column = |name: &str| headers.iter().position(|h| h == name).unwrap();
+        assert_eq!(&records[2][column("name")], "Name 2");
+        assert_eq!(&records[2][column("surname")], "Surname, \"quoted\"");
+        let hobbies: Vec<String> = serde_json::from_str(&records[2][column("hobbies")]).unwrap();
+        assert_eq!(hobbies, vec!["chess", "poker"]);
+        let levels: Vec<Level> = serde_json::from_str(&records[2][column("levels")]).unwrap();
+        assert_eq!(levels[0].value, 2);
+    }
+
+    #[test]
+    fn should_pick_the_format_from_the_extension() {
+        assert_eq!(ExportFormat::from_filename("output/charusters.jsonl"), Some(ExportFormat::Ndjson));
+        assert_eq!(ExportFormat::from_filename("charusters.csv"), Some(ExportFormat::Csv));
+        assert_eq!(ExportFormat::from_filename("charusters"), None);
+    }
+
+    #[test]
+    fn should_create_the_export_folder() {
+        let dir = std::env::temp_dir().join(format!("charuster_export_{}", std::process::id()));
+        let filename = dir.join("nested/charusters.ndjson");
+        let count = export_to_file(get_test_charusters(), ExportFormat::Ndjson, filename.to_str().unwrap());
+
+        assert_eq!(count, 3);
+        assert_eq!(std::fs::read_to_string(&filename).unwrap().lines().count(), 3);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,273 @@
+use std::fs::read_to_string;
+use std::path::Path;
+
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+
+/// Supported dictionary file formats.
+///
+/// The format is taken from the file extension, unless the file name starts with an explicit
+/// format key followed by a colon (e.g. `yaml:resources/names.txt`).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DictionaryFormat {
+    /// one term per line
+    Text,
+    /// an array of terms
+    Json,
+    /// a list of terms
+    Yaml,
+    /// a `terms` array (or array of tables)
+    Toml,
+    /// a column of terms, with a header row
+    Csv,
+}
+
+impl DictionaryFormat {
+    fn from_key(key: &str) -> Option<Self> {
+        match key.to_lowercase().as_str() {
+            "txt" | "text" => Some(DictionaryFormat::Text),
+            "json" => Some(DictionaryFormat::Json),
+            "yaml" | "yml" => Some(DictionaryFormat::Yaml),
+            "toml" => Some(DictionaryFormat::Toml),
+            "csv" => Some(DictionaryFormat::Csv),
+            _ => None,
+        }
+    }
+
+    /// split a dictionary file name into its format and the actual path of the file
+    pub fn from_filename(filename: &str) -> (Self, &str) {
+        DictionaryFormat::from_filename_or(filename, DictionaryFormat::Text)
+    }
+
+    /// like `from_filename` for taxonomy dictionaries, which are JSON files unless told otherwise
+    pub fn from_taxonomy_filename(filename: &str) -> (Self, &str) {
+        DictionaryFormat::from_filename_or(filename, DictionaryFormat::Json)
+    }
+
+    fn from_filename_or(filename: &str, default: DictionaryFormat) -> (Self, &str) {
+        if let Some((key, path)) = filename.split_once(':') {
+            if let Some(format) = DictionaryFormat::from_key(key) {
+                return (format, path);
+            }
+        }
+        let format = Path::new(filename).extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(DictionaryFormat::from_key)
+            .unwrap_or(default);
+        (format, filename)
+    }
+}
+
+/// A dictionary term with the metadata carried by the richer formats
+#[derive(Debug, Clone, PartialEq)]
+pub struct Term {
+    pub value: String,
+    /// relative probability of the term being chosen
+    pub weight: f64,
+    pub tags: Vec<String>,
+    pub locale: Option<String>,
+}
+
+impl Term {
+    /// create a term without metadata
+    pub fn new(value: String) -> Self {
+        Term { value, weight: 1.0, tags: vec![], locale: None }
+    }
+}
+
+// a term as written in the structured formats: a plain string or a table with its metadata
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawTerm {
+    Plain(String),
+    Detailed {
+        #[serde(alias = "term")]
+        value: String,
+        #[serde(default = "default_weight")]
+        weight: f64,
+        #[serde(default)]
+        tags: Vec<String>,
+        #[serde(default)]
+        locale: Option<String>,
+    },
+}
+
+fn default_weight() -> f64 {
+    1.0
+}
+
+impl From<RawTerm> for Term {
+    fn from(raw: RawTerm) -> Self {
+        match raw {
+            RawTerm::Plain(value) => Term::new(value),
+            RawTerm::Detailed { value, weight, tags, locale } => Term { value, weight, tags, locale },
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct TomlTerms {
+    terms: Vec<RawTerm>,
+}
+
+#[derive(Deserialize)]
+struct TomlTaxonomies<T> {
+    taxonomies: Vec<T>,
+}
+
+/// read the terms of a structured dictionary file in the given format. Returns `None` if the file can't be read
+/// and an error if its content is not well-formatted
+pub fn read_structured_terms(filename: &str, format: DictionaryFormat) -> Option<Result<Vec<Term>, String>> {
+    let content = read_to_string(filename).ok()?;
+    let raw_terms: Result<Vec<RawTerm>, String> = match format {
+        DictionaryFormat::Text => panic!("Text dictionaries are not structured: {}", filename),
+        DictionaryFormat::Json => serde_json::from_str(&content).map_err(|e| format!("JSON was not well-formatted: {}", e)),
+        DictionaryFormat::Yaml => serde_yaml::from_str(&content).map_err(|e| format!("YAML was not well-formatted: {}", e)),
+        DictionaryFormat::Toml => toml::from_str::<TomlTerms>(&content).map(|toml| toml.terms)
+            .map_err(|e| format!("TOML was not well-formatted: {}", e)),
+        DictionaryFormat::Csv => return Some(read_csv_terms(&content)),
+    };
+    Some(raw_terms.map(|raw_terms| raw_terms.into_iter().map(Term::from).collect()))
+}
+
+/// read a list of taxonomies in the given format; TOML files keep them in a `taxonomies` array of tables.
+/// Returns `None` if the file can't be read and an error if its content is not well-formatted
+pub fn read_taxonomies<T: DeserializeOwned>(filename: &str, format: DictionaryFormat) -> Option<Result<Vec<T>, String>> {
+    let content = read_to_string(filename).ok()?;
+    let taxonomies = match format {
+        DictionaryFormat::Json => serde_json::from_str(&content).map_err(|e| format!("JSON was not well-formatted: {}", e)),
+        DictionaryFormat::Yaml => serde_yaml::from_str(&content).map_err(|e| format!("YAML was not well-formatted: {}", e)),
+        DictionaryFormat::Toml => toml::from_str::<TomlTaxonomies<T>>(&content).map(|toml| toml.taxonomies)
+            .map_err(|e| format!("TOML was not well-formatted: {}", e)),
+        _ => Err(format!("Taxonomy dictionaries can't be read from {:?} files: {}", format, filename)),
+    };
+    Some(taxonomies)
+}
+
+// the terms are in the `value` (or `term`) column, or in the first one if there is no such column.
+// Optional `weight`, `tags` (separated by `;`) and `locale` columns carry the metadata
+fn read_csv_terms(content: &str) -> Result<Vec<Term>, String> {
+    let mut reader = csv::ReaderBuilder::new().flexible(true).from_reader(content.as_bytes());
+    let headers = reader.headers().map_err(|e| format!("CSV was not well-formatted: {}", e))?.clone();
+    let column = |name: &str| headers.iter().position(|h| h.trim().eq_ignore_ascii_case(name));
+    let value_col = column("value").or_else(|| column("term")).unwrap_or(0);
+    let weight_col = column("weight");
+    let tags_col = column("tags");
+    let locale_col = column("locale");
+
+    let mut terms = vec![];
+    for record in reader.records() {
+        let record = record.map_err(|e| format!("CSV was not well-formatted: {}", e))?;
+        let field = |col: Option<usize>| col.and_then(|c| record.get(c)).map(str::trim).filter(|f| !f.is_empty());
+        let Some(value) = record.get(value_col) else { continue };
+        let mut term = Term::new(value.to_owned());
+        if let Some(weight) = field(weight_col) {
+            term.weight = weight.parse().map_err(|_| format!("CSV term weight is not a number: {}", weight))?;
+        }
+        if let Some(tags) = field(tags_col) {
+            term.tags = tags.split(';').map(|tag| tag.trim().to_owned()).collect();
+        }
+        term.locale = field(locale_col).map(str::to_owned);
+        terms.push(term);
+    }
+    Ok(terms)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    const EXPECTED_TERMS: [&str; 3] = ["term_1", "term_2", "term_3"];
+
+    fn get_dictionary_filename(dict_name: &str) -> String {
+        let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        d.push("resources/test/".to_owned() + dict_name);
+        d.into_os_string().into_string().unwrap()
+    }
+
+    fn assert_expected_terms_with_metadata(terms: &[Term]) {
+        let values: Vec<&str> = terms.iter().map(|t| t.value.as_str()).collect();
+        assert_eq!(values, EXPECTED_TERMS);
+
+        assert_eq!(terms[0].weight, 1.0);
+        assert!(terms[0].tags.is_empty());
+        assert_eq!(terms[0].locale, None);
+
+        assert_eq!(terms[1].weight, 3.0);
+        assert_eq!(terms[1].tags, vec!["rare", "dark"]);
+        assert_eq!(terms[1].locale, Some(String::from("it")));
+
+        assert_eq!(terms[2].weight, 0.5);
+        assert!(terms[2].tags.is_empty());
+        assert_eq!(terms[2].locale, Some(String::from("en")));
+    }
+
+    #[test]
+    fn should_detect_format_from_extension() {
+        assert_eq!(DictionaryFormat::from_filename("resources/names"), (DictionaryFormat::Text, "resources/names"));
+        assert_eq!(DictionaryFormat::from_filename("names.txt"), (DictionaryFormat::Text, "names.txt"));
+        assert_eq!(DictionaryFormat::from_filename("names.json"), (DictionaryFormat::Json, "names.json"));
+        assert_eq!(DictionaryFormat::from_filename("names.yml"), (DictionaryFormat::Yaml, "names.yml"));
+        assert_eq!(DictionaryFormat::from_filename("names.YAML"), (DictionaryFormat::Yaml, "names.YAML"));
+        assert_eq!(DictionaryFormat::from_filename("names.toml"), (DictionaryFormat::Toml, "names.toml"));
+        assert_eq!(DictionaryFormat::from_filename("names.csv"), (DictionaryFormat::Csv, "names.csv"));
+    }
+
+    #[test]
+    fn should_read_taxonomies_without_extension_as_json() {
+        assert_eq!(DictionaryFormat::from_taxonomy_filename("resources/props"), (DictionaryFormat::Json, "resources/props"));
+        assert_eq!(DictionaryFormat::from_taxonomy_filename("props.yml"), (DictionaryFormat::Yaml, "props.yml"));
+        assert_eq!(DictionaryFormat::from_taxonomy_filename("txt:props"), (DictionaryFormat::Text, "props"));
+    }
+
+    #[test]
+    fn should_detect_explicit_format_key() {
+        assert_eq!(DictionaryFormat::from_filename("yaml:resources/names"), (DictionaryFormat::Yaml, "resources/names"));
+        assert_eq!(DictionaryFormat::from_filename("csv:names.txt"), (DictionaryFormat::Csv, "names.txt"));
+        assert_eq!(DictionaryFormat::from_filename("other:names.txt"), (DictionaryFormat::Text, "other:names.txt"));
+    }
+
+    #[test]
+    fn should_read_json_terms() {
+        let terms = read_structured_terms(get_dictionary_filename("test_dictionary.json").as_str(), DictionaryFormat::Json).unwrap().unwrap();
+        assert_expected_terms_with_metadata(&terms);
+    }
+
+    #[test]
+    fn should_read_yaml_terms() {
+        let terms = read_structured_terms(get_dictionary_filename("test_dictionary.yaml").as_str(), DictionaryFormat::Yaml).unwrap().unwrap();
+        assert_expected_terms_with_metadata(&terms);
+    }
+
+    #[test]
+    fn should_read_toml_terms() {
+        let terms = read_structured_terms(get_dictionary_filename("test_dictionary.toml").as_str(), DictionaryFormat::Toml).unwrap().unwrap();
+        assert_expected_terms_with_metadata(&terms);
+    }
+
+    #[test]
+    fn should_read_csv_terms() {
+        let terms = read_structured_terms(get_dictionary_filename("test_dictionary.csv").as_str(), DictionaryFormat::Csv).unwrap().unwrap();
+        assert_expected_terms_with_metadata(&terms);
+    }
+
+    #[test]
+    fn should_return_none_when_reading_terms_from_not_existing_file() {
+        assert!(read_structured_terms("resources/test/not_existing.json", DictionaryFormat::Json).is_none());
+    }
+
+    #[test]
+    fn should_fail_to_read_taxonomies_from_csv() {
+        let taxonomies = read_taxonomies::<String>(get_dictionary_filename("test_dictionary.csv").as_str(), DictionaryFormat::Csv);
+        assert!(taxonomies.unwrap().unwrap_err().starts_with("Taxonomy dictionaries can't be read from Csv files"));
+    }
+
+    #[test]
+    fn should_fail_to_read_terms_that_are_not_well_formatted() {
+        let terms = read_structured_terms(get_dictionary_filename("test_dictionary.csv").as_str(), DictionaryFormat::Json);
+        assert!(terms.unwrap().unwrap_err().starts_with("JSON was not well-formatted"));
+    }
+}
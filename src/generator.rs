@@ -1,18 +1,115 @@
-use std::fs::OpenOptions;
-use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::{Arc, RwLock};
 
-use chrono::{Datelike, DateTime, Duration, NaiveTime, TimeZone, Utc};
-use rand::Rng;
+use chrono::{Datelike, DateTime, Duration, NaiveDate, NaiveTime, TimeZone, Utc};
+use rand::prelude::*;
+use rand::distributions::WeightedIndex;
+use rand::rngs::StdRng;
+use rand_distr::{Distribution as _, Normal};
+use rayon::prelude::*;
+use serde_json::Value;
 
 use crate::character;
 use crate::character::{CharacterBuilder, CharacterFeature, Charuster, Level, Property, Stat};
-use crate::config::{Config, parse_local_config};
-use crate::dictionary::{Dictionary, SimpleDictionary, TwoLevelsDictionary};
+use crate::config::{AttributeKind, Config, Distribution, parse_local_config, ValuesConfig};
+use crate::dictionary::{Dictionary, open_dictionary, SimpleDictionary, TaxonomyDictionary};
+use crate::dependencies::dependency_order;
+use crate::export::export_to_file;
+use crate::import::import_from_file;
+use crate::legendaries::{features_of, lock_features, LockedFeatures, place_legendaries};
+use crate::quotas::apply_quotas;
+use crate::templates::template_config;
 
-type FnCharFeatPropCreator = Box<dyn Fn(String) -> Option<character::CharacterFeature>>;
-type FnCharFeatVecPropCreator = Box<dyn Fn(Vec<String>) -> Option<character::CharacterFeature>>;
-type FnCharFeatVecQuirkCreator<T> = Box<dyn Fn(Vec<T>) -> Option<character::CharacterFeature>>;
-type FnQuirkCreator<T> = Box<dyn Fn(&mut dyn Dictionary) -> T>;
+pub type FnCharFeatPropCreator = Box<dyn Fn(String) -> Option<character::CharacterFeature> + Send + Sync>;
+pub type FnCharFeatVecPropCreator = Box<dyn Fn(Vec<String>) -> Option<character::CharacterFeature> + Send + Sync>;
+pub type FnCharFeatVecQuirkCreator<T> = Box<dyn Fn(Vec<T>) -> Option<character::CharacterFeature> + Send + Sync>;
+pub type FnCharFeatNumberCreator = Box<dyn Fn(i64) -> Option<character::CharacterFeature> + Send + Sync>;
+pub type FnQuirkCreator<T> = Box<dyn Fn(Vec<String>, &mut dyn RngCore) -> T + Send + Sync>;
+
+/// the generators of a feature for each archetype overriding it, along with the name of the archetype
+pub type ArchetypeVariants = Vec<(String, Box<dyn FeatureGenerator>)>;
+
+/// the archetype of each character, drawn before the other features are prepared
+pub type ArchetypeAssignment = Arc<RwLock<Vec<String>>>;
+
+/// creates a custom feature generator from the config
+pub type FnGeneratorFactory = Box<dyn Fn(&Config) -> Box<dyn FeatureGenerator> + Send + Sync>;
+
+/// number of items generated for each vector feature (hobbies, props, levels and stats)
+pub const VEC_FEATURES_SIZE: u8 = 3;
+
+/// features the description is generated after, when they are generated, so its placeholders can be filled
+const DESCRIPTION_DEPENDENCIES: [&str; 4] = ["name", "surname", "nickname", "profession"];
+
+/// Custom feature generators available to the generation, by name.
+///
+/// Registered generators are only used when their name is listed in the `custom_features` of the
+/// `char_conf` section; they run after the built-in ones, in the order they are listed, unless
+/// their dependencies require otherwise.
+#[derive(Default)]
+pub struct GeneratorRegistry {
+    factories: HashMap<String, FnGeneratorFactory>,
+}
+
+impl GeneratorRegistry {
+    pub fn new() -> Self {
+        GeneratorRegistry::default()
+    }
+
+    /// register a custom generator, replacing any generator registered with the same name
+    pub fn register<F>(&mut self, name: &str, factory: F) -> &mut Self
+    where F: Fn(&Config) -> Box<dyn FeatureGenerator> + Send + Sync + 'static {
+        self.factories.insert(name.to_owned(), Box::new(factory));
+        self
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.factories.contains_key(name)
+    }
+
+    /// create the built-in generators enabled in the config and the custom ones, each one after the
+    /// features it depends on
+    pub fn create_generators(&self, config: &Config) -> Vec<NamedGenerator> {
+        let mut generators = create_generators(config);
+        for name in config.char_conf.custom_features.iter() {
+            let factory = self.factories.get(name)
+                .unwrap_or_else(|| panic!("Custom feature {} is not registered", name));
+            generators.push(NamedGenerator::new(name, factory(config)));
+        }
+        order_generators(generators, &config.char_conf.dependencies)
+    }
+}
+
+/// A generator along with the name of the feature it generates and the features it needs
+pub struct NamedGenerator {
+    pub name: String,
+    /// features generated before this one
+    pub dependencies: Vec<String>,
+    pub generator: Box<dyn FeatureGenerator>,
+}
+
+impl NamedGenerator {
+    /// name a generator, taking the dependencies it declares
+    pub fn new(name: &str, generator: Box<dyn FeatureGenerator>) -> Self {
+        NamedGenerator { name: name.to_owned(), dependencies: generator.dependencies(), generator }
+    }
+}
+
+/// sort the generators so that every feature is generated after the features it depends on. The
+/// dependencies set in the config replace the ones of the generators
+pub fn order_generators(mut generators: Vec<NamedGenerator>, dependencies: &HashMap<String, Vec<String>>) -> Vec<NamedGenerator> {
+    for gen in generators.iter_mut() {
+        if let Some(deps) = dependencies.get(&gen.name) {
+            gen.dependencies = deps.clone();
+        }
+    }
+    let features: Vec<(&str, &[String])> = generators.iter().map(|gen| (gen.name.as_str(), &gen.dependencies[..])).collect();
+    let order = dependency_order(&features);
+
+    let mut generators: Vec<Option<NamedGenerator>> = generators.into_iter().map(Some).collect();
+    order.into_iter().map(|i| generators[i].take().unwrap()).collect()
+}
 
 pub fn generate_charusters(conf: Option<Config>) -> Vec<Charuster> {
 
@@ -21,63 +118,232 @@ pub fn generate_charusters(conf: Option<Config>) -> Vec<Charuster> {
         Some(c) => c
     };
 
-    let mut charusters = vec![];
-    let mut generators = create_generators(&config);
-    let char_len = config.execution_conf.charusters_nums;
-    for _ in (0..char_len).into_iter() {
-        let mut builder = CharacterBuilder::new();
-        let gen_len = generators.len();
-        for i in (0..gen_len).into_iter() {
-            let gen = generators.get_mut(i).unwrap();
-            let char_feature = gen.generate().unwrap();
-            match char_feature {
-                CharacterFeature::NAME(value) => &builder.name(value),
-                CharacterFeature::SURNAME(value) => &builder.surname(value),
-                CharacterFeature::NICKNAME(value) => &builder.nickname(value),
-                CharacterFeature::BIRTHDATE(value) => &builder.birthdate(value),
-                CharacterFeature::BIRTHPLACE(value) => &builder.birthplace(value),
-                CharacterFeature::DESCRIPTION(value) => &builder.description(value),
-                CharacterFeature::IMAGE(value) => &builder.image(value),
-                CharacterFeature::COLLECTION(value) => &builder.collection(value),
-                CharacterFeature::PROFESSION(value) => &builder.profession(value),
-                CharacterFeature::HOBBIES(values) => &builder.hobbies(values),
-                CharacterFeature::PROPS(values) => &builder.props(values),
-                CharacterFeature::LEVELS(values) => &builder.levels(values),
-                CharacterFeature::STATS(values) => &builder.stats(values),
-            };
-        }
-        let charuster = builder.build();
-        charusters.push(charuster);
-    }
+    generate_charusters_with(config, &GeneratorRegistry::new())
+}
+
+/// like `generate_charusters`, also running the custom generators of the registry enabled in the config.
+/// When the config appends to its export file, only the new characters are returned
+pub fn generate_charusters_with(config: Config, registry: &GeneratorRegistry) -> Vec<Charuster> {
+    let existing = existing_charusters(&config);
+    let charusters: Vec<Charuster> = CharusterStream::appending(&config, registry, &existing).collect();
 
     if config.execution_conf.export_to_json {
-        export_to_json(&charusters, config.execution_conf.export_to_json_file.as_str());
+        export_to_file(existing.iter().chain(charusters.iter()), config.execution_conf.export_format,
+                       config.execution_conf.export_to_json_file.as_str());
     }
 
     charusters
 }
 
+/// generate the characters of the config completing a partially filled character: the features set in the
+/// template are kept as they are, only the other ones are generated
+pub fn generate_from_template(config: &Config, template: &CharacterBuilder) -> Vec<Charuster> {
+    CharusterStream::with_template(config, &GeneratorRegistry::new(), &template.features()).collect()
+}
+
+/// generate the characters described by the config and write them to the export file as they are
+/// generated, without keeping the collection in memory. Returns the number of exported characters
+pub fn export_charusters(config: &Config) -> usize {
+    export_charusters_with(config, &GeneratorRegistry::new())
+}
+
+/// like `export_charusters`, also running the custom generators of the registry enabled in the config.
+/// When the config appends to its export file, the characters already in it are written first
+pub fn export_charusters_with(config: &Config, registry: &GeneratorRegistry) -> usize {
+    let existing = existing_charusters(config);
+    let stream = CharusterStream::appending(config, registry, &existing);
+    export_to_file(existing.into_iter().chain(stream), config.execution_conf.export_format,
+                   config.execution_conf.export_to_json_file.as_str())
+}
+
+/// the characters already exported to the export file when the config appends to it, none otherwise
+pub fn existing_charusters(config: &Config) -> Vec<Charuster> {
+    let execution_conf = &config.execution_conf;
+    if !execution_conf.append || !execution_conf.export_to_json || !Path::new(&execution_conf.export_to_json_file).exists() {
+        return vec![];
+    }
+    import_from_file(&execution_conf.export_to_json_file, execution_conf.export_format)
+}
+
+/// times a term is drawn again when it's excluded, before keeping it
+const EXCLUDED_ATTEMPTS: usize = 100;
+
+/// number of characters generated together, across threads, by a parallel stream
+const PARALLEL_CHUNK_SIZE: usize = 1024;
+
+/// An iterator over the characters described by a config, generating them on demand.
+///
+/// Only the dictionaries and the values drawn without replacement are kept in memory; a parallel stream
+/// also holds the chunk of characters generated last. The characters are the same generated by
+/// `generate_charusters` with the same seed.
+pub struct CharusterStream {
+    generators: Vec<NamedGenerator>,
+    base_seed: u64,
+    parallel: bool,
+    next_index: usize,
+    len: usize,
+    chunk: std::vec::IntoIter<Charuster>,
+    /// features of the legendary characters, by position
+    legendaries: HashMap<usize, LockedFeatures>,
+    /// features set for all the other characters
+    template: Option<LockedFeatures>,
+}
+
+impl CharusterStream {
+    pub fn new(config: &Config) -> Self {
+        CharusterStream::with_registry(config, &GeneratorRegistry::new())
+    }
+
+    /// a stream also running the custom generators of the registry enabled in the config
+    pub fn with_registry(config: &Config, registry: &GeneratorRegistry) -> Self {
+        CharusterStream::create(config, registry, None, &[])
+    }
+
+    /// a stream of the characters following the existing ones in the same collection: their token ids
+    /// continue the existing ones, the values that can't repeat are never drawn again and quotas count
+    /// the existing characters too
+    pub fn appending(config: &Config, registry: &GeneratorRegistry, existing: &[Charuster]) -> Self {
+        CharusterStream::create(config, registry, None, existing)
+    }
+
+    /// a stream of characters completing the template: only the features it doesn't set are generated,
+    /// along with the archetypes that can have its values. Panics if the config can't generate its values
+    pub fn with_template(config: &Config, registry: &GeneratorRegistry, template: &LockedFeatures) -> Self {
+        let config = template_config(config, template).unwrap_or_else(|e| panic!("Invalid template: {}", e));
+        CharusterStream::create(&config, registry, Some(template.clone()), &[])
+    }
+
+    // the existing characters take the first positions of the collection and are locked, the legendaries
+    // are placed among the characters generated after them
+    fn create(config: &Config, registry: &GeneratorRegistry, template: Option<LockedFeatures>, existing: &[Charuster]) -> Self {
+        let mut rng = match config.execution_conf.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+        let first = existing.len();
+        let len = first + config.execution_conf.charusters_nums as usize;
+        let mut generators = registry.create_generators(config);
+        let earlier: Vec<LockedFeatures> = existing.iter().map(features_of).collect();
+        lock_features(&mut generators, &earlier.iter().cloned().enumerate().collect());
+        let legendaries: HashMap<usize, LockedFeatures> = place_legendaries(config, len - first, &earlier, &mut rng).into_iter()
+            .map(|(position, features)| (first + position, features))
+            .collect();
+        lock_features(&mut generators, &legendaries);
+        if let Some(template) = &template {
+            let templated: HashMap<usize, LockedFeatures> = (first..len).filter(|i| !legendaries.contains_key(i))
+                .map(|i| (i, template.clone()))
+                .collect();
+            lock_features(&mut generators, &templated);
+        }
+        // the values drawn without replacement are assigned to the characters up front, in a single thread
+        for gen in generators.iter_mut() {
+            gen.generator.prepare(len, &mut rng);
+        }
+
+        CharusterStream {
+            generators,
+            base_seed: rng.gen(),
+            parallel: config.execution_conf.parallel,
+            next_index: first,
+            len,
+            chunk: vec![].into_iter(),
+            legendaries,
+            template,
+        }
+    }
+}
+
+impl Iterator for CharusterStream {
+    type Item = Charuster;
+
+    fn next(&mut self) -> Option<Charuster> {
+        if let Some(charuster) = self.chunk.next() {
+            return Some(charuster);
+        }
+        if self.next_index >= self.len {
+            return None;
+        }
+        if !self.parallel {
+            self.next_index += 1;
+            let index = self.next_index - 1;
+            let locked = self.legendaries.get(&index).or(self.template.as_ref());
+            return Some(generate_charuster(&self.generators, index, self.base_seed, locked));
+        }
+
+        let end = (self.next_index + PARALLEL_CHUNK_SIZE).min(self.len);
+        let (generators, base_seed, legendaries, template) = (&self.generators, self.base_seed, &self.legendaries, &self.template);
+        let chunk: Vec<Charuster> = (self.next_index..end).into_par_iter()
+            .map(|i| generate_charuster(generators, i, base_seed, legendaries.get(&i).or(template.as_ref())))
+            .collect();
+        self.next_index = end;
+        self.chunk = chunk.into_iter();
+        self.chunk.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.chunk.len() + self.len - self.next_index;
+        (remaining, Some(remaining))
+    }
+}
+
+impl ExactSizeIterator for CharusterStream {}
+
+/// generate the character at `index` with its own random generator, seeded from the base seed and the
+/// index, so it doesn't depend on the order the characters are generated in. The locked features are
+/// set as they are, only the other features are generated
+pub(crate) fn generate_charuster(generators: &[NamedGenerator], index: usize, base_seed: u64, locked: Option<&LockedFeatures>) -> Charuster {
+    let mut rng = StdRng::seed_from_u64(base_seed.wrapping_add(index as u64));
+    let mut builder = CharacterBuilder::new();
+    builder.token_id(index);
+    for (name, value) in locked.into_iter().flatten() {
+        builder.set_feature(name, value.clone()).unwrap_or_else(|e| panic!("Could not set {} of character {}: {}", name, index, e));
+    }
+    for gen in generators.iter().filter(|gen| !locked.is_some_and(|locked| locked.contains_key(&gen.name))) {
+        let char_feature = gen.generator.generate(index, &mut rng, &builder)
+            .unwrap_or_else(|| panic!("Could not generate {} for character {}", gen.name, index));
+        match char_feature {
+            CharacterFeature::NAME(value) => &builder.name(value),
+            CharacterFeature::SURNAME(value) => &builder.surname(value),
+            CharacterFeature::NICKNAME(value) => &builder.nickname(value),
+            CharacterFeature::BIRTHDATE(value) => &builder.birthdate(value),
+            CharacterFeature::BIRTHPLACE(value) => &builder.birthplace(value),
+            CharacterFeature::DESCRIPTION(value) => &builder.description(value),
+            CharacterFeature::IMAGE(value) => &builder.image(value),
+            CharacterFeature::COLLECTION(value) => &builder.collection(value),
+            CharacterFeature::PROFESSION(value) => &builder.profession(value),
+            CharacterFeature::HOBBIES(values) => &builder.hobbies(values),
+            CharacterFeature::PROPS(values) => &builder.props(values),
+            CharacterFeature::LEVELS(values) => &builder.levels(values),
+            CharacterFeature::STATS(values) => &builder.stats(values),
+            CharacterFeature::ARCHETYPE(value) => &builder.archetype(value),
+            CharacterFeature::CUSTOM(name, value) => &builder.custom(name, value),
+        };
+    }
+    builder.build()
+}
 
-fn create_generators(config: &Config) -> Vec<Box<dyn FeatureGenerator>> {
-    let mut generators: Vec<Box<dyn FeatureGenerator>> = vec![];
+
+/// create the built-in generators enabled in the config
+pub fn create_generators(config: &Config) -> Vec<NamedGenerator> {
+    let mut generators: Vec<NamedGenerator> = vec![];
 
     if config.char_conf.gen_name && !config.values_conf.names_file.is_empty() {
-        let dict = SimpleDictionary::new(config.values_conf.names_file.as_str());
+        let dict = open_dictionary(config.values_conf.names_file.as_str(), &config.values_conf);
         let generator = ChooseGenerator::new(dict, Box::new(|v: String| Some(CharacterFeature::NAME(v.clone()))));
         let boxxx = Box::new(generator);
-        generators.push(boxxx);
+        generators.push(NamedGenerator::new("name", boxxx));
     }
     if config.char_conf.gen_surname && !config.values_conf.surnames_file.is_empty() {
-        let dict = SimpleDictionary::new(config.values_conf.surnames_file.as_str());
+        let dict = open_dictionary(config.values_conf.surnames_file.as_str(), &config.values_conf);
         let generator = ChooseGenerator::new(dict, Box::new(|v: String| Some(CharacterFeature::SURNAME(v.clone()))));
         let boxxx = Box::new(generator);
-        generators.push(boxxx);
+        generators.push(NamedGenerator::new("surname", boxxx));
     }
     if config.char_conf.gen_nickname && !config.values_conf.nicknames_file.is_empty() {
-        let dict = SimpleDictionary::new(config.values_conf.nicknames_file.as_str());
+        let dict = open_dictionary(config.values_conf.nicknames_file.as_str(), &config.values_conf);
         let generator = ChooseAndRemoveGenerator::new(dict, Box::new(|v: String| Some(CharacterFeature::NICKNAME(v.clone()))));
         let boxxx = Box::new(generator);
-        generators.push(boxxx);
+        generators.push(NamedGenerator::new("nickname", boxxx));
     }
     if config.char_conf.gen_birthdate {
         let min_year = config.values_conf.birthdate_min_year;
@@ -85,189 +351,606 @@ fn create_generators(config: &Config) -> Vec<Box<dyn FeatureGenerator>> {
         let generator = DateGenerator::new(min_year, max_year,
                                                Box::new(|v: String| Some(CharacterFeature::BIRTHDATE(v.clone()))));
         let boxxx = Box::new(generator);
-        generators.push(boxxx);
+        generators.push(NamedGenerator::new("birthdate", boxxx));
     }
-    if config.char_conf.gen_birthdate {
-        let dict = SimpleDictionary::new(config.values_conf.birthplaces_file.as_str());
+    if config.char_conf.gen_birthplace && !config.values_conf.birthplaces_file.is_empty() {
+        let dict = open_dictionary(config.values_conf.birthplaces_file.as_str(), &config.values_conf);
         let generator = ChooseAndRemoveGenerator::new(dict, Box::new(|v: String| Some(CharacterFeature::BIRTHPLACE(v.clone()))));
         let boxxx = Box::new(generator);
-        generators.push(boxxx);
+        generators.push(NamedGenerator::new("birthplace", boxxx));
     }
     if config.char_conf.gen_description && !config.values_conf.description_files.is_empty() {
-        let dict = SimpleDictionary::new(config.values_conf.description_files.as_str());
+        let dict = open_dictionary(config.values_conf.description_files.as_str(), &config.values_conf);
         let generator = ChooseAndRemoveGenerator::new(dict, Box::new(|v: String| Some(CharacterFeature::DESCRIPTION(v.clone()))));
-        let boxxx = Box::new(generator);
-        generators.push(boxxx);
+        let boxxx = Box::new(TemplateGenerator::new(Box::new(generator)));
+        generators.push(NamedGenerator::new("description", boxxx));
     }
     if config.char_conf.gen_image && !config.values_conf.images_folder.is_empty() {
-        let dict = SimpleDictionary::new_from_folder(config.values_conf.images_folder.as_str());
+        let dict = Box::new(SimpleDictionary::new_from_folder(config.values_conf.images_folder.as_str()));
         let generator = ChooseAndRemoveGenerator::new(dict, Box::new(|v: String| Some(CharacterFeature::IMAGE(v.clone()))));
         let boxxx = Box::new(generator);
-        generators.push(boxxx);
+        generators.push(NamedGenerator::new("image", boxxx));
     }
     if config.char_conf.gen_collection {
-        let dict = SimpleDictionary::new_with_single_term(String::from("collection"), config.values_conf.collection_name.clone());
+        let dict = Box::new(SimpleDictionary::new_with_single_term(String::from("collection"), config.values_conf.collection_name.clone()));
         let generator = ChooseGenerator::new(dict, Box::new(|v: String| Some(CharacterFeature::COLLECTION(v.clone()))));
         let boxxx = Box::new(generator);
-        generators.push(boxxx);
+        generators.push(NamedGenerator::new("collection", boxxx));
     }
-    if config.char_conf.gen_profession && !config.values_conf.professions_file.is_empty() {
-        let dict = SimpleDictionary::new(config.values_conf.professions_file.as_str());
+    let allowed_professions = &config.values_conf.allowed_professions;
+    if config.char_conf.gen_profession && (!config.values_conf.professions_file.is_empty() || !allowed_professions.is_empty()) {
+        let dict: Box<dyn Dictionary> = if allowed_professions.is_empty() {
+            open_dictionary(config.values_conf.professions_file.as_str(), &config.values_conf)
+        } else {
+            Box::new(SimpleDictionary::new_with_terms(String::from("professions"), allowed_professions.clone()))
+        };
         let generator = ChooseGenerator::new(dict, Box::new(|v: String| Some(CharacterFeature::PROFESSION(v.clone()))));
         let boxxx = Box::new(generator);
-        generators.push(boxxx);
+        generators.push(NamedGenerator::new("profession", boxxx));
     }
-    // TODO add num of desired item in vecs
     if config.char_conf.gen_hobbies && !config.values_conf.hobbies_file.is_empty() {
-        let dict = SimpleDictionary::new(config.values_conf.hobbies_file.as_str());
-        let generator = ChooseVecGenerator::new(dict, Box::new(|v: Vec<String>| Some(CharacterFeature::HOBBIES(v))), 3);
+        let dict = open_dictionary(config.values_conf.hobbies_file.as_str(), &config.values_conf);
+        let generator = ChooseVecGenerator::new(dict, Box::new(|v: Vec<String>| Some(CharacterFeature::HOBBIES(v))), VEC_FEATURES_SIZE);
         let boxxx = Box::new(generator);
-        generators.push(boxxx);
+        generators.push(NamedGenerator::new("hobbies", boxxx));
     }
     if config.char_conf.gen_props && !config.values_conf.props_file.is_empty() {
-        let dict = TwoLevelsDictionary::new(config.values_conf.props_file.as_str());
-        let generator = ChooseVecQuirkGenerator::new(Box::new(dict), Box::new(|v: Vec<Property>| Some(CharacterFeature::PROPS(v))), 3,
-                                                     Box::new(|dict: &mut dyn Dictionary| {
-                                                         let term = dict.choose_and_remove().unwrap();
-                                                         let taxonomy : Vec<&str> = term.split("+").collect();
-                                                         character::Property {
-                                                             prop_type: String::from(taxonomy.get(0).unwrap().to_owned()),
-                                                             name: String::from(taxonomy.get(1).unwrap().to_owned())
-                                                         }
-                                                     }));
+        let mut dict = TaxonomyDictionary::new(config.values_conf.props_file.as_str());
+        if !config.values_conf.property_kinds.is_empty() {
+            dict.retain_kinds(&config.values_conf.property_kinds);
+        }
+        let generator = ChooseVecQuirkGenerator::new(Box::new(dict), Box::new(|v: Vec<Property>| Some(CharacterFeature::PROPS(v))), VEC_FEATURES_SIZE,
+                                                     Box::new(|path: Vec<String>, _: &mut dyn RngCore| character::Property::from_path(path)));
         let boxxx = Box::new(generator);
-        generators.push(boxxx);
+        generators.push(NamedGenerator::new("props", boxxx));
     }
     if config.char_conf.gen_levels && !config.values_conf.levels_file.is_empty() {
-        let dict = SimpleDictionary::new(config.values_conf.levels_file.as_str());
-        let generator = ChooseVecQuirkGenerator::new(Box::new(dict), Box::new(|v: Vec<Level>| Some(CharacterFeature::LEVELS(v))), 3,
-                                                     Box::new(|dict: &mut dyn Dictionary| {
+        let dict = open_dictionary(config.values_conf.levels_file.as_str(), &config.values_conf);
+        let range = config.values_conf.levels_range;
+        let generator = ChooseVecQuirkGenerator::new(dict, Box::new(|v: Vec<Level>| Some(CharacterFeature::LEVELS(v))), VEC_FEATURES_SIZE,
+                                                     Box::new(move |mut path: Vec<String>, rng: &mut dyn RngCore| {
                                                          character::Level {
-                                                             name: dict.choose_and_remove().unwrap(),
-                                                             value: rand::thread_rng().gen_range(1..=100),
-                                                             max_value: 100
+                                                             name: path.pop().unwrap(),
+                                                             value: get_random_number(range.min as i64, range.max as i64, range.distribution, rng) as i32,
+                                                             max_value: range.max
                                                          }
                                                      }));
         let boxxx = Box::new(generator);
-        generators.push(boxxx);
+        generators.push(NamedGenerator::new("levels", boxxx));
     }
     if config.char_conf.gen_stats && !config.values_conf.stats_file.is_empty() {
-        let dict = SimpleDictionary::new(config.values_conf.stats_file.as_str());
-        let generator = ChooseVecQuirkGenerator::new(Box::new(dict), Box::new(|v: Vec<Stat>| Some(CharacterFeature::STATS(v))), 3,
-                                                     Box::new(|dict: &mut dyn Dictionary| {
+        let dict = open_dictionary(config.values_conf.stats_file.as_str(), &config.values_conf);
+        let range = config.values_conf.stats_range;
+        let generator = ChooseVecQuirkGenerator::new(dict, Box::new(|v: Vec<Stat>| Some(CharacterFeature::STATS(v))), VEC_FEATURES_SIZE,
+                                                     Box::new(move |mut path: Vec<String>, rng: &mut dyn RngCore| {
                                                          character::Stat {
-                                                             name: dict.choose_and_remove().unwrap(),
-                                                             value: rand::thread_rng().gen_range(1..=100),
-                                                             max_value: 100
+                                                             name: path.pop().unwrap(),
+                                                             value: get_random_number(range.min as i64, range.max as i64, range.distribution, rng) as i32,
+                                                             max_value: range.max
                                                          }
                                                      }));
         let boxxx = Box::new(generator);
-        generators.push(boxxx);
+        generators.push(NamedGenerator::new("stats", boxxx));
+    }
+    // descriptions can mention the other features with their placeholders
+    let template_features: Vec<String> = DESCRIPTION_DEPENDENCIES.iter()
+        .filter(|feature| generators.iter().any(|gen| gen.name == **feature))
+        .map(|feature| feature.to_string())
+        .collect();
+    if let Some(description) = generators.iter_mut().find(|gen| gen.name == "description") {
+        description.dependencies = template_features;
+    }
+    let assignment = (!config.archetypes.is_empty()).then(|| apply_archetypes(config, &mut generators));
+    generators.append(&mut create_attribute_generators(config));
+    if !config.quotas.is_empty() {
+        apply_quotas(config, &mut generators);
+    }
+    // the archetypes are assigned once their quotas are applied
+    if let Some(assignment) = assignment {
+        generators = generators.into_iter().map(|mut gen| {
+            if gen.name == "archetype" {
+                gen.generator = Box::new(ArchetypeAssignmentGenerator::new(gen.generator, assignment.clone()));
+            }
+            gen
+        }).collect();
+    }
+
+    generators
+}
+
+// the source of a feature drawn without replacement, None for the other features
+fn unique_source<'a>(values: &'a ValuesConfig, feature: &str) -> Option<&'a str> {
+    match feature {
+        "nickname" => Some(&values.nicknames_file),
+        "birthplace" => Some(&values.birthplaces_file),
+        "description" => Some(&values.description_files),
+        "image" => Some(&values.images_folder),
+        _ => None,
+    }
+}
+
+/// add the generator drawing the archetype of the characters, and let the archetypes generate the features
+/// they override with their own generators. The values drawn without replacement are drawn by the
+/// generators of the config unless the archetype has its own source
+fn apply_archetypes(config: &Config, generators: &mut Vec<NamedGenerator>) -> ArchetypeAssignment {
+    let assignment = ArchetypeAssignment::default();
+    let mut variants: HashMap<String, ArchetypeVariants> = HashMap::new();
+    for (i, archetype) in config.archetypes.iter().enumerate() {
+        let mut archetype_config = config.archetype_config(i).unwrap_or_else(|e| panic!("{}", e));
+        let features: Vec<&str> = archetype.features().into_iter()
+            .filter(|feature| match unique_source(&config.values_conf, feature) {
+                Some(source) => unique_source(&archetype_config.values_conf, feature) != Some(source),
+                None => true,
+            })
+            .collect();
+        archetype_config.char_conf.retain_features(&features);
+        for variant in create_generators(&archetype_config) {
+            if !generators.iter().any(|gen| gen.name == variant.name) {
+                panic!("Archetype {} overrides {}, which is not generated", archetype.name, variant.name);
+            }
+            variants.entry(variant.name).or_default().push((archetype.name.clone(), variant.generator));
+        }
+    }
+
+    *generators = std::mem::take(generators).into_iter().map(|mut gen| {
+        if let Some(variants) = variants.remove(&gen.name) {
+            gen.generator = Box::new(ArchetypeVariantsGenerator::new(gen.generator, variants, assignment.clone()));
+            gen.dependencies.push(String::from("archetype"));
+        }
+        gen
+    }).collect();
+    let names: Vec<String> = config.archetypes.iter().map(|archetype| archetype.name.clone()).collect();
+    let weights: Vec<f64> = config.archetypes.iter().map(|archetype| archetype.weight).collect();
+    generators.insert(0, NamedGenerator::new("archetype", Box::new(ArchetypeGenerator::new(names, &weights))));
+    assignment
+}
+
+/// create the generators of the custom attributes declared in the config
+fn create_attribute_generators(config: &Config) -> Vec<NamedGenerator> {
+    let mut generators: Vec<NamedGenerator> = vec![];
+
+    for attribute in config.attributes.iter() {
+        let name = attribute.name.clone();
+        let generator: Box<dyn FeatureGenerator> = match attribute.kind {
+            AttributeKind::Choose => {
+                let dict = open_dictionary(attribute.source.as_str(), &config.values_conf);
+                let generator = ChooseGenerator::new(dict, Box::new(move |v: String| Some(CharacterFeature::CUSTOM(name.clone(), Value::from(v)))));
+                Box::new(TemplateGenerator::new(Box::new(generator)))
+            }
+            AttributeKind::ChooseAndRemove => {
+                let dict = open_dictionary(attribute.source.as_str(), &config.values_conf);
+                let generator = ChooseAndRemoveGenerator::new(dict, Box::new(move |v: String| Some(CharacterFeature::CUSTOM(name.clone(), Value::from(v)))));
+                Box::new(TemplateGenerator::new(Box::new(generator)))
+            }
+            AttributeKind::Vector => {
+                let dict = open_dictionary(attribute.source.as_str(), &config.values_conf);
+                Box::new(SampleVecGenerator::new(dict, Box::new(move |v: Vec<String>| Some(CharacterFeature::CUSTOM(name.clone(), Value::from(v)))), attribute.count))
+            }
+            AttributeKind::Number => {
+                let min = attribute.min.unwrap_or(1);
+                let max = attribute.max.unwrap_or(100);
+                Box::new(NumberGenerator::new(min, max, attribute.distribution, Box::new(move |v: i64| Some(CharacterFeature::CUSTOM(name.clone(), Value::from(v))))))
+            }
+            AttributeKind::Date => {
+                let year = |y: Option<i64>, default: u16| y.map(|y| u16::try_from(y)
+                    .unwrap_or_else(|_| panic!("Invalid year {} for attribute {}", y, attribute.name)))
+                    .unwrap_or(default);
+                let min_year = year(attribute.min, config.values_conf.birthdate_min_year);
+                let max_year = year(attribute.max, config.values_conf.birthdate_max_year);
+                let generator = DateGenerator::new(min_year, max_year, Box::new(move |v: String| Some(CharacterFeature::CUSTOM(name.clone(), Value::from(v)))));
+                Box::new(generator.with_distribution(attribute.distribution))
+            }
+        };
+        generators.push(NamedGenerator::new(&attribute.name, generator));
     }
 
     generators
 }
 
-fn get_random_date(min_year: u16, max_year: u16) -> DateTime<Utc>{
-    let min_date = Utc.ymd(min_year as i32, 1, 1);
-    let max_date = Utc.ymd(max_year as i32, 1, 1);
+/// an integer between `min` and `max` (both included), following the distribution
+fn get_random_number(min: i64, max: i64, distribution: Distribution, rng: &mut dyn RngCore) -> i64 {
+    if min >= max {
+        return min;
+    }
+    match distribution {
+        Distribution::Uniform => rng.gen_range(min..=max),
+        Distribution::Normal => {
+            let normal = Normal::new((min + max) as f64 / 2.0, (max - min) as f64 / 6.0).unwrap();
+            // draw again the values outside the bounds, so they don't pile up on them
+            loop {
+                let value = normal.sample(rng).round() as i64;
+                if (min..=max).contains(&value) {
+                    return value;
+                }
+            }
+        }
+    }
+}
+
+fn get_random_date(min_year: u16, max_year: u16, distribution: Distribution, rng: &mut dyn RngCore) -> DateTime<Utc>{
+    let min_date = NaiveDate::from_ymd_opt(min_year as i32, 1, 1).unwrap();
+    let max_date = NaiveDate::from_ymd_opt(max_year as i32, 1, 1).unwrap();
 
     let days_span = max_date.num_days_from_ce() - min_date.num_days_from_ce();
-    let days_to_add = rand::thread_rng().gen_range(0..=days_span);
-    let rnd_date = min_date.checked_add_signed(Duration::days(days_to_add as i64)).unwrap();
-    //println!("{}", rnd_date.year());
-    rnd_date.and_time(NaiveTime::from_hms(0, 0, 0)).unwrap()
+    let days_to_add = get_random_number(0, days_span as i64, distribution, rng);
+    let rnd_date = min_date.checked_add_signed(Duration::days(days_to_add)).unwrap();
+    Utc.from_utc_datetime(&rnd_date.and_time(NaiveTime::MIN))
 }
 
 /** GENERATORS **/
-trait FeatureGenerator {
-    fn generate(&mut self) -> Option<character::CharacterFeature>;
+/// Generates a feature of the characters. Implementations can be added to the generation through a `GeneratorRegistry`
+pub trait FeatureGenerator: Send + Sync {
+    /// called once before generating `count` characters: generators drawing values without
+    /// replacement draw them all here, so the characters can then be generated in any order
+    fn prepare(&mut self, _count: usize, _rng: &mut dyn RngCore) {}
+
+    /// names of the features that have to be generated before this one
+    fn dependencies(&self) -> Vec<String> {
+        vec![]
+    }
+
+    /// called before `prepare` for each character whose feature is set beforehand, like the legendary
+    /// characters, with its value: the feature is not generated for that character, and generators drawing
+    /// values without replacement don't draw the value for the others
+    fn lock(&mut self, _index: usize, _value: &Value) {}
+
+    /// called before `prepare` with values that must not be drawn again, like the rerolled ones. Generators
+    /// choosing terms draw others as long as the dictionary has some
+    fn exclude(&mut self, _values: &[String]) {}
+
+    /// generate the feature of the character at `index`, given the features generated so far
+    fn generate(&self, index: usize, rng: &mut dyn RngCore, character: &CharacterBuilder) -> Option<character::CharacterFeature>;
 }
 
 // DateGenerator
-struct DateGenerator {
+pub struct DateGenerator {
     birthdate_min_year: u16,
     birthdate_max_year: u16,
+    distribution: Distribution,
     fn_char_feat_creator: FnCharFeatPropCreator,
 }
 
 impl DateGenerator {
-    fn new(birthdate_min_year: u16, birthdate_max_year: u16, fn_char_feat_creator: FnCharFeatPropCreator) -> Self {
-        DateGenerator { birthdate_min_year, birthdate_max_year, fn_char_feat_creator }
+    pub fn new(birthdate_min_year: u16, birthdate_max_year: u16, fn_char_feat_creator: FnCharFeatPropCreator) -> Self {
+        DateGenerator { birthdate_min_year, birthdate_max_year, distribution: Distribution::Uniform, fn_char_feat_creator }
+    }
+
+    pub fn with_distribution(mut self, distribution: Distribution) -> Self {
+        self.distribution = distribution;
+        self
     }
 }
 
 impl FeatureGenerator for DateGenerator {
-    fn generate(&mut self) -> Option<character::CharacterFeature> {
-        let rnd_date = get_random_date(self.birthdate_min_year, self.birthdate_max_year);
+    fn generate(&self, _index: usize, rng: &mut dyn RngCore, _character: &CharacterBuilder) -> Option<character::CharacterFeature> {
+        let rnd_date = get_random_date(self.birthdate_min_year, self.birthdate_max_year, self.distribution, rng);
         (self.fn_char_feat_creator)(rnd_date.timestamp().to_string())
     }
 }
 
+// TemplateGenerator
+/// fills the placeholders of the text features generated by another generator with the features of the character
+pub struct TemplateGenerator {
+    inner: Box<dyn FeatureGenerator>,
+}
+
+impl TemplateGenerator {
+    pub fn new(inner: Box<dyn FeatureGenerator>) -> Self {
+        TemplateGenerator { inner }
+    }
+}
+
+impl FeatureGenerator for TemplateGenerator {
+    fn prepare(&mut self, count: usize, rng: &mut dyn RngCore) {
+        self.inner.prepare(count, rng);
+    }
+
+    fn dependencies(&self) -> Vec<String> {
+        self.inner.dependencies()
+    }
+
+    fn lock(&mut self, index: usize, value: &Value) {
+        self.inner.lock(index, value);
+    }
+
+    fn exclude(&mut self, values: &[String]) {
+        self.inner.exclude(values);
+    }
+
+    fn generate(&self, index: usize, rng: &mut dyn RngCore, character: &CharacterBuilder) -> Option<character::CharacterFeature> {
+        let feature = match self.inner.generate(index, rng, character)? {
+            CharacterFeature::DESCRIPTION(text) => CharacterFeature::DESCRIPTION(character.fill_template(&text)),
+            CharacterFeature::CUSTOM(name, Value::String(text)) => CharacterFeature::CUSTOM(name, Value::from(character.fill_template(&text))),
+            feature => feature,
+        };
+        Some(feature)
+    }
+}
+
+// NumberGenerator
+pub struct NumberGenerator {
+    min: i64,
+    max: i64,
+    distribution: Distribution,
+    fn_char_feat_creator: FnCharFeatNumberCreator,
+}
+
+impl NumberGenerator {
+    pub fn new(min: i64, max: i64, distribution: Distribution, fn_char_feat_creator: FnCharFeatNumberCreator) -> Self {
+        NumberGenerator { min, max, distribution, fn_char_feat_creator }
+    }
+}
+
+impl FeatureGenerator for NumberGenerator {
+    fn generate(&self, _index: usize, rng: &mut dyn RngCore, _character: &CharacterBuilder) -> Option<character::CharacterFeature> {
+        (self.fn_char_feat_creator)(get_random_number(self.min, self.max, self.distribution, rng))
+    }
+}
+
+// ArchetypeGenerator
+pub struct ArchetypeGenerator {
+    names: Vec<String>,
+    weights: WeightedIndex<f64>,
+}
+
+impl ArchetypeGenerator {
+    pub fn new(names: Vec<String>, weights: &[f64]) -> Self {
+        let weights = WeightedIndex::new(weights).unwrap_or_else(|e| panic!("Invalid archetype weights: {}", e));
+        ArchetypeGenerator { names, weights }
+    }
+}
+
+impl FeatureGenerator for ArchetypeGenerator {
+    fn generate(&self, _index: usize, rng: &mut dyn RngCore, _character: &CharacterBuilder) -> Option<character::CharacterFeature> {
+        Some(CharacterFeature::ARCHETYPE(self.names[self.weights.sample(rng)].clone()))
+    }
+}
+
+// ArchetypeAssignmentGenerator
+/// Generates the archetype of every character up front and shares it, so the generators of the archetypes
+/// can prepare the values of their own characters
+pub struct ArchetypeAssignmentGenerator {
+    inner: Box<dyn FeatureGenerator>,
+    assignment: ArchetypeAssignment,
+    // archetypes of the characters set beforehand
+    locked: HashMap<usize, String>,
+}
+
+impl ArchetypeAssignmentGenerator {
+    pub fn new(inner: Box<dyn FeatureGenerator>, assignment: ArchetypeAssignment) -> Self {
+        ArchetypeAssignmentGenerator { inner, assignment, locked: HashMap::new() }
+    }
+}
+
+impl FeatureGenerator for ArchetypeAssignmentGenerator {
+    fn prepare(&mut self, count: usize, rng: &mut dyn RngCore) {
+        self.inner.prepare(count, rng);
+        let character = CharacterBuilder::new();
+        let assigned: Vec<String> = (0..count).map(|index| match self.locked.get(&index) {
+            Some(archetype) => archetype.clone(),
+            None => match self.inner.generate(index, rng, &character) {
+                Some(CharacterFeature::ARCHETYPE(archetype)) => archetype,
+                _ => panic!("Could not generate archetype for character {}", index),
+            },
+        }).collect();
+        *self.assignment.write().unwrap() = assigned;
+    }
+
+    fn dependencies(&self) -> Vec<String> {
+        self.inner.dependencies()
+    }
+
+    fn lock(&mut self, index: usize, value: &Value) {
+        self.inner.lock(index, value);
+        if let Value::String(archetype) = value {
+            self.locked.insert(index, archetype.clone());
+        }
+    }
+
+    fn exclude(&mut self, values: &[String]) {
+        self.inner.exclude(values);
+    }
+
+    fn generate(&self, index: usize, _rng: &mut dyn RngCore, _character: &CharacterBuilder) -> Option<character::CharacterFeature> {
+        let archetype = self.assignment.read().unwrap().get(index)?.clone();
+        Some(CharacterFeature::ARCHETYPE(archetype))
+    }
+}
+
+// ArchetypeVariantsGenerator
+/// Generates a feature with the generator of the archetype of the character, if it has its own, or with
+/// the generator of the config otherwise
+pub struct ArchetypeVariantsGenerator {
+    base: Box<dyn FeatureGenerator>,
+    variants: ArchetypeVariants,
+    assignment: ArchetypeAssignment,
+}
+
+impl ArchetypeVariantsGenerator {
+    pub fn new(base: Box<dyn FeatureGenerator>, variants: ArchetypeVariants, assignment: ArchetypeAssignment) -> Self {
+        ArchetypeVariantsGenerator { base, variants, assignment }
+    }
+}
+
+impl FeatureGenerator for ArchetypeVariantsGenerator {
+    /// each generator prepares the values of the characters it generates the feature of, the other
+    /// characters are locked for it
+    fn prepare(&mut self, count: usize, rng: &mut dyn RngCore) {
+        let assignment = self.assignment.read().unwrap();
+        for index in 0..count {
+            let archetype = assignment.get(index).map(String::as_str);
+            let own = self.variants.iter().position(|(name, _)| Some(name.as_str()) == archetype);
+            if own.is_some() {
+                self.base.lock(index, &Value::Null);
+            }
+            for (i, (_, variant)) in self.variants.iter_mut().enumerate() {
+                if own != Some(i) {
+                    variant.lock(index, &Value::Null);
+                }
+            }
+        }
+        drop(assignment);
+        self.base.prepare(count, rng);
+        for (_, variant) in self.variants.iter_mut() {
+            variant.prepare(count, rng);
+        }
+    }
+
+    fn lock(&mut self, index: usize, value: &Value) {
+        self.base.lock(index, value);
+        for (_, variant) in self.variants.iter_mut() {
+            variant.lock(index, value);
+        }
+    }
+
+    fn exclude(&mut self, values: &[String]) {
+        self.base.exclude(values);
+        for (_, variant) in self.variants.iter_mut() {
+            variant.exclude(values);
+        }
+    }
+
+    fn dependencies(&self) -> Vec<String> {
+        let mut dependencies = self.base.dependencies();
+        dependencies.push(String::from("archetype"));
+        dependencies
+    }
+
+    fn generate(&self, index: usize, rng: &mut dyn RngCore, character: &CharacterBuilder) -> Option<character::CharacterFeature> {
+        let archetype = character.feature("archetype");
+        let generator = self.variants.iter()
+            .find(|(name, _)| archetype.as_ref().and_then(|a| a.as_str()) == Some(name.as_str()))
+            .map(|(_, variant)| variant)
+            .unwrap_or(&self.base);
+        generator.generate(index, rng, character)
+    }
+}
+
 // ChooseGenerator
-struct ChooseGenerator {
-    dict: SimpleDictionary,
+pub struct ChooseGenerator {
+    dict: Box<dyn Dictionary>,
     fn_char_feat_creator: FnCharFeatPropCreator,
+    // values drawn again when chosen
+    excluded: HashSet<String>,
 }
 
 impl ChooseGenerator {
-    fn new(dict: SimpleDictionary, fn_char_feat_creator: FnCharFeatPropCreator) -> ChooseGenerator {
-        ChooseGenerator { dict, fn_char_feat_creator }
+    pub fn new(dict: Box<dyn Dictionary>, fn_char_feat_creator: FnCharFeatPropCreator) -> ChooseGenerator {
+        ChooseGenerator { dict, fn_char_feat_creator, excluded: HashSet::new() }
     }
 }
 
 impl FeatureGenerator for ChooseGenerator {
-    fn generate(&mut self) -> Option<character::CharacterFeature> {
-        let value = self.dict.choose()?;
-        (self.fn_char_feat_creator)(value.clone())
+    fn exclude(&mut self, values: &[String]) {
+        self.excluded.extend(values.iter().cloned());
+    }
+
+    /// choose a term, drawing again the excluded ones up to `EXCLUDED_ATTEMPTS` times, so dictionaries made
+    /// of excluded terms only still generate a value
+    fn generate(&self, _index: usize, rng: &mut dyn RngCore, _character: &CharacterBuilder) -> Option<character::CharacterFeature> {
+        let mut value = self.dict.choose_with(rng)?;
+        for _ in 0..EXCLUDED_ATTEMPTS {
+            if !self.excluded.contains(&value) {
+                break;
+            }
+            value = self.dict.choose_with(rng)?;
+        }
+        (self.fn_char_feat_creator)(value)
     }
 }
 
 // ChooseAndRemoveGenerator
-struct ChooseAndRemoveGenerator {
-    dict: SimpleDictionary,
+pub struct ChooseAndRemoveGenerator {
+    dict: Box<dyn Dictionary>,
     fn_char_feat_creator: FnCharFeatPropCreator,
+    // values removed from the dictionary by `prepare`, one per character
+    drawn: Vec<String>,
+    // characters not drawing a value
+    locked: HashSet<usize>,
+    // values never drawn: the ones of the locked characters and the excluded ones
+    excluded: HashSet<String>,
 }
 
 impl ChooseAndRemoveGenerator {
-    fn new(dict: SimpleDictionary, fn_char_feat_creator: FnCharFeatPropCreator) -> ChooseAndRemoveGenerator {
-        ChooseAndRemoveGenerator { dict, fn_char_feat_creator }
+    pub fn new(dict: Box<dyn Dictionary>, fn_char_feat_creator: FnCharFeatPropCreator) -> ChooseAndRemoveGenerator {
+        ChooseAndRemoveGenerator { dict, fn_char_feat_creator, drawn: vec![], locked: HashSet::new(), excluded: HashSet::new() }
     }
 }
 
 impl FeatureGenerator for ChooseAndRemoveGenerator {
-    fn generate(&mut self) -> Option<character::CharacterFeature> {
-        let value = self.dict.choose_and_remove()?;
+    fn prepare(&mut self, count: usize, rng: &mut dyn RngCore) {
+        let (dict, locked, excluded) = (&mut self.dict, &self.locked, &self.excluded);
+        self.drawn = (0..count)
+            .map_while(|i| match locked.contains(&i) {
+                true => Some(String::new()),
+                false => std::iter::from_fn(|| dict.choose_and_remove_with(rng)).find(|value| !excluded.contains(value)),
+            })
+            .collect();
+    }
+
+    fn lock(&mut self, index: usize, value: &Value) {
+        self.locked.insert(index);
+        if let Value::String(value) = value {
+            self.excluded.insert(value.clone());
+        }
+    }
+
+    fn exclude(&mut self, values: &[String]) {
+        self.excluded.extend(values.iter().cloned());
+    }
+
+    /// return the value drawn for the character, None if the dictionary ran out of terms
+    fn generate(&self, index: usize, _rng: &mut dyn RngCore, _character: &CharacterBuilder) -> Option<character::CharacterFeature> {
+        let value = self.drawn.get(index)?;
         (self.fn_char_feat_creator)(value.clone())
     }
 }
 
 // ChooseVecGenerator
-struct ChooseVecGenerator {
-    dict: SimpleDictionary,
+pub struct ChooseVecGenerator {
+    dict: Box<dyn Dictionary>,
     fn_char_feat_vec_creator: FnCharFeatVecPropCreator,
     vec_size: u8,
 }
 
 impl ChooseVecGenerator {
-    fn new(dict: SimpleDictionary, fn_char_feat_vec_creator: FnCharFeatVecPropCreator, vec_size: u8) -> ChooseVecGenerator {
+    pub fn new(dict: Box<dyn Dictionary>, fn_char_feat_vec_creator: FnCharFeatVecPropCreator, vec_size: u8) -> ChooseVecGenerator {
         ChooseVecGenerator { dict, fn_char_feat_vec_creator, vec_size }
     }
 }
 
 impl FeatureGenerator for ChooseVecGenerator {
-    fn generate(&mut self) -> Option<character::CharacterFeature> {
+    fn generate(&self, _index: usize, rng: &mut dyn RngCore, _character: &CharacterBuilder) -> Option<character::CharacterFeature> {
         let mut feat_vec = vec![];
-        for _ in (0..self.vec_size).into_iter() {
-            let value = self.dict.choose()?;
-            feat_vec.push(value.clone());
+        for _ in 0..self.vec_size {
+            feat_vec.push(self.dict.choose_with(rng)?);
         }
         (self.fn_char_feat_vec_creator)(feat_vec)
     }
 }
 
+// SampleVecGenerator
+/// like `ChooseVecGenerator`, never repeating a term in the same vector
+pub struct SampleVecGenerator {
+    dict: Box<dyn Dictionary>,
+    fn_char_feat_vec_creator: FnCharFeatVecPropCreator,
+    vec_size: u8,
+}
+
+impl SampleVecGenerator {
+    pub fn new(dict: Box<dyn Dictionary>, fn_char_feat_vec_creator: FnCharFeatVecPropCreator, vec_size: u8) -> SampleVecGenerator {
+        SampleVecGenerator { dict, fn_char_feat_vec_creator, vec_size }
+    }
+}
+
+impl FeatureGenerator for SampleVecGenerator {
+    fn generate(&self, _index: usize, rng: &mut dyn RngCore, _character: &CharacterBuilder) -> Option<character::CharacterFeature> {
+        (self.fn_char_feat_vec_creator)(self.dict.sample_distinct(self.vec_size as usize, rng))
+    }
+}
+
 // ChooseVecQuirkGenerator
-struct ChooseVecQuirkGenerator<T: character::Quirk> {
+pub struct ChooseVecQuirkGenerator<T: character::Quirk> {
     dict: Box<dyn Dictionary>,
     fn_char_feat_vec_creator: FnCharFeatVecQuirkCreator<T>,
     fn_quirk_creator: FnQuirkCreator<T>,
@@ -275,59 +958,508 @@ struct ChooseVecQuirkGenerator<T: character::Quirk> {
 }
 
 impl<T: character::Quirk> ChooseVecQuirkGenerator<T> {
-    fn new(dict: Box<dyn Dictionary>, fn_char_feat_vec_creator: FnCharFeatVecQuirkCreator<T>, vec_size: u8, fn_quirk_creator: FnQuirkCreator<T>) -> ChooseVecQuirkGenerator<T> {
+    pub fn new(dict: Box<dyn Dictionary>, fn_char_feat_vec_creator: FnCharFeatVecQuirkCreator<T>, vec_size: u8, fn_quirk_creator: FnQuirkCreator<T>) -> ChooseVecQuirkGenerator<T> {
         ChooseVecQuirkGenerator { dict, fn_char_feat_vec_creator, vec_size, fn_quirk_creator }
     }
 }
 
 impl<T> FeatureGenerator for ChooseVecQuirkGenerator<T>
 where T: character::Quirk {
-    fn generate(&mut self) -> Option<character::CharacterFeature> {
-        let mut feat_vec = vec![];
-        let mut cloned_dict = dyn_clone::clone_box(&*self.dict);
-        for _ in (0..self.vec_size).into_iter() {
-            let quirk = (self.fn_quirk_creator)(cloned_dict.as_mut());
-            feat_vec.push(quirk);
-        }
+    fn generate(&self, _index: usize, rng: &mut dyn RngCore, _character: &CharacterBuilder) -> Option<character::CharacterFeature> {
+        let paths = self.dict.sample_distinct_paths(self.vec_size as usize, rng);
+        let feat_vec = paths.into_iter().map(|path| (self.fn_quirk_creator)(path, rng)).collect();
         (self.fn_char_feat_vec_creator)(feat_vec)
     }
 }
 
-fn export_to_json(charusters: &Vec<Charuster>, filename: &str) {
-    let json = serde_json::to_string(&charusters).unwrap();
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use std::path::PathBuf;
 
-    let write = OpenOptions::new().write(true).create(true).truncate(true).open(filename);
-    let mut reader = BufReader::new(json.as_bytes());
-    let mut writer = BufWriter::new(write.unwrap());
+    use crate::config::AttributeConfig;
 
-    let mut length = 1;
+    use super::*;
 
-    while length > 0 {
-        let buffer = reader.fill_buf().unwrap();
+    fn get_seeded_config(seed: u64, parallel: bool) -> Config {
+        let mut config = parse_local_config();
+        config.execution_conf.charusters_nums = 50;
+        config.execution_conf.export_to_json = false;
+        config.execution_conf.seed = Some(seed);
+        config.execution_conf.parallel = parallel;
+        config.char_conf.gen_image = false;
+        config
+    }
 
-        writer.write(buffer).expect("JSON export failed");
+    fn generate_json(config: Config) -> String {
+        serde_json::to_string(&generate_charusters(Some(config))).unwrap()
+    }
 
-        length = buffer.len();
-        reader.consume(length);
+    #[test]
+    fn generate_churusters() {
+        generate_charusters(None);
     }
-}
 
+    #[test]
+    fn should_generate_the_same_charusters_from_the_same_seed() {
+        assert_eq!(generate_json(get_seeded_config(42, false)), generate_json(get_seeded_config(42, false)));
+        assert_ne!(generate_json(get_seeded_config(42, false)), generate_json(get_seeded_config(43, false)));
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[test]
+    fn should_generate_the_same_charusters_in_parallel_and_sequentially() {
+        assert_eq!(generate_json(get_seeded_config(42, true)), generate_json(get_seeded_config(42, false)));
+    }
 
     #[test]
-    fn generate_churusters() {
-        generate_charusters(None);
+    fn should_generate_from_a_near_empty_config() {
+        let mut config_file = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        config_file.push("resources/test/test_minimal_config.toml");
+        let config = crate::config::parse_config(config_file.to_str().unwrap());
+
+        let charusters = generate_charusters(Some(config));
+        assert_eq!(charusters.len(), 3);
+        assert!(charusters.iter().all(|charuster| !charuster.name().is_empty()));
+    }
+
+    fn get_archetypes_config(parallel: bool) -> Config {
+        let mut config_file = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        config_file.push("resources/test/test_archetypes_config.toml");
+        let mut config = crate::config::parse_config(config_file.to_str().unwrap());
+        config.execution_conf.parallel = parallel;
+        config
+    }
+
+    #[test]
+    fn should_generate_the_features_of_the_archetypes() {
+        let charusters = generate_charusters(Some(get_archetypes_config(false)));
+
+        let brutes: Vec<&Charuster> = charusters.iter().filter(|c| c.archetype() == "Brute").collect();
+        let scholars: Vec<&Charuster> = charusters.iter().filter(|c| c.archetype() == "Scholar").collect();
+        assert_eq!(brutes.len() + scholars.len(), 40);
+        assert!(brutes.len() > scholars.len() && !scholars.is_empty());
+        for brute in brutes {
+            assert!(["Blacksmith", "Guard"].contains(&brute.profession().as_str()));
+            assert!(brute.props().iter().all(|prop| prop.prop_type == "term_1"));
+            assert!(brute.stats().iter().all(|stat| (90..=100).contains(&stat.value) && stat.max_value == 100));
+            assert!(brute.levels().iter().all(|level| level.max_value == 100));
+            assert!(!brute.name().starts_with("term_"));
+        }
+        for scholar in scholars {
+            assert_eq!(scholar.profession(), "Librarian");
+            assert!(scholar.name().starts_with("term_"));
+            assert!(scholar.levels().iter().all(|level| (1..=10).contains(&level.value) && level.max_value == 10));
+        }
+    }
+
+    #[test]
+    fn should_generate_the_same_archetypes_in_parallel_and_sequentially() {
+        assert_eq!(generate_json(get_archetypes_config(true)), generate_json(get_archetypes_config(false)));
+    }
+
+    #[test]
+    fn should_draw_the_unique_values_of_each_archetype_among_its_characters() {
+        let mut nicknames_file = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        nicknames_file.push("resources/test/test_nicknames_dictionary");
+        let mut config = get_archetypes_config(false);
+        config.archetypes[0].values_conf.insert(String::from("lazy_dictionaries"), toml::Value::from(true));
+        config.archetypes[1].values_conf.insert(String::from("nicknames_file"), toml::Value::from(nicknames_file.to_str().unwrap()));
+        let charusters = generate_charusters(Some(config));
+
+        let scholar_nicknames = std::fs::read_to_string(&nicknames_file).unwrap();
+        let scholar_nicknames: Vec<&str> = scholar_nicknames.lines().collect();
+        assert!(charusters.iter().filter(|c| c.archetype() == "Scholar").count() < scholar_nicknames.len());
+        for charuster in charusters.iter() {
+            assert_eq!(scholar_nicknames.contains(&charuster.nickname().as_str()), charuster.archetype() == "Scholar");
+        }
+        let nicknames: HashSet<&String> = charusters.iter().map(|c| c.nickname()).collect();
+        assert_eq!(nicknames.len(), 40);
+    }
+
+    #[test]
+    #[should_panic(expected = "Archetype Scholar overrides name, which is not generated")]
+    fn should_panic_on_archetypes_overriding_features_not_generated() {
+        let mut config = get_archetypes_config(false);
+        config.values_conf.names_file = String::new();
+        generate_charusters(Some(config));
+    }
+
+    fn get_quotas_config(parallel: bool) -> Config {
+        let mut config_file = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        config_file.push("resources/test/test_quotas_config.toml");
+        let mut config = crate::config::parse_config(config_file.to_str().unwrap());
+        config.execution_conf.parallel = parallel;
+        config
+    }
+
+    #[test]
+    fn should_meet_the_quotas() {
+        let charusters = generate_charusters(Some(get_quotas_config(false)));
+
+        let with_prop = |name: &str| charusters.iter().filter(|c| c.props().iter().any(|prop| prop.name == name)).count();
+        let with_profession = |profession: &str| charusters.iter().filter(|c| c.profession() == profession).count();
+        assert_eq!(with_prop("sub_term_1_1"), 10);
+        assert!(with_prop("sub_term_2_2") <= 1);
+        assert!(with_profession("Farmer") >= 10);
+        assert_eq!(with_profession("Acrobat"), 0);
+        assert!(charusters.iter().all(|c| c.props().iter().map(|prop| &prop.prop_type).collect::<HashSet<_>>().len() == c.props().len()));
+    }
+
+    #[test]
+    fn should_meet_the_same_quotas_in_parallel_and_sequentially() {
+        assert_eq!(generate_json(get_quotas_config(true)), generate_json(get_quotas_config(false)));
+    }
+
+    #[test]
+    fn should_count_the_legendary_characters_in_the_quotas() {
+        let mut config = get_quotas_config(false);
+        let mut legendary_file = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        legendary_file.push("resources/test/legendaries/arthur.json");
+        config.legendaries.push(crate::config::LegendaryConfig { file: legendary_file.into_os_string().into_string().unwrap(), position: None });
+        let charusters = generate_charusters(Some(config));
+
+        assert_eq!(charusters.iter().filter(|c| c.props().iter().any(|prop| prop.name == "sub_term_1_1")).count(), 10);
+        assert_eq!(charusters.iter().filter(|c| c.name() == "Arthur" && c.surname() == "Pendragon").count(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Infeasible quotas: quota 0 on props sub_term_1_1: 60 characters needed, the collection has 50")]
+    fn should_panic_on_infeasible_quotas() {
+        let mut config = get_quotas_config(false);
+        config.quotas[0].exactly = Some(crate::config::QuotaAmount::Count(60));
+        generate_charusters(Some(config));
+    }
+
+    fn get_legendaries_config(parallel: bool) -> Config {
+        let mut config_file = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        config_file.push("resources/test/test_legendaries_config.toml");
+        let mut config = crate::config::parse_config(config_file.to_str().unwrap());
+        config.execution_conf.parallel = parallel;
+        config
+    }
+
+    #[test]
+    fn should_complete_the_legendary_characters() {
+        let charusters = generate_charusters(Some(get_legendaries_config(false)));
+
+        let arthur = &charusters[0];
+        assert_eq!((arthur.name().as_str(), arthur.surname().as_str()), ("Arthur", "Pendragon"));
+        assert_eq!(arthur.profession(), "King");
+        assert_eq!(arthur.custom()["lucky_number"], 7);
+        assert_eq!(arthur.props().len(), 1);
+        assert_eq!(arthur.stats().len(), 3);
+        assert!(!arthur.birthplace().is_empty());
+
+        let merlin = charusters.iter().find(|c| c.name() == "Merlin").unwrap();
+        assert_eq!(merlin.profession(), "Wizard");
+        assert_eq!(merlin.hobbies(), &vec![String::from("alchemy"), String::from("stargazing")]);
+        assert!(!merlin.surname().is_empty());
+        // the nickname of Arthur is not drawn for the other characters
+        let nicknames: HashSet<&str> = charusters[1..].iter().map(|c| c.nickname().as_str()).collect();
+        assert_eq!(nicknames, ["term_1", "term_3"].into_iter().collect());
+    }
+
+    #[test]
+    fn should_place_the_same_legendary_characters_in_parallel_and_sequentially() {
+        assert_eq!(generate_json(get_legendaries_config(true)), generate_json(get_legendaries_config(false)));
+    }
+
+    #[test]
+    fn should_generate_only_the_features_missing_from_the_template() {
+        let mut template = CharacterBuilder::new();
+        template.surname(String::from("Pendragon"));
+        template.profession(String::from("Librarian"));
+        let charusters = generate_from_template(&get_archetypes_config(true), &template);
+
+        assert_eq!(charusters.len(), 40);
+        for charuster in charusters.iter() {
+            assert_eq!((charuster.surname().as_str(), charuster.profession().as_str()), ("Pendragon", "Librarian"));
+            // only scholars can be librarians
+            assert_eq!(charuster.archetype(), "Scholar");
+            assert!(charuster.name().starts_with("term_"));
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid template: no archetype can have the values of the template")]
+    fn should_panic_on_templates_the_config_cant_generate() {
+        let mut template = CharacterBuilder::new();
+        template.profession(String::from("Pirate"));
+        generate_from_template(&get_archetypes_config(false), &template);
+    }
+
+    fn get_append_config(export_file: &str, charusters_nums: u32) -> Config {
+        let mut config = get_legendaries_config(false);
+        config.legendaries.clear();
+        config.execution_conf.charusters_nums = charusters_nums;
+        config.execution_conf.export_to_json = true;
+        config.execution_conf.export_to_json_file = export_file.to_owned();
+        config.execution_conf.export_format = crate::export::ExportFormat::Ndjson;
+        config.execution_conf.append = true;
+        config
+    }
+
+    #[test]
+    fn should_append_to_the_exported_collection() {
+        let dir = std::env::temp_dir().join(format!("charuster_append_{}", std::process::id()));
+        let export_file = dir.join("charusters.ndjson").into_os_string().into_string().unwrap();
+        let earlier = generate_charusters(Some(get_append_config(&export_file, 2)));
+        assert_eq!(export_charusters(&get_append_config(&export_file, 1)), 3);
+
+        let charusters = import_from_file(&export_file, crate::export::ExportFormat::Ndjson);
+        assert_eq!(serde_json::to_value(&charusters[..2]).unwrap(), serde_json::to_value(&earlier).unwrap());
+        let token_ids: Vec<usize> = charusters.iter().map(|c| c.token_id()).collect();
+        assert_eq!(token_ids, vec![0, 1, 2]);
+        // the nicknames of the earlier characters are not drawn again
+        let nicknames: HashSet<&str> = charusters.iter().map(|c| c.nickname().as_str()).collect();
+        assert_eq!(nicknames.len(), 3);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn should_stream_the_characters_following_the_existing_ones() {
+        let existing = generate_charusters(Some(get_legendaries_config(false)));
+        let mut config = get_legendaries_config(false);
+        config.legendaries.clear();
+        config.char_conf.gen_nickname = false;
+        config.execution_conf.charusters_nums = 2;
+
+        let stream = CharusterStream::appending(&config, &GeneratorRegistry::new(), &existing);
+        assert_eq!(stream.len(), 2);
+        let token_ids: Vec<usize> = stream.map(|c| c.token_id()).collect();
+        assert_eq!(token_ids, vec![3, 4]);
+    }
+
+    #[test]
+    fn should_place_only_the_legendaries_not_appended_yet() {
+        let mut config = get_legendaries_config(false);
+        let merlin = config.legendaries.remove(1);
+        let existing = generate_charusters(Some(config));
+        let mut config = get_legendaries_config(false);
+        config.legendaries[1] = merlin;
+        config.char_conf.gen_nickname = false;
+        config.execution_conf.charusters_nums = 2;
+
+        let appended: Vec<Charuster> = CharusterStream::appending(&config, &GeneratorRegistry::new(), &existing).collect();
+        let is = |charuster: &Charuster, name: &str, profession: &str| charuster.name() == name && charuster.profession() == profession;
+        assert_eq!(existing.iter().filter(|c| is(c, "Arthur", "King")).count(), 1);
+        assert!(!appended.iter().any(|c| is(c, "Arthur", "King")));
+        assert_eq!(appended.iter().filter(|c| is(c, "Merlin", "Wizard")).count(), 1);
+    }
+
+    struct LuckyNumberGenerator {
+        max: u32,
+    }
+
+    impl FeatureGenerator for LuckyNumberGenerator {
+        fn generate(&self, _index: usize, rng: &mut dyn RngCore, _character: &CharacterBuilder) -> Option<CharacterFeature> {
+            Some(CharacterFeature::CUSTOM(String::from("lucky_number"), serde_json::Value::from(rng.gen_range(1..=self.max))))
+        }
+    }
+
+    fn get_lucky_number_registry() -> GeneratorRegistry {
+        let mut registry = GeneratorRegistry::new();
+        registry.register("lucky_number", |config: &Config| {
+            Box::new(LuckyNumberGenerator { max: config.execution_conf.charusters_nums })
+        });
+        registry
+    }
+
+    #[test]
+    fn should_run_the_enabled_custom_generators() {
+        let mut config = get_seeded_config(42, true);
+        config.char_conf.custom_features = vec![String::from("lucky_number")];
+        let charusters = generate_charusters_with(config, &get_lucky_number_registry());
+
+        for charuster in charusters.iter() {
+            let lucky_number = charuster.custom()["lucky_number"].as_u64().unwrap();
+            assert!((1..=50).contains(&lucky_number));
+        }
+    }
+
+    #[test]
+    fn should_not_run_the_custom_generators_not_enabled() {
+        let charusters = generate_charusters_with(get_seeded_config(42, false), &get_lucky_number_registry());
+        assert!(charusters.iter().all(|charuster| charuster.custom().is_empty()));
+    }
+
+    #[test]
+    #[should_panic(expected = "Custom feature unknown is not registered")]
+    fn should_panic_when_enabling_a_custom_generator_not_registered() {
+        let mut config = get_seeded_config(42, false);
+        config.char_conf.custom_features = vec![String::from("unknown")];
+        generate_charusters_with(config, &get_lucky_number_registry());
+    }
+
+    struct InitialsGenerator;
+
+    impl FeatureGenerator for InitialsGenerator {
+        fn dependencies(&self) -> Vec<String> {
+            vec![String::from("name"), String::from("surname")]
+        }
+
+        fn generate(&self, _index: usize, _rng: &mut dyn RngCore, character: &CharacterBuilder) -> Option<CharacterFeature> {
+            let initial = |feature: &str| character.feature(feature)?.as_str()?.chars().next();
+            let initials: String = [initial("name")?, initial("surname")?].iter().collect();
+            Some(CharacterFeature::CUSTOM(String::from("initials"), serde_json::Value::from(initials)))
+        }
+    }
+
+    fn get_initials_config() -> (Config, GeneratorRegistry) {
+        let mut config = get_seeded_config(42, false);
+        config.execution_conf.charusters_nums = 5;
+        config.char_conf.custom_features = vec![String::from("initials")];
+        let mut registry = GeneratorRegistry::new();
+        registry.register("initials", |_: &Config| Box::new(InitialsGenerator));
+        (config, registry)
+    }
+
+    fn feature_names(generators: &[NamedGenerator]) -> Vec<&str> {
+        generators.iter().map(|gen| gen.name.as_str()).collect()
+    }
+
+    #[test]
+    fn should_generate_features_after_their_dependencies() {
+        let (mut config, registry) = get_initials_config();
+        config.char_conf.dependencies.insert(String::from("name"), vec![String::from("props")]);
+        config.char_conf.dependencies.insert(String::from("nickname"), vec![String::from("initials")]);
+
+        let generators = registry.create_generators(&config);
+        let names = feature_names(&generators);
+        let position = |feature: &str| names.iter().position(|name| *name == feature).unwrap();
+        assert!(position("props") < position("name"));
+        assert!(position("name") < position("initials"));
+        assert!(position("surname") < position("initials"));
+        assert!(position("initials") < position("nickname"));
+
+        for charuster in generate_charusters_with(config, &registry) {
+            let initials = format!("{}{}", charuster.name().chars().next().unwrap(), charuster.surname().chars().next().unwrap());
+            assert_eq!(charuster.custom()["initials"], initials);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Cyclic dependency between features: name -> initials -> name")]
+    fn should_panic_on_dependency_cycles() {
+        let (mut config, registry) = get_initials_config();
+        config.char_conf.dependencies.insert(String::from("name"), vec![String::from("initials")]);
+        registry.create_generators(&config);
+    }
+
+    #[test]
+    fn should_fill_the_description_templates() {
+        let mut config = get_seeded_config(42, true);
+        config.execution_conf.charusters_nums = 3;
+        config.char_conf.gen_description = true;
+        let mut descriptions = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        descriptions.push("resources/test/test_descriptions");
+        config.values_conf.description_files = descriptions.into_os_string().into_string().unwrap();
+
+        let generators = create_generators(&config);
+        let description = generators.iter().find(|gen| gen.name == "description").unwrap();
+        assert_eq!(description.dependencies, DESCRIPTION_DEPENDENCIES);
+
+        let mut expected: Vec<String> = vec![];
+        let mut descriptions: Vec<String> = vec![];
+        for charuster in generate_charusters(Some(config)) {
+            expected.push(format!("{} {}, the {}", charuster.name(), charuster.surname(), charuster.profession()));
+            expected.push(format!("A {} called {}", charuster.profession(), charuster.nickname()));
+            expected.push(format!("{} from nowhere", charuster.name()));
+            descriptions.push(charuster.description().clone());
+        }
+        assert!(descriptions.iter().all(|description| expected.contains(description)), "{:?}", descriptions);
+    }
+
+    fn get_attribute(name: &str, kind: AttributeKind) -> AttributeConfig {
+        let mut source = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        source.push("resources/test/test_dictionary");
+        AttributeConfig {
+            name: name.to_owned(),
+            kind,
+            source: source.into_os_string().into_string().unwrap(),
+            count: 2,
+            min: None,
+            max: None,
+            distribution: Distribution::Uniform,
+        }
+    }
+
+    #[test]
+    fn should_generate_the_custom_attributes() {
+        let mut config = get_seeded_config(42, false);
+        config.execution_conf.charusters_nums = 3;
+        let mut luck = get_attribute("luck", AttributeKind::Number);
+        luck.min = Some(1);
+        luck.max = Some(20);
+        let mut year = get_attribute("year", AttributeKind::Date);
+        year.min = Some(2000);
+        year.max = Some(2001);
+        config.attributes = vec![
+            get_attribute("alignment", AttributeKind::Choose),
+            get_attribute("relic", AttributeKind::ChooseAndRemove),
+            get_attribute("titles", AttributeKind::Vector),
+            luck,
+            year,
+        ];
+
+        let charusters = generate_charusters(Some(config));
+        let relics: HashSet<&str> = charusters.iter().map(|c| c.custom()["relic"].as_str().unwrap()).collect();
+        assert_eq!(relics.len(), 3);
+        for charuster in charusters.iter() {
+            let custom = charuster.custom();
+            assert!(custom["alignment"].as_str().unwrap().starts_with("term_"));
+            let titles = custom["titles"].as_array().unwrap();
+            assert_eq!(titles.len(), 2);
+            assert_ne!(titles[0], titles[1]);
+            assert!((1..=20).contains(&custom["luck"].as_i64().unwrap()));
+            let timestamp: i64 = custom["year"].as_str().unwrap().parse().unwrap();
+            assert!((2000..=2001).contains(&Utc.timestamp_opt(timestamp, 0).unwrap().year()));
+        }
+    }
+
+    #[test]
+    fn should_return_numbers_following_the_distribution() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let uniform: Vec<i64> = (0..10000).map(|_| get_random_number(0, 100, Distribution::Uniform, &mut rng)).collect();
+        let normal: Vec<i64> = (0..10000).map(|_| get_random_number(0, 100, Distribution::Normal, &mut rng)).collect();
+        assert!(uniform.iter().chain(normal.iter()).all(|n| (0..=100).contains(n)));
+
+        let in_center = |numbers: &[i64]| numbers.iter().filter(|n| (40..=60).contains(*n)).count();
+        assert!(in_center(&uniform) < 2500);
+        assert!(in_center(&normal) > 4500);
+        assert_eq!(get_random_number(5, 5, Distribution::Normal, &mut rng), 5);
+    }
+
+    #[test]
+    fn should_stream_the_same_charusters_generated_at_once() {
+        let mut config = get_seeded_config(42, true);
+        config.execution_conf.charusters_nums = PARALLEL_CHUNK_SIZE as u32 + 10;
+        // there aren't enough birthplaces for more than a chunk of characters
+        config.char_conf.gen_birthplace = false;
+        let mut stream = CharusterStream::new(&config);
+        assert_eq!(stream.len(), PARALLEL_CHUNK_SIZE + 10);
+        stream.next();
+        assert_eq!(stream.len(), PARALLEL_CHUNK_SIZE + 9);
+
+        let streamed: Vec<Charuster> = CharusterStream::new(&config).collect();
+        assert_eq!(serde_json::to_string(&streamed).unwrap(), generate_json(config));
+    }
+
+    #[test]
+    fn should_not_repeat_values_drawn_without_replacement_in_parallel() {
+        let charusters = generate_charusters(Some(get_seeded_config(42, true)));
+        let nicknames: HashSet<&String> = charusters.iter().map(|c| c.nickname()).collect();
+        let birthplaces: HashSet<&String> = charusters.iter().map(|c| c.birthplace()).collect();
+        assert_eq!(nicknames.len(), charusters.len());
+        assert_eq!(birthplaces.len(), charusters.len());
     }
 
     #[test]
     fn should_return_random_date_in_the_expected_interval() {
         let min_year = 1900;
         let max_year = 1950;
-        for _ in (0..1000).into_iter() {
-            let gen_time = get_random_date(min_year, max_year);
+        for _ in 0..1000 {
+            let gen_time = get_random_date(min_year, max_year, Distribution::Uniform, &mut thread_rng());
             assert!(gen_time.year() >= min_year as i32 && gen_time.year() <= max_year as i32);
         }
     }
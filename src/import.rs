@@ -0,0 +1,130 @@
+use std::fs::File;
+use std::io;
+use std::io::{BufRead, BufReader, Read};
+
+use serde_json::{Map, Value};
+
+use crate::character::Charuster;
+use crate::export::ExportFormat;
+
+/// columns of the CSV exports holding JSON values; the others are plain text
+const JSON_COLUMNS: [&str; 6] = ["token_id", "hobbies", "props", "levels", "stats", "custom"];
+
+/// read the characters exported in the given format from `reader`
+pub fn import<R: Read>(reader: R, format: ExportFormat) -> io::Result<Vec<Charuster>> {
+    match format {
+        ExportFormat::Json => Ok(serde_json::from_reader(reader)?),
+        ExportFormat::Ndjson => import_ndjson(reader),
+        ExportFormat::Csv => import_csv(reader),
+    }
+}
+
+/// read the characters exported to a file in the given format
+pub fn import_from_file(filename: &str, format: ExportFormat) -> Vec<Charuster> {
+    let file = File::open(filename).unwrap_or_else(|e| panic!("Could not open collection {}: {}", filename, e));
+    import(BufReader::new(file), format).unwrap_or_else(|e| panic!("Could not read collection {}: {}", filename, e))
+}
+
+fn import_ndjson<R: Read>(reader: R) -> io::Result<Vec<Charuster>> {
+    let mut charusters = vec![];
+    for line in BufReader::new(reader).lines() {
+        let line = line?;
+        if !line.trim().is_empty() {
+            charusters.push(serde_json::from_str(&line)?);
+        }
+    }
+    Ok(charusters)
+}
+
+// a row per character: text cells are read as they are, the cells of the vector features and of the
+// custom features as JSON. Empty JSON cells are missing features
+fn import_csv<R: Read>(reader: R) -> io::Result<Vec<Charuster>> {
+    let mut csv_reader = csv::Reader::from_reader(reader);
+    let headers = csv_reader.headers()?.clone();
+    let mut charusters = vec![];
+    for record in csv_reader.records() {
+        let record = record?;
+        let mut fields = Map::new();
+        for (column, cell) in headers.iter().zip(record.iter()) {
+            let value = if JSON_COLUMNS.contains(&column) {
+                if cell.is_empty() {
+                    continue;
+                }
+                serde_json::from_str(cell)?
+            } else {
+                Value::from(cell)
+            };
+            fields.insert(column.to_owned(), value);
+        }
+        charusters.push(serde_json::from_value(Value::Object(fields))?);
+    }
+    Ok(charusters)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::character::{Level, Property};
+    use crate::export::export;
+
+    use super::*;
+
+    fn get_test_charusters() -> Vec<Charuster> {
+        (0..3).map(|i| {
+            let mut builder = Charuster::builder();
+            builder.token_id(i as usize + 10);
+            builder.name(format!("Name {}", i));
+            builder.surname(String::from("Surname, \"quoted\""));
+            builder.hobbies(vec![String::from("chess"), String::from("poker")]);
+            builder.props(vec![Property::from_path(vec![String::from("Weapons"), String::from("Katana")])]);
+            builder.levels(vec![Level { name: String::from("Strength"), value: i, max_value: 100 }]);
+            builder.archetype(String::from("Brute"));
+            builder.custom(String::from("luck"), Value::from(i));
+            builder.build()
+        }).collect()
+    }
+
+    fn round_trip(format: ExportFormat) {
+        let charusters = get_test_charusters();
+        let mut exported = vec![];
+        export(&charusters, format, &mut exported).unwrap();
+
+        let imported = import(exported.as_slice(), format).unwrap();
+        assert_eq!(serde_json::to_value(&imported).unwrap(), serde_json::to_value(&charusters).unwrap());
+    }
+
+    #[test]
+    fn should_import_json_exports() {
+        round_trip(ExportFormat::Json);
+    }
+
+    #[test]
+    fn should_import_ndjson_exports() {
+        round_trip(ExportFormat::Ndjson);
+    }
+
+    #[test]
+    fn should_import_csv_exports() {
+        round_trip(ExportFormat::Csv);
+    }
+
+    #[test]
+    fn should_import_csv_exports_of_characters_with_different_features() {
+        let mut charusters = get_test_charusters();
+        charusters[0] = Charuster::builder().build();
+        let mut builder = charusters.remove(2).into_builder();
+        builder.custom(String::from("mood"), Value::from("grumpy"));
+        charusters.push(builder.build());
+        let mut exported = vec![];
+        export(&charusters, ExportFormat::Csv, &mut exported).unwrap();
+
+        let imported = import(exported.as_slice(), ExportFormat::Csv).unwrap();
+        assert_eq!(serde_json::to_value(&imported).unwrap(), serde_json::to_value(&charusters).unwrap());
+    }
+
+    #[test]
+    fn should_report_invalid_exports() {
+        assert!(import("[{\"name\": 3}]".as_bytes(), ExportFormat::Json).is_err());
+        assert!(import("name,hobbies\nMario,[chess\n".as_bytes(), ExportFormat::Csv).is_err());
+    }
+}
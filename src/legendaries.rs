@@ -0,0 +1,204 @@
+use std::collections::HashMap;
+use std::fs::read_to_string;
+use std::path::Path;
+
+use rand::prelude::*;
+use serde_json::{Map, Value};
+
+use crate::character::{CharacterBuilder, Charuster};
+use crate::config::{Config, LegendaryConfig};
+use crate::generator::NamedGenerator;
+
+/// the features set by a pre-authored character, by name
+pub type LockedFeatures = Map<String, Value>;
+
+/// read the features of a pre-authored character from a JSON or TOML file (picked by the extension). The
+/// entries of the `custom` map are features too, empty values are left to the generators and the token id
+/// is the one of the position the character takes
+pub fn read_character(filename: &str) -> Result<LockedFeatures, String> {
+    let content = read_to_string(filename).map_err(|e| format!("{} can't be read: {}", filename, e))?;
+    let value: Value = if Path::new(filename).extension().is_some_and(|ext| ext == "toml") {
+        let value: toml::Value = toml::from_str(&content).map_err(|e| format!("{} is not valid TOML: {}", filename, e))?;
+        serde_json::to_value(value).map_err(|e| e.to_string())?
+    } else {
+        serde_json::from_str(&content).map_err(|e| format!("{} is not valid JSON: {}", filename, e))?
+    };
+    locked_features(value).map_err(|e| format!("{}: {}", filename, e))
+}
+
+/// the features set in a character written as it's exported, see `read_character`
+pub fn locked_features(value: Value) -> Result<LockedFeatures, String> {
+    let mut fields = match value {
+        Value::Object(fields) => fields,
+        _ => return Err(String::from("it does not describe a character")),
+    };
+    fields.remove("token_id");
+    if let Some(custom) = fields.remove("custom") {
+        match custom {
+            Value::Object(custom) => fields.extend(custom),
+            _ => return Err(String::from("the custom features are not a table")),
+        }
+    }
+
+    let mut features = LockedFeatures::new();
+    for (name, value) in fields.into_iter().filter(|(_, value)| is_set(value)) {
+        CharacterBuilder::new().set_feature(&name, value.clone())?;
+        features.insert(name, value);
+    }
+    Ok(features)
+}
+
+/// the features of a generated character, custom ones included, to lock them
+pub(crate) fn features_of(charuster: &Charuster) -> LockedFeatures {
+    locked_features(serde_json::to_value(charuster).unwrap()).unwrap_or_else(|e| panic!("{}", e))
+}
+
+/// the terms of a feature: its text, or the texts or names of its items
+pub(crate) fn terms(value: &Value) -> Vec<String> {
+    match value {
+        Value::String(value) => vec![value.clone()],
+        Value::Array(values) => values.iter().filter_map(|v| v.get("name").unwrap_or(v).as_str().map(str::to_owned)).collect(),
+        _ => vec![],
+    }
+}
+
+fn is_set(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::String(value) => !value.is_empty(),
+        Value::Array(values) => !values.is_empty(),
+        _ => true,
+    }
+}
+
+/// the problems of the legendary characters of the config, by index, None for the problems of all of them
+pub fn check_legendaries(config: &Config) -> Vec<(Option<usize>, String)> {
+    let total = config.execution_conf.charusters_nums as usize;
+    let mut problems = vec![];
+    for (i, legendary) in config.legendaries.iter().enumerate() {
+        if let Err(message) = read_character(&legendary.file) {
+            problems.push((Some(i), message));
+        }
+        match legendary.position {
+            Some(position) if position >= total =>
+                problems.push((Some(i), format!("position {} is out of the collection of {} characters", position, total))),
+            Some(position) if config.legendaries[..i].iter().any(|other| other.position == Some(position)) =>
+                problems.push((Some(i), format!("position {} is taken by another legendary character", position))),
+            _ => {}
+        }
+    }
+    if config.legendaries.len() > total {
+        problems.push((None, format!("{} legendary characters, the collection has {}", config.legendaries.len(), total)));
+    }
+    problems
+}
+
+/// read the legendary characters of the config and place them in a collection of `count` characters: at their
+/// position, or at a random position not taken. The legendaries already among the `existing` characters are
+/// skipped. Panics if they can't be read or placed
+pub fn place_legendaries(config: &Config, count: usize, existing: &[LockedFeatures], rng: &mut dyn RngCore) -> HashMap<usize, LockedFeatures> {
+    let legendaries: Vec<(&LegendaryConfig, LockedFeatures)> = config.legendaries.iter()
+        .map(|legendary| (legendary, read_character(&legendary.file).unwrap_or_else(|e| panic!("{}", e))))
+        .filter(|(_, features)| !is_placed(features, existing))
+        .collect();
+    if legendaries.len() > count {
+        panic!("Could not place {} legendary characters in a collection of {}", legendaries.len(), count);
+    }
+    let mut placed = HashMap::new();
+    let (positioned, unplaced): (Vec<_>, Vec<_>) = legendaries.into_iter().partition(|(legendary, _)| legendary.position.is_some());
+    for (legendary, features) in positioned {
+        let position = legendary.position.unwrap();
+        if position >= count || placed.contains_key(&position) {
+            panic!("Could not place legendary character {} at position {}", legendary.file, position);
+        }
+        placed.insert(position, features);
+    }
+    if !unplaced.is_empty() {
+        let free: Vec<usize> = (0..count).filter(|i| !placed.contains_key(i)).collect();
+        let positions: Vec<usize> = free.choose_multiple(rng, unplaced.len()).copied().collect();
+        for ((_, features), position) in unplaced.into_iter().zip(positions) {
+            placed.insert(position, features);
+        }
+    }
+    placed
+}
+
+// whether one of the characters has every feature the legendary character sets, as they are exported
+fn is_placed(legendary: &LockedFeatures, characters: &[LockedFeatures]) -> bool {
+    let mut builder = CharacterBuilder::new();
+    for (name, value) in legendary {
+        builder.set_feature(name, value.clone()).unwrap_or_else(|e| panic!("Could not set {}: {}", name, e));
+    }
+    let features = builder.features();
+    characters.iter().any(|character| features.iter().all(|(name, value)| character.get(name) == Some(value)))
+}
+
+/// tell the generators the features set beforehand for some characters, like the legendary ones, so they
+/// don't generate them nor draw their values for the other characters
+pub fn lock_features(generators: &mut [NamedGenerator], locked: &HashMap<usize, LockedFeatures>) {
+    for gen in generators.iter_mut() {
+        for (index, features) in locked.iter() {
+            if let Some(value) = features.get(&gen.name) {
+                gen.generator.lock(*index, value);
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::config::LegendaryConfig;
+
+    use super::*;
+
+    fn get_test_filename(name: &str) -> String {
+        let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        d.push("resources/test/legendaries");
+        d.push(name);
+        d.into_os_string().into_string().unwrap()
+    }
+
+    #[test]
+    fn should_read_characters_from_json_and_toml() {
+        let arthur = read_character(&get_test_filename("arthur.json")).unwrap();
+        assert_eq!(arthur["name"], "Arthur");
+        assert_eq!(arthur["lucky_number"], 7);
+        assert!(!arthur.contains_key("description"));
+
+        let merlin = read_character(&get_test_filename("merlin.toml")).unwrap();
+        assert_eq!(merlin["profession"], "Wizard");
+        assert_eq!(merlin["hobbies"], serde_json::json!(["alchemy", "stargazing"]));
+    }
+
+    #[test]
+    fn should_place_the_legendaries_at_their_positions() {
+        let mut config: Config = toml::from_str("").unwrap();
+        config.execution_conf.charusters_nums = 4;
+        config.legendaries = vec![
+            LegendaryConfig { file: get_test_filename("merlin.toml"), position: None },
+            LegendaryConfig { file: get_test_filename("arthur.json"), position: Some(2) },
+        ];
+
+        let placed = place_legendaries(&config, 4, &[], &mut StdRng::seed_from_u64(1));
+        assert_eq!(placed.len(), 2);
+        assert_eq!(placed[&2]["name"], "Arthur");
+        assert!(placed.iter().any(|(i, features)| *i != 2 && features["name"] == "Merlin"));
+    }
+
+    #[test]
+    fn should_report_legendaries_that_cant_be_placed() {
+        let mut config: Config = toml::from_str("").unwrap();
+        config.execution_conf.charusters_nums = 2;
+        config.legendaries = vec![
+            LegendaryConfig { file: get_test_filename("arthur.json"), position: Some(1) },
+            LegendaryConfig { file: get_test_filename("merlin.toml"), position: Some(1) },
+            LegendaryConfig { file: get_test_filename("not_existing.json"), position: Some(2) },
+        ];
+
+        let problems: Vec<Option<usize>> = check_legendaries(&config).into_iter().map(|(i, _)| i).collect();
+        assert_eq!(problems, vec![Some(1), Some(2), Some(2), None]);
+    }
+}
@@ -1,4 +1,19 @@
-mod dictionary;
+pub mod dictionary;
+mod dependencies;
+pub mod diff;
+mod formats;
+pub mod legendaries;
+mod mapped;
+mod quotas;
+mod sanitation;
+mod syllables;
+pub mod templates;
 pub mod generator;
 pub mod character;
 pub mod config;
+pub mod lint;
+pub mod export;
+pub mod import;
+pub mod collections;
+pub mod reroll;
+pub mod validation;
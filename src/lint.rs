@@ -0,0 +1,470 @@
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::config::{AttributeKind, Config, SanitationConfig, ValuesConfig};
+use crate::dictionary::{SimpleDictionary, TaxonomyDictionary};
+use crate::formats::{DictionaryFormat, Term};
+use crate::generator::VEC_FEATURES_SIZE;
+use crate::sanitation::sanitize_terms;
+use crate::syllables::{SYLLABLES_FILE_SUFFIX, SyllableDictionary};
+
+/// terms longer than this (in chars) are reported, except in description dictionaries
+pub const MAX_TERM_LENGTH: usize = 64;
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    /// the generation would fail or produce wrong results
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum IssueKind {
+    MissingSource,
+    UnreadableSource,
+    Duplicate,
+    EmptyEntry,
+    SuspiciousCharacters,
+    TooLong,
+    EmptyTaxonomy,
+    InsufficientCapacity,
+    /// a change made to the terms by the sanitation while the dictionary is loaded
+    Sanitized,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LintIssue {
+    /// the `values_conf` key referencing the dictionary
+    pub key: String,
+    pub source: String,
+    pub kind: IssueKind,
+    pub severity: Severity,
+    /// 1-based position of the term: the line for plain text files, the entry for the other formats
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub entry: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub term: Option<String>,
+    pub message: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LintReport {
+    pub errors: usize,
+    pub warnings: usize,
+    pub issues: Vec<LintIssue>,
+}
+
+impl LintReport {
+    pub fn has_errors(&self) -> bool {
+        self.errors > 0
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+
+    /// one line per issue followed by a summary line
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        for issue in self.issues.iter() {
+            let location = match issue.entry {
+                Some(entry) => format!("{}:{}", issue.source, entry),
+                None => issue.source.clone(),
+            };
+            let severity = match issue.severity {
+                Severity::Error => "error",
+                Severity::Warning => "warning",
+            };
+            text.push_str(&format!("{}: {} [{}] {}\n", location, severity, issue.key, issue.message));
+        }
+        text.push_str(&format!("{} errors, {} warnings\n", self.errors, self.warnings));
+        text
+    }
+}
+
+enum SourceKind {
+    Terms,
+    Taxonomy,
+    Folder,
+}
+
+// a dictionary referenced by the config, with the requirements of the generator using it
+struct Source<'a> {
+    key: &'a str,
+    filename: &'a str,
+    kind: SourceKind,
+    enabled: bool,
+    /// distinct terms (or root categories for taxonomies) needed to generate the collection
+    required_terms: usize,
+    check_length: bool,
+}
+
+/// load every dictionary referenced in the `values_conf` section and by the custom attributes, reporting the problems found.
+/// Disabled features are linted as well, but their capacity is not checked and their missing files are warnings
+pub fn lint_dictionaries(config: &Config) -> LintReport {
+    let char_conf = &config.char_conf;
+    let values = &config.values_conf;
+    let nums = config.execution_conf.charusters_nums as usize;
+    let vec_size = VEC_FEATURES_SIZE as usize;
+
+    let sources = vec![
+        Source { key: "names_file", filename: &values.names_file, kind: SourceKind::Terms, enabled: char_conf.gen_name, required_terms: 1, check_length: true },
+        Source { key: "surnames_file", filename: &values.surnames_file, kind: SourceKind::Terms, enabled: char_conf.gen_surname, required_terms: 1, check_length: true },
+        Source { key: "nicknames_file", filename: &values.nicknames_file, kind: SourceKind::Terms, enabled: char_conf.gen_nickname, required_terms: nums, check_length: true },
+        Source { key: "birthplaces_file", filename: &values.birthplaces_file, kind: SourceKind::Terms, enabled: char_conf.gen_birthplace, required_terms: nums, check_length: true },
+        Source { key: "description_files", filename: &values.description_files, kind: SourceKind::Terms, enabled: char_conf.gen_description, required_terms: nums, check_length: false },
+        Source { key: "images_folder", filename: &values.images_folder, kind: SourceKind::Folder, enabled: char_conf.gen_image, required_terms: nums, check_length: false },
+        Source { key: "professions_file", filename: &values.professions_file, kind: SourceKind::Terms, enabled: char_conf.gen_profession, required_terms: 1, check_length: true },
+        Source { key: "hobbies_file", filename: &values.hobbies_file, kind: SourceKind::Terms, enabled: char_conf.gen_hobbies, required_terms: 1, check_length: true },
+        Source { key: "props_file", filename: &values.props_file, kind: SourceKind::Taxonomy, enabled: char_conf.gen_props, required_terms: vec_size, check_length: true },
+        Source { key: "levels_file", filename: &values.levels_file, kind: SourceKind::Terms, enabled: char_conf.gen_levels, required_terms: vec_size, check_length: true },
+        Source { key: "stats_file", filename: &values.stats_file, kind: SourceKind::Terms, enabled: char_conf.gen_stats, required_terms: vec_size, check_length: true },
+    ];
+    let attribute_keys: Vec<String> = config.attributes.iter().map(|attribute| format!("attributes.{}", attribute.name)).collect();
+    let attribute_sources = config.attributes.iter().zip(attribute_keys.iter())
+        .filter(|(attribute, _)| matches!(attribute.kind, AttributeKind::Choose | AttributeKind::ChooseAndRemove | AttributeKind::Vector))
+        .map(|(attribute, key)| {
+            let required_terms = match attribute.kind {
+                AttributeKind::ChooseAndRemove => nums,
+                AttributeKind::Vector => attribute.count as usize,
+                _ => 1,
+            };
+            Source { key, filename: &attribute.source, kind: SourceKind::Terms, enabled: true, required_terms, check_length: true }
+        });
+    let sources: Vec<Source> = sources.into_iter().chain(attribute_sources).collect();
+
+    let mut issues = vec![];
+    for source in sources.iter().filter(|source| !source.filename.is_empty()) {
+        lint_source(source, values, &mut issues);
+    }
+
+    let errors = issues.iter().filter(|issue| issue.severity == Severity::Error).count();
+    LintReport { errors, warnings: issues.len() - errors, issues }
+}
+
+fn lint_source(source: &Source, values: &ValuesConfig, issues: &mut Vec<LintIssue>) {
+    let sanitation = &values.sanitation;
+    let mut report = |kind, severity, entry, term: Option<&str>, message: String| {
+        issues.push(LintIssue {
+            key: source.key.to_owned(),
+            source: source.filename.to_owned(),
+            kind,
+            severity,
+            entry,
+            term: term.map(|t| t.to_owned()),
+            message,
+        });
+    };
+
+    let (format, path) = DictionaryFormat::from_filename(source.filename);
+    if !Path::new(path).exists() {
+        let severity = if source.enabled { Severity::Error } else { Severity::Warning };
+        report(IssueKind::MissingSource, severity, None, None, format!("{} not found", path));
+        return;
+    }
+    // syllable inventories have no terms to check, only their patterns
+    if source.filename.ends_with(SYLLABLES_FILE_SUFFIX) {
+        if let Err(cause) = SyllableDictionary::from_file(source.filename) {
+            report(IssueKind::UnreadableSource, Severity::Error, None, None, cause);
+        }
+        return;
+    }
+
+    let loaded = match source.kind {
+        SourceKind::Terms => SimpleDictionary::try_new_sanitized(source.filename, &SanitationConfig::disabled()).map(|(dict, _)| Loaded::Terms(dict)),
+        SourceKind::Taxonomy => TaxonomyDictionary::try_new(source.filename).map(Loaded::Taxonomy),
+        SourceKind::Folder => SimpleDictionary::try_new_from_folder(source.filename).map(Loaded::Terms),
+    };
+    let loaded = match loaded {
+        Ok(loaded) => loaded,
+        Err(cause) => {
+            report(IssueKind::UnreadableSource, Severity::Error, None, None, cause);
+            return;
+        }
+    };
+
+    let (terms, capacity): (Vec<(Option<usize>, String)>, usize) = match &loaded {
+        Loaded::Terms(dict) => {
+            let skip_comments = sanitation.skip_comments && format == DictionaryFormat::Text;
+            let terms: Vec<(usize, &Term)> = dict.terms().iter().enumerate()
+                .filter(|(_, term)| !(skip_comments && term.value.trim_start().starts_with('#')))
+                .collect();
+            let distinct = terms.iter()
+                .map(|(_, term)| term.value.trim().to_lowercase())
+                .filter(|value| !value.is_empty())
+                .collect::<HashSet<String>>()
+                .len();
+            let values = terms.into_iter().map(|(i, term)| (Some(i + 1), term.value.clone())).collect();
+            (values, distinct)
+        }
+        Loaded::Taxonomy(dict) => {
+            for empty in dict.empty_taxonomies() {
+                report(IssueKind::EmptyTaxonomy, Severity::Warning, None, None,
+                       format!("taxonomy {} has no terms", empty.join(" > ")));
+            }
+            let values = dict.term_paths().into_iter().map(|path| (None, path.last().unwrap().clone())).collect();
+            (values, dict.kinds_with_terms())
+        }
+    };
+
+    let mut first_occurrences: HashMap<String, Option<usize>> = HashMap::new();
+    for (entry, value) in terms.iter() {
+        let entry = *entry;
+        if value.trim().is_empty() {
+            report(IssueKind::EmptyEntry, Severity::Warning, entry, Some(value), String::from("empty term"));
+            continue;
+        }
+        if let Some(description) = describe_suspicious_characters(value) {
+            report(IssueKind::SuspiciousCharacters, Severity::Warning, entry, Some(value),
+                   format!("term {:?} contains {}", value, description));
+        }
+        let length = value.chars().count();
+        if source.check_length && length > MAX_TERM_LENGTH {
+            report(IssueKind::TooLong, Severity::Warning, entry, Some(value),
+                   format!("term {:?} is {} chars long (max {})", value, length, MAX_TERM_LENGTH));
+        }
+        let key = value.trim().to_lowercase();
+        match first_occurrences.get(&key) {
+            Some(first) => {
+                let first = first.map(|f| format!(" (first at {})", f)).unwrap_or_default();
+                report(IssueKind::Duplicate, Severity::Warning, entry, Some(value), format!("duplicated term {:?}{}", value, first));
+            }
+            None => {
+                first_occurrences.insert(key, entry);
+            }
+        }
+    }
+
+    // the terms were loaded as they are in the file, the sanitation runs on them to report its changes.
+    // Lazy dictionaries only go through some of its steps
+    let lazy = sanitation.lazy();
+    let sanitation = if values.lazy_dictionaries && format == DictionaryFormat::Text { &lazy } else { sanitation };
+    if let (SourceKind::Terms, Loaded::Terms(dict)) = (&source.kind, &loaded) {
+        let (_, changes) = sanitize_terms(source.filename, dict.terms().to_vec(), sanitation, format == DictionaryFormat::Text);
+        for change in changes.changes() {
+            report(IssueKind::Sanitized, Severity::Warning, None, None, change);
+        }
+    }
+
+    if source.enabled && capacity < source.required_terms {
+        let unit = match source.kind {
+            SourceKind::Taxonomy => "non empty categories",
+            _ => "distinct terms",
+        };
+        report(IssueKind::InsufficientCapacity, Severity::Error, None, None,
+               format!("{} {} available, {} needed to generate the collection", capacity, unit, source.required_terms));
+    }
+}
+
+enum Loaded {
+    Terms(SimpleDictionary),
+    Taxonomy(TaxonomyDictionary),
+}
+
+// describe the invisible or unexpected characters of a term, if any
+fn describe_suspicious_characters(value: &str) -> Option<String> {
+    let mut found = vec![];
+    if value.trim() != value {
+        found.push(String::from("leading or trailing whitespace"));
+    }
+    for c in value.chars() {
+        let suspicious = c.is_control()
+            || matches!(c, '\u{FFFD}' | '\u{200B}'..='\u{200D}' | '\u{2060}' | '\u{FEFF}' | '\u{00A0}');
+        if suspicious {
+            let description = format!("U+{:04X}", c as u32);
+            if !found.contains(&description) {
+                found.push(description);
+            }
+        }
+    }
+    if found.is_empty() { None } else { Some(found.join(", ")) }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::config::parse_config;
+
+    use super::*;
+
+    fn get_resource_filename(name: &str) -> String {
+        let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        d.push("resources/".to_owned() + name);
+        d.into_os_string().into_string().unwrap()
+    }
+
+    // the test config with every dictionary pointing at the three terms test dictionary
+    fn get_test_config() -> Config {
+        let mut config = parse_config(get_resource_filename("test/test_config.toml").as_str());
+        let dictionary = get_resource_filename("test/test_dictionary");
+        config.execution_conf.charusters_nums = 3;
+        config.values_conf.names_file = dictionary.clone();
+        config.values_conf.surnames_file = dictionary.clone();
+        config.values_conf.nicknames_file = dictionary.clone();
+        config.values_conf.birthplaces_file = dictionary.clone();
+        config.values_conf.hobbies_file = dictionary.clone();
+        config.values_conf.professions_file = dictionary.clone();
+        config.values_conf.stats_file = dictionary.clone();
+        config.values_conf.levels_file = dictionary.clone();
+        config.values_conf.description_files = dictionary;
+        config.values_conf.props_file = get_resource_filename("test/test_two_levels_dictionary.json");
+        config.values_conf.images_folder = get_resource_filename("test/image_dir");
+        for attribute in config.attributes.iter_mut() {
+            attribute.source = get_resource_filename("test/test_dictionary");
+        }
+        config
+    }
+
+    fn issues_of<'a>(report: &'a LintReport, key: &str) -> Vec<&'a LintIssue> {
+        report.issues.iter().filter(|issue| issue.key == key).collect()
+    }
+
+    #[test]
+    fn should_report_nothing_for_clean_dictionaries() {
+        let report = lint_dictionaries(&get_test_config());
+        assert!(report.issues.is_empty(), "{}", report.to_text());
+        assert!(!report.has_errors());
+    }
+
+    #[test]
+    fn should_report_missing_sources() {
+        let mut config = get_test_config();
+        config.values_conf.names_file = String::from("not_existing");
+        config.values_conf.surnames_file = String::from("not_existing");
+        config.char_conf.gen_surname = false;
+
+        let report = lint_dictionaries(&config);
+        let names = issues_of(&report, "names_file");
+        assert_eq!(names.len(), 1);
+        assert_eq!(names[0].kind, IssueKind::MissingSource);
+        assert_eq!(names[0].severity, Severity::Error);
+        let surnames = issues_of(&report, "surnames_file");
+        assert_eq!(surnames.len(), 1);
+        assert_eq!(surnames[0].severity, Severity::Warning);
+        assert_eq!(report.errors, 1);
+        assert_eq!(report.warnings, 1);
+    }
+
+    #[test]
+    fn should_report_term_problems_with_their_lines() {
+        let mut config = get_test_config();
+        config.values_conf.names_file = get_resource_filename("test/test_lint_dictionary");
+
+        let report = lint_dictionaries(&config);
+        let issues: Vec<(IssueKind, Option<usize>)> = issues_of(&report, "names_file").iter()
+            .map(|issue| (issue.kind, issue.entry))
+            .collect();
+        assert_eq!(issues, vec![
+            (IssueKind::SuspiciousCharacters, Some(2)),
+            (IssueKind::Duplicate, Some(2)),
+            (IssueKind::EmptyEntry, Some(4)),
+            (IssueKind::SuspiciousCharacters, Some(5)),
+            (IssueKind::TooLong, Some(6)),
+            (IssueKind::Sanitized, None),
+            (IssueKind::Sanitized, None),
+        ]);
+        let sanitized: Vec<&str> = issues_of(&report, "names_file").iter()
+            .filter(|issue| issue.kind == IssueKind::Sanitized)
+            .map(|issue| issue.message.as_str())
+            .collect();
+        assert_eq!(sanitized, vec!["trimmed whitespace from 1 terms", "skipped 1 blank lines"]);
+        assert!(!report.has_errors());
+    }
+
+    #[test]
+    fn should_report_empty_taxonomies() {
+        let mut config = get_test_config();
+        config.values_conf.props_file = get_resource_filename("test/test_taxonomy_dictionary.json");
+        config.char_conf.gen_props = false;
+
+        let report = lint_dictionaries(&config);
+        let issues = issues_of(&report, "props_file");
+        assert_eq!(issues.len(), 3);
+        assert!(issues.iter().all(|issue| issue.kind == IssueKind::EmptyTaxonomy));
+        assert_eq!(issues[0].message, "taxonomy Weapons > Firearms > Cannons has no terms");
+    }
+
+    #[test]
+    fn should_report_insufficient_capacity() {
+        let mut config = get_test_config();
+        config.execution_conf.charusters_nums = 4;
+        config.values_conf.props_file = get_resource_filename("test/test_taxonomy_dictionary.json");
+
+        let report = lint_dictionaries(&config);
+        let capacity_keys: Vec<&str> = report.issues.iter()
+            .filter(|issue| issue.kind == IssueKind::InsufficientCapacity)
+            .map(|issue| issue.key.as_str())
+            .collect();
+        assert_eq!(capacity_keys, vec!["nicknames_file", "birthplaces_file", "description_files", "images_folder", "props_file"]);
+        assert!(report.has_errors());
+    }
+
+    #[test]
+    fn should_lint_the_sources_of_the_custom_attributes() {
+        let mut config = get_test_config();
+        config.attributes[0].source = String::from("not_existing");
+        config.attributes[1].count = 4;
+
+        let report = lint_dictionaries(&config);
+        let alignment = issues_of(&report, "attributes.alignment");
+        assert_eq!(alignment.len(), 1);
+        assert_eq!(alignment[0].kind, IssueKind::MissingSource);
+        let titles = issues_of(&report, "attributes.titles");
+        assert_eq!(titles.len(), 1);
+        assert_eq!(titles[0].kind, IssueKind::InsufficientCapacity);
+        assert!(issues_of(&report, "attributes.luck").is_empty());
+    }
+
+    #[test]
+    fn should_report_unreadable_sources() {
+        let mut config = get_test_config();
+        config.values_conf.props_file = get_resource_filename("test/test_dictionary");
+
+        let report = lint_dictionaries(&config);
+        let issues = issues_of(&report, "props_file");
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].kind, IssueKind::UnreadableSource);
+        assert!(issues[0].message.contains("JSON was not well-formatted"), "{}", issues[0].message);
+    }
+
+    #[test]
+    fn should_report_invalid_syllable_inventories() {
+        let mut config = get_test_config();
+        config.values_conf.names_file = get_resource_filename("test/test_invalid_dictionary.syllables.json");
+
+        let report = lint_dictionaries(&config);
+        let issues = issues_of(&report, "names_file");
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].kind, IssueKind::UnreadableSource);
+        assert!(issues[0].message.ends_with("codas are empty"));
+    }
+
+    #[test]
+    fn should_serialize_report_to_json() {
+        let mut config = get_test_config();
+        config.values_conf.names_file = String::from("not_existing");
+
+        let json: serde_json::Value = serde_json::from_str(&lint_dictionaries(&config).to_json()).unwrap();
+        assert_eq!(json["errors"], 1);
+        assert_eq!(json["warnings"], 0);
+        assert_eq!(json["issues"][0]["key"], "names_file");
+        assert_eq!(json["issues"][0]["kind"], "missing_source");
+        assert_eq!(json["issues"][0]["severity"], "error");
+        assert!(json["issues"][0].get("entry").is_none());
+    }
+
+    #[test]
+    fn should_describe_suspicious_characters() {
+        assert_eq!(describe_suspicious_characters("term"), None);
+        assert_eq!(describe_suspicious_characters("Café"), None);
+        assert_eq!(describe_suspicious_characters(" term").unwrap(), "leading or trailing whitespace");
+        assert_eq!(describe_suspicious_characters("te\u{200B}rm\u{200B}").unwrap(), "U+200B");
+        assert_eq!(describe_suspicious_characters("te\trm\u{FFFD}").unwrap(), "U+0009, U+FFFD");
+    }
+}
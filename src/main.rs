@@ -1,8 +1,220 @@
+use std::env;
+use std::process;
+
+use charuster::collections;
 use charuster::config;
+use charuster::diff;
+use charuster::export;
 use charuster::generator;
+use charuster::import;
+use charuster::legendaries;
+use charuster::lint;
+use charuster::reroll;
+use charuster::templates;
+use charuster::validation;
+
+const USAGE: &str = "Usage:
+    charuster [--profile NAME] [--template FILE] [--append]
+                                                          generate the characters described in resources/config.toml,
+                                                          completing the features of the template or adding them
+                                                          to the ones already exported
+    charuster lint [--json] [--profile NAME] [CONFIG]     check the dictionaries referenced by the config
+    charuster validate [--json] [--profile NAME] [CONFIG] check the config values before generating
+    charuster config [--profile NAME] [CONFIG]            print the effective config, with its layers merged
+    charuster reroll --characters I,J --features F,G [--collection FILE] [--profile NAME] [CONFIG]
+                                                          generate again some features of some exported characters
+    charuster convert INPUT OUTPUT                        export a collection again, in the formats of the file extensions
+    charuster diff OLD NEW [--key FEATURE] [--features F,G] [--json]
+                                                          compare two exported collections";
+
+// the options shared by the commands
+struct Options {
+    json: bool,
+    /// add the generated characters to the exported ones
+    append: bool,
+    profile: Option<String>,
+    /// file of the partial character completed by the generation
+    template: Option<String>,
+    /// indexes of the characters to reroll
+    characters: Vec<usize>,
+    /// features to reroll
+    features: Vec<String>,
+    /// exported collection to reroll, the export file of the config by default
+    collection: Option<String>,
+    /// feature matching the characters of two collections, their index by default
+    key: Option<String>,
+    config_file: String,
+}
 
 fn main() {
-    let config = config::parse_local_config();
-    generator::generate_charusters(Some(config));
+    let args: Vec<String> = env::args().skip(1).collect();
+    match args.first().map(|arg| arg.as_str()) {
+        None | Some("--profile" | "--template" | "--append") => run_generate(&parse_options(&args)),
+        Some("lint") => run_lint(&parse_options(&args[1..])),
+        Some("validate") => run_validate(&parse_options(&args[1..])),
+        Some("config") => run_config(&parse_options(&args[1..])),
+        Some("reroll") => run_reroll(&parse_options(&args[1..])),
+        Some("convert") if args.len() == 3 => run_convert(&args[1], &args[2]),
+        Some("diff") if args.len() >= 3 => run_diff(&args[1], &args[2], &parse_options(&args[3..])),
+        Some(_) => {
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+    }
+}
+
+fn parse_options(args: &[String]) -> Options {
+    let mut options = Options {
+        json: false, append: false, profile: None, template: None, characters: vec![], features: vec![], collection: None, key: None,
+        config_file: config::local_config_filename(),
+    };
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--json" => options.json = true,
+            "--append" => options.append = true,
+            "--profile" => options.profile = Some(option_value(args.next())),
+            "--template" => options.template = Some(option_value(args.next())),
+            "--characters" => options.characters = option_value(args.next()).split(',')
+                .map(|index| index.trim().parse().unwrap_or_else(|_| {
+                    eprintln!("{} is not a character index", index);
+                    process::exit(2);
+                }))
+                .collect(),
+            "--features" => options.features = option_value(args.next()).split(',').map(|f| f.trim().to_owned()).collect(),
+            "--collection" => options.collection = Some(option_value(args.next())),
+            "--key" => options.key = Some(option_value(args.next())),
+            flag if flag.starts_with("--") => {}
+            config_file => options.config_file = config_file.to_owned(),
+        }
+    }
+    options
+}
+
+// the value following an option, exiting if it's missing
+fn option_value(value: Option<&String>) -> String {
+    match value {
+        Some(value) => value.clone(),
+        None => {
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+    }
+}
+
+fn run_generate(options: &Options) {
+    let report = validation::validate_config_file_with_profile(&options.config_file, options.profile.as_deref());
+    if !report.problems.is_empty() {
+        eprint!("{}", report.to_text());
+    }
+    if report.has_errors() {
+        process::exit(1);
+    }
+    let mut config = config::parse_config_with_profile(&options.config_file, options.profile.as_deref());
+    config.execution_conf.append |= options.append;
+    if let Some(template_file) = &options.template {
+        run_generate_from_template(&config, template_file);
+    } else if !config.collections.is_empty() {
+        print!("{}", collections::generate_collections(&config).to_text());
+    } else if config.execution_conf.export_to_json {
+        generator::export_charusters(&config);
+    } else {
+        generator::generate_charusters(Some(config));
+    }
     println!("*** Charusters generated!!!")
 }
+
+fn run_generate_from_template(config: &config::Config, template_file: &str) {
+    if !config.collections.is_empty() {
+        eprintln!("A template can't be completed for a config with collections");
+        process::exit(1);
+    }
+    if config.execution_conf.append {
+        eprintln!("A template can't be completed while appending to the exported collection");
+        process::exit(1);
+    }
+    let template = legendaries::read_character(template_file).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(1);
+    });
+    if let Err(e) = templates::template_config(config, &template) {
+        eprintln!("Invalid template: {}", e);
+        process::exit(1);
+    }
+    let stream = generator::CharusterStream::with_template(config, &generator::GeneratorRegistry::new(), &template);
+    if config.execution_conf.export_to_json {
+        export::export_to_file(stream, config.execution_conf.export_format, &config.execution_conf.export_to_json_file);
+    } else {
+        stream.for_each(drop);
+    }
+}
+
+fn run_reroll(options: &Options) {
+    if options.characters.is_empty() || options.features.is_empty() {
+        eprintln!("{}", USAGE);
+        process::exit(2);
+    }
+    let config = config::parse_config_with_profile(&options.config_file, options.profile.as_deref());
+    let execution_conf = &config.execution_conf;
+    let collection = options.collection.as_deref().unwrap_or(&execution_conf.export_to_json_file);
+    let log = reroll::reroll_file(&config, collection, execution_conf.export_format, &options.characters, &options.features);
+    print!("{}", log.to_text());
+}
+
+fn run_convert(input: &str, output: &str) {
+    let charusters = import::import_from_file(input, file_format(input));
+    let count = export::export_to_file(&charusters, file_format(output), output);
+    println!("{} characters exported to {}", count, output);
+}
+
+fn run_diff(old: &str, new: &str, options: &Options) {
+    let features: Vec<String> = match options.features.is_empty() {
+        true => diff::DISTRIBUTION_FEATURES.iter().map(|feature| feature.to_string()).collect(),
+        false => options.features.clone(),
+    };
+    let diff = diff::diff_collections(&import::import_from_file(old, file_format(old)), &import::import_from_file(new, file_format(new)),
+                                      options.key.as_deref(), &features);
+    if options.json {
+        println!("{}", diff.to_json());
+    } else {
+        print!("{}", diff.to_text());
+    }
+}
+
+// the format of a collection file from its extension, exiting if it's not known
+fn file_format(filename: &str) -> export::ExportFormat {
+    export::ExportFormat::from_filename(filename).unwrap_or_else(|| {
+        eprintln!("The format of {} can't be told from its extension (json, ndjson, jsonl or csv)", filename);
+        process::exit(2);
+    })
+}
+
+fn run_lint(options: &Options) {
+    let config = config::parse_config_with_profile(&options.config_file, options.profile.as_deref());
+    let report = lint::lint_dictionaries(&config);
+    if options.json {
+        println!("{}", report.to_json());
+    } else {
+        print!("{}", report.to_text());
+    }
+    if report.has_errors() {
+        process::exit(1);
+    }
+}
+
+fn run_validate(options: &Options) {
+    let report = validation::validate_config_file_with_profile(&options.config_file, options.profile.as_deref());
+    if options.json {
+        println!("{}", report.to_json());
+    } else {
+        print!("{}", report.to_text());
+    }
+    if report.has_errors() {
+        process::exit(1);
+    }
+}
+
+fn run_config(options: &Options) {
+    let config = config::parse_config_with_profile(&options.config_file, options.profile.as_deref());
+    print!("{}", config::to_toml(&config));
+}
@@ -0,0 +1,276 @@
+use std::collections::HashSet;
+use std::fs::File;
+use std::sync::Arc;
+
+use memmap2::Mmap;
+use rand::prelude::*;
+
+use crate::config::SanitationConfig;
+use crate::dictionary::Dictionary;
+
+// MappedDictionary
+/// A plain text dictionary read lazily from a memory-mapped file.
+///
+/// Only an index of the line offsets is kept in memory: terms are decoded when they are chosen and
+/// removed terms are tracked in a bitmap, so cloning the dictionary doesn't copy its terms. Blank
+/// lines and line terminators (`\n` or `\r\n`) are skipped while indexing; trimming and comment
+/// skipping are applied to the offsets as well, the other steps of the sanitation are not.
+#[derive(Clone)]
+pub struct MappedDictionary {
+    mmap: Arc<Mmap>,
+    // start and end of every term in the mapped file
+    offsets: Arc<Vec<(usize, usize)>>,
+    removed: Vec<u64>,
+    remaining: usize,
+}
+
+impl MappedDictionary {
+    /// create a new dictionary mapping the file in memory, trimming the terms and skipping the comment
+    /// lines as set in the sanitation config
+    pub fn new(filename: &str, sanitation: &SanitationConfig) -> Self {
+        let file = File::open(filename).unwrap_or_else(|_| panic!("Dictionary not found: {}", filename));
+        // SAFETY: the dictionary files are not expected to be modified while the generation is running
+        let mmap = unsafe { Mmap::map(&file) }.unwrap_or_else(|e| panic!("Could not map dictionary {}: {}", filename, e));
+        let offsets = index_lines(&mmap, sanitation.trim, sanitation.skip_comments);
+
+        MappedDictionary {
+            removed: vec![0; bitmap_len(offsets.len())],
+            remaining: offsets.len(),
+            mmap: Arc::new(mmap),
+            offsets: Arc::new(offsets),
+        }
+    }
+
+    fn term(&self, i: usize) -> String {
+        let (start, end) = self.offsets[i];
+        String::from_utf8_lossy(&self.mmap[start..end]).into_owned()
+    }
+
+    fn is_removed(&self, i: usize) -> bool {
+        self.removed[i / 64] & (1 << (i % 64)) != 0
+    }
+
+    /// choose a random index among the terms not removed yet, by rejection sampling
+    fn choose_index(&self, rng: &mut dyn RngCore) -> Option<usize> {
+        if self.remaining == 0 {
+            return None;
+        }
+        loop {
+            let i = rng.gen_range(0..self.offsets.len());
+            if !self.is_removed(i) {
+                return Some(i);
+            }
+        }
+    }
+
+    /// drop the removed terms from the index once they are the majority, so the rejection sampling
+    /// of `choose_index` keeps needing two attempts at most on average
+    fn compact_if_needed(&mut self) {
+        if self.remaining * 2 >= self.offsets.len() {
+            return;
+        }
+        let offsets: Vec<(usize, usize)> = self.offsets.iter().enumerate()
+            .filter(|(i, _)| !self.is_removed(*i))
+            .map(|(_, offset)| *offset)
+            .collect();
+        self.removed = vec![0; bitmap_len(offsets.len())];
+        self.offsets = Arc::new(offsets);
+    }
+}
+
+impl Dictionary for MappedDictionary {
+    /// choose a term of the dictionary, mark it as removed and return it
+    fn choose_and_remove_with(&mut self, rng: &mut dyn RngCore) -> Option<String> {
+        let i = self.choose_index(rng)?;
+        let term = self.term(i);
+        self.removed[i / 64] |= 1 << (i % 64);
+        self.remaining -= 1;
+        self.compact_if_needed();
+        Some(term)
+    }
+
+    /// choose a term of the dictionary and return it
+    fn choose_with(&self, rng: &mut dyn RngCore) -> Option<String> {
+        let i = self.choose_index(rng)?;
+        Some(self.term(i))
+    }
+
+    /// choose up to `k` distinct terms by rejection sampling, without touching the removed terms bitmap
+    fn sample_distinct(&self, k: usize, rng: &mut dyn RngCore) -> Vec<String> {
+        let k = k.min(self.remaining);
+        let mut chosen = HashSet::with_capacity(k);
+        let mut terms = Vec::with_capacity(k);
+        while terms.len() < k {
+            let i = self.choose_index(rng).unwrap();
+            if chosen.insert(i) {
+                terms.push(self.term(i));
+            }
+        }
+        terms
+    }
+}
+
+fn bitmap_len(terms: usize) -> usize {
+    terms.div_ceil(64)
+}
+
+// offsets of the non blank lines, without their terminators, optionally without their leading and trailing
+// whitespace and skipping the `#` comment lines
+fn index_lines(bytes: &[u8], trim: bool, skip_comments: bool) -> Vec<(usize, usize)> {
+    let mut offsets = vec![];
+    let mut start = 0;
+    while start < bytes.len() {
+        let end = bytes[start..].iter().position(|b| *b == b'\n').map(|p| start + p).unwrap_or(bytes.len());
+        let (mut term_start, mut term_end) = (start, end);
+        if term_end > term_start && bytes[term_end - 1] == b'\r' {
+            term_end -= 1;
+        }
+        if trim {
+            (term_start, term_end) = trimmed(bytes, term_start, term_end);
+        }
+        let comment = skip_comments && bytes[term_start..term_end].iter().find(|b| !b.is_ascii_whitespace()) == Some(&b'#');
+        if term_end > term_start && !comment {
+            offsets.push((term_start, term_end));
+        }
+        start = end + 1;
+    }
+    offsets
+}
+
+// offsets of the line without its leading and trailing whitespace, unicode whitespace included when the line
+// is valid UTF-8
+fn trimmed(bytes: &[u8], start: usize, end: usize) -> (usize, usize) {
+    match std::str::from_utf8(&bytes[start..end]) {
+        Ok(line) => {
+            let trimmed_start = line.trim_start();
+            (end - trimmed_start.len(), end - (trimmed_start.len() - trimmed_start.trim_end().len()))
+        }
+        Err(_) => {
+            let line = &bytes[start..end];
+            let leading = line.iter().take_while(|b| b.is_ascii_whitespace()).count();
+            let trailing = line[leading..].iter().rev().take_while(|b| b.is_ascii_whitespace()).count();
+            (start + leading, end - trailing)
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    const EXPECTED_TERMS: [&str; 3] = ["term_1", "term_2", "term_3"];
+
+    fn get_dictionary_filename(dict_name: &str) -> String {
+        let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        d.push("resources/".to_owned() + dict_name);
+        d.into_os_string().into_string().unwrap()
+    }
+
+    #[test]
+    fn should_index_lines_skipping_terminators_and_blank_lines() {
+        assert_eq!(index_lines(b"a\nbb\r\n\n\r\nccc", false, false), vec![(0, 1), (2, 4), (9, 12)]);
+        assert_eq!(index_lines(b"a\n", false, false), vec![(0, 1)]);
+        assert!(index_lines(b"", false, false).is_empty());
+    }
+
+    #[test]
+    fn should_index_lines_trimming_and_skipping_comments() {
+        assert_eq!(index_lines(b" a \n  # c\n\t\r\nb\xc2\xa0", true, true), vec![(1, 2), (13, 14)]);
+        assert_eq!(index_lines(b" a \n  # c", false, true), vec![(0, 3)]);
+        assert_eq!(index_lines(b" a \n  # c", true, false), vec![(1, 2), (6, 9)]);
+    }
+
+    #[test]
+    fn should_create_dictionary_from_file() {
+        let dict = MappedDictionary::new(get_dictionary_filename("test/test_dictionary").as_str(), &SanitationConfig::disabled());
+        assert_eq!(dict.remaining, 3);
+        for (i, term) in EXPECTED_TERMS.iter().enumerate() {
+            assert_eq!(&dict.term(i), term);
+        }
+    }
+
+    #[test]
+    fn should_skip_carriage_returns_and_blank_lines() {
+        let dict = MappedDictionary::new(get_dictionary_filename("test/test_dirty_dictionary").as_str(), &SanitationConfig::disabled());
+        let terms: Vec<String> = (0..dict.remaining).map(|i| dict.term(i)).collect();
+        assert_eq!(terms, vec!["term_1", "  term_2  ", "# comment", "TERM_1", "term_3"]);
+    }
+
+    #[test]
+    fn should_trim_terms_and_skip_comments_as_set_in_the_sanitation_config() {
+        let dict = MappedDictionary::new(get_dictionary_filename("test/test_dirty_dictionary").as_str(), &SanitationConfig::default());
+        let terms: Vec<String> = (0..dict.remaining).map(|i| dict.term(i)).collect();
+        assert_eq!(terms, vec!["term_1", "term_2", "TERM_1", "term_3"]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn should_panic_while_creating_dictionary_from_non_existing_file() {
+        MappedDictionary::new("resources/test/not_existing", &SanitationConfig::disabled());
+    }
+
+    #[test]
+    fn should_choose_a_random_term_from_the_dictionary() {
+        let dict = MappedDictionary::new(get_dictionary_filename("test/test_dictionary").as_str(), &SanitationConfig::disabled());
+        for _ in 0..10 {
+            assert!(EXPECTED_TERMS.contains(&dict.choose().unwrap().as_str()));
+        }
+        assert_eq!(dict.remaining, 3);
+    }
+
+    #[test]
+    fn should_choose_a_random_term_and_remove_it_from_the_dictionary() {
+        let mut dict = MappedDictionary::new(get_dictionary_filename("test/test_dictionary").as_str(), &SanitationConfig::disabled());
+        let mut terms = HashSet::new();
+        for i in 0..3 {
+            let term = dict.choose_and_remove().unwrap();
+            assert!(EXPECTED_TERMS.contains(&term.as_str()));
+            assert!(terms.insert(term));
+            assert_eq!(dict.remaining, 2 - i);
+        }
+        assert_eq!(dict.remaining, 0);
+        assert!(dict.choose_and_remove().is_none());
+        assert!(dict.choose().is_none());
+    }
+
+    #[test]
+    fn should_sample_distinct_terms_not_removed() {
+        let mut dict = MappedDictionary::new(get_dictionary_filename("test/test_dictionary").as_str(), &SanitationConfig::disabled());
+        let removed = dict.choose_and_remove().unwrap();
+
+        for _ in 0..10 {
+            let terms = dict.sample_distinct(3, &mut thread_rng());
+            assert_eq!(terms.len(), 2);
+            assert_ne!(terms[0], terms[1]);
+            assert!(!terms.contains(&removed));
+        }
+        assert_eq!(dict.remaining, 2);
+    }
+
+    #[test]
+    fn should_remove_terms_only_from_the_clone() {
+        let dict = MappedDictionary::new(get_dictionary_filename("test/test_dictionary").as_str(), &SanitationConfig::disabled());
+        let mut cloned = dict.clone();
+        cloned.choose_and_remove().unwrap();
+        cloned.choose_and_remove().unwrap();
+
+        assert_eq!(cloned.remaining, 1);
+        assert_eq!(dict.remaining, 3);
+        assert!(Arc::ptr_eq(&dict.mmap, &cloned.mmap));
+    }
+
+    #[test]
+    fn should_never_return_removed_terms_after_compaction() {
+        let mut dict = MappedDictionary::new(get_dictionary_filename("surnames").as_str(), &SanitationConfig::disabled());
+        let total = dict.remaining;
+        let mut terms = HashSet::new();
+        while let Some(term) = dict.choose_and_remove() {
+            assert!(terms.insert(term));
+            assert!(dict.offsets.len() <= dict.remaining * 2);
+        }
+        assert_eq!(terms.len(), total);
+    }
+}
@@ -0,0 +1,378 @@
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use rand::prelude::*;
+use serde_json::Value;
+
+use crate::character::{CharacterBuilder, CharacterFeature, Property};
+use crate::config::{AttributeKind, Config, QuotaConfig};
+use crate::dictionary::{SimpleDictionary, TaxonomyDictionary};
+use crate::formats::DictionaryFormat;
+use crate::generator::{FeatureGenerator, NamedGenerator, VEC_FEATURES_SIZE};
+use crate::syllables::SYLLABLES_FILE_SUFFIX;
+
+/// times a feature is generated again when it has a value the character can't have
+const QUOTA_ATTEMPTS: usize = 1000;
+
+// how the values of a feature are counted by the quotas
+#[derive(Clone, Copy, PartialEq)]
+enum QuotaKind {
+    /// a single value per character
+    Single,
+    /// up to the given number of values per character
+    Multiple(usize),
+}
+
+// the quota kind of a feature, None if quotas are not supported on it
+fn quota_kind(config: &Config, feature: &str) -> Option<QuotaKind> {
+    match feature {
+        "name" | "surname" | "profession" | "archetype" => Some(QuotaKind::Single),
+        "hobbies" | "props" => Some(QuotaKind::Multiple(VEC_FEATURES_SIZE as usize)),
+        _ => config.attributes.iter().find(|attribute| attribute.name == feature).and_then(|attribute| match attribute.kind {
+            AttributeKind::Choose => Some(QuotaKind::Single),
+            AttributeKind::Vector => Some(QuotaKind::Multiple(attribute.count as usize)),
+            _ => None,
+        }),
+    }
+}
+
+fn is_generated(config: &Config, feature: &str) -> bool {
+    match feature {
+        "archetype" => !config.archetypes.is_empty(),
+        _ => config.char_conf.is_enabled(feature) || config.attributes.iter().any(|attribute| attribute.name == feature),
+    }
+}
+
+// the path of each term of the props dictionary, by name
+fn prop_paths(props_file: &str) -> HashMap<String, Vec<String>> {
+    TaxonomyDictionary::new(props_file).term_paths().into_iter()
+        .map(|path| (path.last().unwrap().clone(), path))
+        .collect()
+}
+
+// the weight of each term a single value feature is drawn from, along with the name of their source. None when
+// they can't be known beforehand: the feature is overridden by archetypes, built from syllables or read from a
+// missing file
+fn term_weights(config: &Config, feature: &str) -> Option<(String, HashMap<String, f64>)> {
+    if config.archetypes.iter().any(|archetype| archetype.features().contains(&feature)) {
+        return None;
+    }
+    let values = &config.values_conf;
+    let (source, terms): (String, Vec<(String, f64)>) = match feature {
+        "archetype" => (String::from("the archetypes"),
+                        config.archetypes.iter().map(|archetype| (archetype.name.clone(), archetype.weight)).collect()),
+        "profession" if !values.allowed_professions.is_empty() => (String::from("allowed_professions"),
+                        values.allowed_professions.iter().map(|profession| (profession.clone(), 1.0)).collect()),
+        _ => {
+            let source = match feature {
+                "name" => &values.names_file,
+                "surname" => &values.surnames_file,
+                "profession" => &values.professions_file,
+                _ => &config.attributes.iter().find(|attribute| attribute.name == feature && attribute.kind == AttributeKind::Choose)?.source,
+            };
+            let (_, path) = DictionaryFormat::from_filename(source);
+            if source.ends_with(SYLLABLES_FILE_SUFFIX) || !Path::new(path).is_file() {
+                return None;
+            }
+            let terms = SimpleDictionary::new_sanitized(source, &values.sanitation).0.terms().iter()
+                .map(|term| (term.value.clone(), term.weight))
+                .collect();
+            (source.clone(), terms)
+        }
+    };
+    let mut weights = HashMap::new();
+    for (term, weight) in terms {
+        *weights.entry(term).or_insert(0.0) += weight;
+    }
+    Some((source, weights))
+}
+
+/// the quotas of the config that can't be met, by index, with the reason
+pub fn check_quotas(config: &Config) -> Vec<(usize, String)> {
+    let total = config.execution_conf.charusters_nums as usize;
+    let mut problems = vec![];
+    let props = config.quotas.iter().any(|quota| quota.feature == "props") && Path::new(&config.values_conf.props_file).is_file();
+    let prop_paths = if props { prop_paths(&config.values_conf.props_file) } else { HashMap::new() };
+    let single_features: HashSet<&str> = config.quotas.iter().map(|quota| quota.feature.as_str())
+        .filter(|feature| quota_kind(config, feature) == Some(QuotaKind::Single) && is_generated(config, feature))
+        .collect();
+    let dictionaries: HashMap<&str, (String, HashMap<String, f64>)> = single_features.into_iter()
+        .filter_map(|feature| term_weights(config, feature).map(|terms| (feature, terms)))
+        .collect();
+    // the characters each value of a single value feature can have at most, by feature
+    let mut allowed: HashMap<&str, Vec<(&str, usize)>> = HashMap::new();
+    // the first quota of each feature (and of each kind of props), along with the values needed by all its quotas
+    let mut needed: Vec<(usize, QuotaKind, usize)> = vec![];
+    let group = |quota: &QuotaConfig| (quota.feature.clone(), prop_paths.get(&quota.value).map(|path| path[0].clone()));
+
+    for (i, quota) in config.quotas.iter().enumerate() {
+        let kind = match quota_kind(config, &quota.feature) {
+            Some(kind) => kind,
+            None => {
+                problems.push((i, format!("quotas are not supported on {}", quota.feature)));
+                continue;
+            }
+        };
+        if !is_generated(config, &quota.feature) {
+            problems.push((i, format!("{} is not generated", quota.feature)));
+        } else if config.quotas[..i].iter().any(|other| other.feature == quota.feature && other.value == quota.value) {
+            problems.push((i, format!("quota on {} {} is declared more than once", quota.feature, quota.value)));
+        } else if props && quota.feature == "props" && !prop_paths.contains_key(&quota.value) {
+            problems.push((i, format!("{} is not a term of {}", quota.value, config.values_conf.props_file)));
+        } else if let Some((source, weights)) = dictionaries.get(quota.feature.as_str()) {
+            match weights.get(&quota.value) {
+                None => problems.push((i, format!("{} is not a term of {}", quota.value, source))),
+                Some(weight) => {
+                    let (max, others) = (quota.bounds(total).map(|(_, max)| max).unwrap_or(total), weights.values().sum::<f64>() - weight);
+                    allowed.entry(quota.feature.as_str()).or_default().push((quota.value.as_str(), max));
+                    // the characters that can't have the value draw again, up to QUOTA_ATTEMPTS times
+                    if max < total && others > 0.0 && others * (QUOTA_ATTEMPTS as f64) < weight + others {
+                        problems.push((i, format!("the characters without {} would rarely get another term of {}", quota.value, source)));
+                    }
+                }
+            }
+        }
+        match quota.bounds(total) {
+            Ok((min, _)) => match needed.iter_mut().find(|(first, _, _)| group(&config.quotas[*first]) == group(quota)) {
+                Some((_, _, needed)) => *needed += min,
+                // a character has a single prop of each kind
+                None if group(quota).1.is_some() => needed.push((i, QuotaKind::Single, min)),
+                None => needed.push((i, kind, min)),
+            },
+            Err(message) => problems.push((i, message)),
+        }
+    }
+
+    for (first, kind, needed) in needed {
+        let feature = match group(&config.quotas[first]) {
+            (feature, Some(kind)) => format!("{} of kind {}", feature, kind),
+            (feature, None) => feature,
+        };
+        match kind {
+            QuotaKind::Single if needed > total =>
+                problems.push((first, format!("quotas on {} need {} characters, the collection has {}", feature, needed, total))),
+            QuotaKind::Multiple(size) if needed > total * size =>
+                problems.push((first, format!("quotas on {} need {} values, the collection has room for {}", feature, needed, total * size))),
+            _ => {}
+        }
+    }
+    // the characters can't all be generated when every term has a quota
+    for (feature, allowed) in allowed {
+        let (source, weights) = &dictionaries[feature];
+        let capped: HashSet<&str> = allowed.iter().map(|(value, _)| *value).collect();
+        let most: usize = allowed.iter().map(|(_, max)| max).sum();
+        if weights.iter().all(|(term, weight)| *weight <= 0.0 || capped.contains(term.as_str())) && most < total {
+            let first = config.quotas.iter().position(|quota| quota.feature == feature).unwrap();
+            problems.push((first, format!("quotas on {} allow {} characters of the {} terms of {}, the collection has {}", feature, most, capped.len(), source, total)));
+        }
+    }
+    problems.sort_by_key(|(i, _)| *i);
+    problems
+}
+
+/// wrap the generators of the features with quotas so they meet them. Panics if some quotas can't be met
+pub fn apply_quotas(config: &Config, generators: &mut Vec<NamedGenerator>) {
+    let problems = check_quotas(config);
+    if !problems.is_empty() {
+        let problems: Vec<String> = problems.into_iter()
+            .map(|(i, message)| format!("quota {} on {} {}: {}", i, config.quotas[i].feature, config.quotas[i].value, message))
+            .collect();
+        panic!("Infeasible quotas: {}", problems.join("; "));
+    }
+
+    *generators = std::mem::take(generators).into_iter().map(|mut gen| {
+        let quotas: Vec<QuotaConfig> = config.quotas.iter().filter(|quota| quota.feature == gen.name).cloned().collect();
+        if !quotas.is_empty() {
+            let kind = quota_kind(config, &gen.name).unwrap();
+            let prop_paths = if gen.name == "props" { prop_paths(&config.values_conf.props_file) } else { HashMap::new() };
+            gen.generator = Box::new(QuotaGenerator { base: gen.generator, kind, quotas, prop_paths, forced: vec![], forbidden: vec![], locked: HashMap::new() });
+        }
+        gen
+    }).collect();
+}
+
+// QuotaGenerator
+/// Generates a feature meeting the quotas on its values: the characters having each value are chosen
+/// up front, the other characters generate the feature again until they don't have it
+struct QuotaGenerator {
+    base: Box<dyn FeatureGenerator>,
+    kind: QuotaKind,
+    quotas: Vec<QuotaConfig>,
+    /// path of the props, to add the props of the quotas
+    prop_paths: HashMap<String, Vec<String>>,
+    /// indexes of the quotas whose value each character must have
+    forced: Vec<Vec<usize>>,
+    /// indexes of the quotas whose value each character can't have
+    forbidden: Vec<Vec<usize>>,
+    /// values of the characters whose feature is set beforehand, counted by the quotas
+    locked: HashMap<usize, Vec<String>>,
+}
+
+impl QuotaGenerator {
+    // whether the character can have the value of another quota, along with the values forced so far
+    fn has_room(&self, index: usize, quota: &QuotaConfig) -> bool {
+        if self.locked.contains_key(&index) {
+            return false;
+        }
+        let forced = &self.forced[index];
+        match self.kind {
+            QuotaKind::Single => forced.is_empty(),
+            QuotaKind::Multiple(size) => forced.len() < size && match self.prop_paths.get(&quota.value) {
+                // props of a character are all of different kinds
+                Some(path) => forced.iter().all(|q| self.prop_paths[&self.quotas[*q].value][0] != path[0]),
+                None => true,
+            },
+        }
+    }
+}
+
+impl FeatureGenerator for QuotaGenerator {
+    /// for each quota, choose the characters that must have its value and the ones that can't have it
+    fn prepare(&mut self, count: usize, rng: &mut dyn RngCore) {
+        self.base.prepare(count, rng);
+        self.forced = vec![vec![]; count];
+        self.forbidden = vec![vec![]; count];
+        let mut order: Vec<usize> = (0..count).collect();
+        for (q, quota) in self.quotas.iter().enumerate() {
+            let (min, max) = quota.bounds(count).unwrap_or_else(|e| panic!("Infeasible quota on {} {}: {}", quota.feature, quota.value, e));
+            // the locked characters having the value count towards the quota
+            let locked = self.locked.values().filter(|values| values.contains(&quota.value)).count();
+            let (min, max) = (min.saturating_sub(locked), max.saturating_sub(locked));
+            order.shuffle(rng);
+            let chosen: Vec<usize> = order.iter().copied().filter(|i| self.has_room(*i, quota)).take(min).collect();
+            if chosen.len() < min {
+                panic!("Infeasible quota on {} {}: only {} characters can have it", quota.feature, quota.value, chosen.len());
+            }
+            let mut is_chosen = vec![false; count];
+            for i in chosen {
+                self.forced[i].push(q);
+                is_chosen[i] = true;
+            }
+            // the first characters after the chosen ones can have the value or not, the rest can't
+            let others = order.iter().copied().filter(|i| !is_chosen[*i] && !self.locked.contains_key(i));
+            for i in others.skip(max - min) {
+                self.forbidden[i].push(q);
+            }
+        }
+    }
+
+    fn dependencies(&self) -> Vec<String> {
+        self.base.dependencies()
+    }
+
+    fn lock(&mut self, index: usize, value: &Value) {
+        self.base.lock(index, value);
+        let values = match value {
+            Value::Array(values) => values.iter().filter_map(|v| v.get("name").unwrap_or(v).as_str().map(str::to_owned)).collect(),
+            value => value.as_str().map(str::to_owned).into_iter().collect(),
+        };
+        self.locked.insert(index, values);
+    }
+
+    fn exclude(&mut self, values: &[String]) {
+        self.base.exclude(values);
+    }
+
+    fn generate(&self, index: usize, rng: &mut dyn RngCore, character: &CharacterBuilder) -> Option<CharacterFeature> {
+        let forced: Vec<&str> = self.forced[index].iter().map(|q| self.quotas[*q].value.as_str()).collect();
+        let forbidden: Vec<&str> = self.forbidden[index].iter().map(|q| self.quotas[*q].value.as_str()).collect();
+        for _ in 0..QUOTA_ATTEMPTS {
+            let feature = force_values(self.base.generate(index, rng, character)?, &forced, &self.prop_paths, rng);
+            if !quota_values(&feature).iter().any(|value| forbidden.contains(&value.as_str())) {
+                return Some(feature);
+            }
+        }
+        panic!("Could not generate {} without {} for character {}", self.quotas[0].feature, forbidden.join(", "), index)
+    }
+}
+
+// the values of a feature counted by the quotas: the names of the props, the terms of the other features
+fn quota_values(feature: &CharacterFeature) -> Vec<String> {
+    match feature {
+        CharacterFeature::NAME(value) | CharacterFeature::SURNAME(value) | CharacterFeature::PROFESSION(value)
+        | CharacterFeature::ARCHETYPE(value) => vec![value.clone()],
+        CharacterFeature::HOBBIES(values) => values.clone(),
+        CharacterFeature::PROPS(props) => props.iter().map(|prop| prop.name.clone()).collect(),
+        CharacterFeature::CUSTOM(_, Value::String(value)) => vec![value.clone()],
+        CharacterFeature::CUSTOM(_, Value::Array(values)) => values.iter().filter_map(|v| v.as_str().map(str::to_owned)).collect(),
+        _ => vec![],
+    }
+}
+
+// the feature with the forced values, replacing some of its other values
+fn force_values(feature: CharacterFeature, forced: &[&str], prop_paths: &HashMap<String, Vec<String>>, rng: &mut dyn RngCore) -> CharacterFeature {
+    let forced_value = |value: String| forced.first().map(|forced| forced.to_string()).unwrap_or(value);
+    match feature {
+        CharacterFeature::NAME(value) => CharacterFeature::NAME(forced_value(value)),
+        CharacterFeature::SURNAME(value) => CharacterFeature::SURNAME(forced_value(value)),
+        CharacterFeature::PROFESSION(value) => CharacterFeature::PROFESSION(forced_value(value)),
+        CharacterFeature::ARCHETYPE(value) => CharacterFeature::ARCHETYPE(forced_value(value)),
+        CharacterFeature::CUSTOM(name, Value::String(value)) => CharacterFeature::CUSTOM(name, Value::from(forced_value(value))),
+        CharacterFeature::HOBBIES(values) => CharacterFeature::HOBBIES(force_terms(values, forced, rng)),
+        CharacterFeature::CUSTOM(name, Value::Array(values)) => {
+            let values = values.into_iter().filter_map(|v| v.as_str().map(str::to_owned)).collect();
+            CharacterFeature::CUSTOM(name, Value::from(force_terms(values, forced, rng)))
+        }
+        CharacterFeature::PROPS(props) => CharacterFeature::PROPS(force_props(props, forced, prop_paths, rng)),
+        feature => feature,
+    }
+}
+
+// add the forced terms missing, each one in place of a random term not forced
+fn force_terms(mut terms: Vec<String>, forced: &[&str], rng: &mut dyn RngCore) -> Vec<String> {
+    let missing: Vec<&str> = forced.iter().copied().filter(|value| !terms.iter().any(|term| term == value)).collect();
+    for value in missing {
+        let replaceable: Vec<usize> = (0..terms.len()).filter(|i| !forced.contains(&terms[*i].as_str())).collect();
+        match replaceable.choose(rng) {
+            Some(i) => terms[*i] = value.to_string(),
+            None => terms.push(value.to_string()),
+        }
+    }
+    terms
+}
+
+// add the forced props missing, each one in place of the prop of the same kind or of a random prop not forced
+fn force_props(mut props: Vec<Property>, forced: &[&str], prop_paths: &HashMap<String, Vec<String>>, rng: &mut dyn RngCore) -> Vec<Property> {
+    let missing: Vec<&str> = forced.iter().copied().filter(|value| !props.iter().any(|prop| prop.name == *value)).collect();
+    for value in missing {
+        let prop = Property::from_path(prop_paths[value].clone());
+        let same_kind = props.iter().position(|p| p.prop_type == prop.prop_type);
+        let replaceable: Vec<usize> = (0..props.len())
+            .filter(|i| !forced.contains(&props[*i].name.as_str()) && !forced.iter().any(|f| prop_paths[*f][0] == props[*i].prop_type))
+            .collect();
+        match same_kind.or_else(|| replaceable.choose(rng).copied()) {
+            Some(i) => props[i] = prop,
+            None => props.push(prop),
+        }
+    }
+    props
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    #[test]
+    fn should_replace_terms_not_forced() {
+        let terms = force_terms(strings(&["chess", "poker", "golf"]), &["darts", "chess"], &mut thread_rng());
+        assert_eq!(terms.len(), 3);
+        assert!(terms.contains(&String::from("darts")) && terms.contains(&String::from("chess")));
+    }
+
+    #[test]
+    fn should_replace_props_of_the_same_kind() {
+        let paths: HashMap<String, Vec<String>> = [("Lightning quick", "Speed"), ("Rabid", "Rage")].iter()
+            .map(|(name, kind)| (name.to_string(), strings(&[kind, name])))
+            .collect();
+        let props = vec![Property::from_path(strings(&["Speed", "Slow"])), Property::from_path(strings(&["Stink", "Fetid"]))];
+
+        let props = force_props(props, &["Lightning quick"], &paths, &mut thread_rng());
+        let names: Vec<&str> = props.iter().map(|prop| prop.name.as_str()).collect();
+        assert_eq!(names, vec!["Lightning quick", "Fetid"]);
+    }
+}
@@ -0,0 +1,229 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+use rand::prelude::*;
+use rand::rngs::StdRng;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::character::Charuster;
+use crate::config::Config;
+use crate::export::{export_to_file, ExportFormat};
+use crate::generator::{generate_charuster, GeneratorRegistry, NamedGenerator};
+use crate::import::import_from_file;
+use crate::legendaries::{features_of, lock_features, LockedFeatures, terms};
+
+/// suffix of the change log written next to a rerolled collection, after the name of the collection file
+pub const CHANGELOG_SUFFIX: &str = ".changes.json";
+
+/// A feature of a character replaced by a reroll
+#[derive(Serialize, Debug)]
+pub struct FeatureChange {
+    /// index of the character in the collection
+    pub index: usize,
+    pub feature: String,
+    pub old: Value,
+    pub new: Value,
+}
+
+#[derive(Serialize, Debug, Default)]
+pub struct RerollLog {
+    pub changes: Vec<FeatureChange>,
+}
+
+impl RerollLog {
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        for change in self.changes.iter() {
+            text.push_str(&format!("#{} {}: {} -> {}\n", change.index, change.feature, change.old, change.new));
+        }
+        text.push_str(&format!("{} features rerolled\n", self.changes.len()));
+        text
+    }
+}
+
+/// reroll some features of some characters of a collection, see `reroll_charusters_with`
+pub fn reroll_charusters(config: &Config, charusters: Vec<Charuster>, indices: &[usize], features: &[String]) -> (Vec<Charuster>, RerollLog) {
+    reroll_charusters_with(config, charusters, indices, features, &GeneratorRegistry::new())
+}
+
+/// generate again the given features of the characters at `indices`, along with the features depending on
+/// them, keeping every other feature of the collection. The rerolled values are never drawn again, and the
+/// values drawn without replacement stay unique across the collection. Panics if a feature is not generated
+/// by the config or an index is out of the collection
+pub fn reroll_charusters_with(config: &Config, mut charusters: Vec<Charuster>, indices: &[usize], features: &[String],
+                              registry: &GeneratorRegistry) -> (Vec<Charuster>, RerollLog) {
+    let mut generators = registry.create_generators(config);
+    if let Some(feature) = features.iter().find(|feature| !generators.iter().any(|gen| gen.name == **feature)) {
+        panic!("Could not reroll {}, it is not generated by the config", feature);
+    }
+    let features = with_dependents(&generators, features);
+    let mut indices = indices.to_vec();
+    indices.sort_unstable();
+    indices.dedup();
+    if let Some(index) = indices.iter().find(|index| **index >= charusters.len()) {
+        panic!("Could not reroll character {}, the collection has {}", index, charusters.len());
+    }
+
+    let mut locked: HashMap<usize, LockedFeatures> = charusters.iter().enumerate()
+        .map(|(i, charuster)| (i, features_of(charuster)))
+        .collect();
+    let mut rerolled: HashMap<usize, LockedFeatures> = HashMap::new();
+    for index in indices.iter() {
+        let character = locked.get_mut(index).unwrap();
+        let old = features.iter().filter_map(|feature| character.remove(feature).map(|value| (feature.clone(), value))).collect();
+        rerolled.insert(*index, old);
+    }
+    lock_features(&mut generators, &locked);
+    for gen in generators.iter_mut().filter(|gen| features.contains(&gen.name)) {
+        let values: Vec<String> = rerolled.values().filter_map(|old| old.get(&gen.name)).flat_map(terms).collect();
+        gen.generator.exclude(&values);
+    }
+
+    let mut rng = match config.execution_conf.seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    };
+    for gen in generators.iter_mut() {
+        gen.generator.prepare(charusters.len(), &mut rng);
+    }
+    let base_seed: u64 = rng.gen();
+
+    let mut log = RerollLog::default();
+    for index in indices {
+        let charuster = generate_charuster(&generators, index, base_seed, locked.get(&index));
+        let new = features_of(&charuster);
+        for feature in features.iter() {
+            log.changes.push(FeatureChange {
+                index,
+                feature: feature.clone(),
+                old: rerolled[&index].get(feature).cloned().unwrap_or(Value::Null),
+                new: new.get(feature).cloned().unwrap_or(Value::Null),
+            });
+        }
+        charusters[index] = charuster;
+    }
+    (charusters, log)
+}
+
+// the features followed by the features depending on them, directly or not, in the order they are generated
+fn with_dependents(generators: &[NamedGenerator], features: &[String]) -> Vec<String> {
+    let mut rerolled = features.to_vec();
+    for gen in generators.iter().filter(|gen| !features.contains(&gen.name)) {
+        if gen.dependencies.iter().any(|dependency| rerolled.contains(dependency)) {
+            rerolled.push(gen.name.clone());
+        }
+    }
+    rerolled
+}
+
+/// reroll the features of a collection exported to a file, writing the updated collection to the same file
+/// and the change log next to it
+pub fn reroll_file(config: &Config, filename: &str, format: ExportFormat, indices: &[usize], features: &[String]) -> RerollLog {
+    let (charusters, log) = reroll_charusters(config, import_from_file(filename, format), indices, features);
+    export_to_file(&charusters, format, filename);
+    let log_filename = changelog_filename(filename);
+    let file = File::create(&log_filename).unwrap_or_else(|e| panic!("Could not create change log {}: {}", log_filename, e));
+    serde_json::to_writer_pretty(BufWriter::new(file), &log).unwrap_or_else(|e| panic!("Could not write {}: {}", log_filename, e));
+    log
+}
+
+/// path of the change log of a rerolled collection: the collection file without its extension, followed by
+/// `.changes.json`
+pub fn changelog_filename(filename: &str) -> String {
+    let path = Path::new(filename);
+    let stem = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or_else(|| panic!("Invalid collection file: {}", filename));
+    path.with_file_name(format!("{}{}", stem, CHANGELOG_SUFFIX)).into_os_string().into_string().unwrap()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use std::fs::read_to_string;
+    use std::path::PathBuf;
+
+    use crate::config::parse_config;
+    use crate::generator::generate_charusters;
+
+    use super::*;
+
+    fn get_reroll_config() -> Config {
+        let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        d.push("resources/test/test_legendaries_config.toml");
+        let mut config = parse_config(d.to_str().unwrap());
+        config.legendaries.clear();
+        config.execution_conf.charusters_nums = 2;
+        config
+    }
+
+    #[test]
+    fn should_reroll_only_the_selected_features() {
+        let config = get_reroll_config();
+        let charusters = generate_charusters(Some(get_reroll_config()));
+        let before = serde_json::to_value(&charusters).unwrap();
+
+        let features = vec![String::from("nickname"), String::from("profession")];
+        let (rerolled, log) = reroll_charusters(&config, charusters, &[1], &features);
+        let after = serde_json::to_value(&rerolled).unwrap();
+
+        assert_eq!(before[0], after[0]);
+        assert_eq!(before[1]["name"], after[1]["name"]);
+        assert_eq!(before[1]["stats"], after[1]["stats"]);
+        assert_ne!(before[1]["profession"], after[1]["profession"]);
+        // the nickname can't be the old one nor the one of the other character
+        let nicknames: HashSet<&Value> = [&before[0]["nickname"], &before[1]["nickname"], &after[1]["nickname"]].into_iter().collect();
+        assert_eq!(nicknames.len(), 3);
+        assert_eq!(log.changes.len(), 2);
+        assert_eq!((log.changes[0].index, log.changes[0].feature.as_str()), (1, "nickname"));
+        assert_eq!(log.changes[0].old, before[1]["nickname"]);
+    }
+
+    #[test]
+    fn should_reroll_the_features_depending_on_the_selected_ones() {
+        let mut config = get_reroll_config();
+        config.char_conf.dependencies.insert(String::from("nickname"), vec![String::from("profession")]);
+        let charusters = generate_charusters(Some(get_reroll_config()));
+        let before = serde_json::to_value(&charusters).unwrap();
+
+        let (rerolled, log) = reroll_charusters(&config, charusters, &[1], &[String::from("profession")]);
+        let after = serde_json::to_value(&rerolled).unwrap();
+
+        let changes: Vec<(usize, &str)> = log.changes.iter().map(|change| (change.index, change.feature.as_str())).collect();
+        assert_eq!(changes, vec![(1, "profession"), (1, "nickname")]);
+        assert_ne!(before[1]["nickname"], after[1]["nickname"]);
+        assert_eq!(log.changes[1].new, after[1]["nickname"]);
+        assert_eq!(before[1]["name"], after[1]["name"]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Could not reroll image, it is not generated by the config")]
+    fn should_panic_on_features_not_generated() {
+        let charusters = generate_charusters(Some(get_reroll_config()));
+        reroll_charusters(&get_reroll_config(), charusters, &[0], &[String::from("image")]);
+    }
+
+    #[test]
+    fn should_name_the_change_log_after_the_collection() {
+        assert_eq!(changelog_filename("output/charusters.json"), "output/charusters.changes.json");
+        assert_eq!(changelog_filename("output/zombies.ndjson"), "output/zombies.changes.json");
+        assert_eq!(changelog_filename("charusters"), "charusters.changes.json");
+    }
+
+    #[test]
+    fn should_reroll_an_exported_collection() {
+        let dir = std::env::temp_dir().join(format!("charuster_reroll_{}", std::process::id()));
+        let filename = dir.join("charusters.ndjson").into_os_string().into_string().unwrap();
+        export_to_file(generate_charusters(Some(get_reroll_config())), ExportFormat::Ndjson, &filename);
+
+        let log = reroll_file(&get_reroll_config(), &filename, ExportFormat::Ndjson, &[0, 1], &[String::from("surname")]);
+        let charusters = import_from_file(&filename, ExportFormat::Ndjson);
+        assert_eq!(charusters.len(), 2);
+        assert_eq!(log.to_text().lines().last(), Some("2 features rerolled"));
+        let written: Value = serde_json::from_str(&read_to_string(dir.join("charusters.changes.json")).unwrap()).unwrap();
+        assert_eq!(written["changes"][1]["new"], Value::from(charusters[1].surname().as_str()));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,179 @@
+use std::collections::HashSet;
+
+use unicode_normalization::UnicodeNormalization;
+
+use crate::config::SanitationConfig;
+use crate::formats::Term;
+
+// max number of examples listed for each kind of change in the warnings
+const MAX_LISTED_TERMS: usize = 10;
+
+/// What the sanitation pipeline changed while loading a dictionary
+#[derive(Debug, Default, PartialEq)]
+pub struct SanitationReport {
+    pub dictionary: String,
+    /// terms that had leading or trailing whitespace
+    pub trimmed: usize,
+    /// `#` comment lines skipped
+    pub comments: usize,
+    /// blank lines skipped
+    pub blanks: usize,
+    /// terms rewritten by the unicode normalization
+    pub normalized: usize,
+    /// duplicated terms dropped, in order of appearance
+    pub duplicates: Vec<String>,
+}
+
+impl SanitationReport {
+    pub fn is_empty(&self) -> bool {
+        self.trimmed == 0 && self.comments == 0 && self.blanks == 0 && self.normalized == 0 && self.duplicates.is_empty()
+    }
+
+    /// human readable description of each change, one per line
+    pub fn warnings(&self) -> Vec<String> {
+        self.changes().into_iter().map(|change| format!("{}: {}", self.dictionary, change)).collect()
+    }
+
+    /// description of each change, without the name of the dictionary
+    pub fn changes(&self) -> Vec<String> {
+        let mut changes = vec![];
+        if self.trimmed > 0 {
+            changes.push(format!("trimmed whitespace from {} terms", self.trimmed));
+        }
+        if self.comments > 0 {
+            changes.push(format!("skipped {} comment lines", self.comments));
+        }
+        if self.blanks > 0 {
+            changes.push(format!("skipped {} blank lines", self.blanks));
+        }
+        if self.normalized > 0 {
+            changes.push(format!("normalized {} terms to unicode NFC", self.normalized));
+        }
+        if !self.duplicates.is_empty() {
+            let listed: Vec<&str> = self.duplicates.iter().take(MAX_LISTED_TERMS).map(|d| d.as_str()).collect();
+            let more = if self.duplicates.len() > MAX_LISTED_TERMS { ", ..." } else { "" };
+            changes.push(format!("removed {} duplicated terms ({}{})", self.duplicates.len(), listed.join(", "), more));
+        }
+        changes
+    }
+}
+
+/// clean up the terms read from a dictionary file according to the config, reporting every change.
+/// Comment lines are only recognized in plain text files (`with_comments`)
+pub fn sanitize_terms(dictionary: &str, terms: Vec<Term>, config: &SanitationConfig, with_comments: bool) -> (Vec<Term>, SanitationReport) {
+    let mut report = SanitationReport { dictionary: dictionary.to_owned(), ..Default::default() };
+    let mut seen = HashSet::new();
+    let mut sanitized = Vec::with_capacity(terms.len());
+
+    for mut term in terms {
+        if with_comments && config.skip_comments && term.value.trim_start().starts_with('#') {
+            report.comments += 1;
+            continue;
+        }
+        if config.trim {
+            let trimmed = term.value.trim();
+            if trimmed.len() != term.value.len() {
+                report.trimmed += 1;
+                term.value = trimmed.to_owned();
+            }
+        }
+        if config.skip_blank_lines && term.value.trim().is_empty() {
+            report.blanks += 1;
+            continue;
+        }
+        if config.normalize_unicode {
+            let normalized: String = term.value.nfc().collect();
+            if normalized != term.value {
+                report.normalized += 1;
+                term.value = normalized;
+            }
+        }
+        if config.deduplicate && !seen.insert(term.value.to_lowercase()) {
+            report.duplicates.push(term.value);
+            continue;
+        }
+        sanitized.push(term);
+    }
+
+    (sanitized, report)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn terms(values: &[&str]) -> Vec<Term> {
+        values.iter().map(|v| Term::new(v.to_string())).collect()
+    }
+
+    fn values(terms: &[Term]) -> Vec<&str> {
+        terms.iter().map(|t| t.value.as_str()).collect()
+    }
+
+    const DIRTY_TERMS: [&str; 9] = ["Plain", "  Bold\t", "# a comment", "", "   ", "plain", "Cafe\u{301}", "Café", "Dark"];
+
+    #[test]
+    fn should_sanitize_terms_with_default_config() {
+        let (sanitized, report) = sanitize_terms("dirty", terms(&DIRTY_TERMS), &SanitationConfig::default(), true);
+
+        assert_eq!(values(&sanitized), vec!["Plain", "Bold", "Café", "Dark"]);
+        assert_eq!(report.dictionary, "dirty");
+        assert_eq!(report.trimmed, 2);
+        assert_eq!(report.comments, 1);
+        assert_eq!(report.blanks, 2);
+        assert_eq!(report.normalized, 1);
+        assert_eq!(report.duplicates, vec!["plain", "Café"]);
+        assert_eq!(report.warnings().len(), 5);
+    }
+
+    #[test]
+    fn should_keep_terms_untouched_when_sanitation_is_disabled() {
+        let (sanitized, report) = sanitize_terms("dirty", terms(&DIRTY_TERMS), &SanitationConfig::disabled(), true);
+
+        assert_eq!(values(&sanitized), DIRTY_TERMS);
+        assert!(report.is_empty());
+        assert!(report.warnings().is_empty());
+    }
+
+    #[test]
+    fn should_not_skip_comments_in_structured_dictionaries() {
+        let (sanitized, report) = sanitize_terms("dirty", terms(&["#hashtag", "term"]), &SanitationConfig::default(), false);
+
+        assert_eq!(values(&sanitized), vec!["#hashtag", "term"]);
+        assert_eq!(report.comments, 0);
+    }
+
+    #[test]
+    fn should_not_merge_differently_composed_terms_when_normalization_is_off() {
+        let config = SanitationConfig { normalize_unicode: false, ..SanitationConfig::default() };
+        let (sanitized, report) = sanitize_terms("dirty", terms(&["Cafe\u{301}", "Café", "CAFÉ"]), &config, true);
+
+        assert_eq!(values(&sanitized), vec!["Cafe\u{301}", "Café"]);
+        assert_eq!(report.duplicates, vec!["CAFÉ"]);
+    }
+
+    #[test]
+    fn should_keep_the_metadata_of_the_first_occurrence() {
+        let mut dirty = terms(&["Plain", "plain"]);
+        dirty[0].weight = 2.0;
+        dirty[1].weight = 5.0;
+        let (sanitized, _) = sanitize_terms("dirty", dirty, &SanitationConfig::default(), true);
+
+        assert_eq!(sanitized.len(), 1);
+        assert_eq!(sanitized[0].weight, 2.0);
+    }
+
+    #[test]
+    fn should_list_a_limited_number_of_duplicates_in_the_warnings() {
+        let dirty: Vec<String> = (0..30).map(|i| format!("term_{}", i % 3)).collect();
+        let dirty: Vec<&str> = dirty.iter().map(|d| d.as_str()).collect();
+        let (_, report) = sanitize_terms("dirty", terms(&dirty), &SanitationConfig::default(), true);
+
+        assert_eq!(report.duplicates.len(), 27);
+        let warnings = report.warnings();
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].starts_with("dirty: removed 27 duplicated terms (term_0, term_1, term_2, term_0"));
+        assert!(warnings[0].ends_with(", ...)"));
+    }
+}
@@ -0,0 +1,344 @@
+use std::collections::HashSet;
+use std::fs::read_to_string;
+use std::path::Path;
+
+use rand::prelude::*;
+use serde::Deserialize;
+
+use crate::dictionary::Dictionary;
+
+/// suffix identifying syllable inventory files among the dictionary files
+pub const SYLLABLES_FILE_SUFFIX: &str = ".syllables.json";
+
+// max attempts to build a name that doesn't contain forbidden clusters (or that wasn't already removed)
+const MAX_ATTEMPTS: usize = 1000;
+
+/// a value with its selection weight; a plain string has weight 1
+#[derive(Debug, Deserialize, Clone)]
+#[serde(untagged)]
+enum WeightedValue {
+    Plain(String),
+    Weighted { value: String, weight: f64 },
+}
+
+impl WeightedValue {
+    fn value(&self) -> &str {
+        match self {
+            WeightedValue::Plain(value) => value,
+            WeightedValue::Weighted { value, .. } => value,
+        }
+    }
+
+    fn weight(&self) -> f64 {
+        match self {
+            WeightedValue::Plain(_) => 1.0,
+            WeightedValue::Weighted { weight, .. } => *weight,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+struct Phonology {
+    patterns: Vec<WeightedValue>,
+    onsets: Vec<WeightedValue>,
+    nuclei: Vec<WeightedValue>,
+    #[serde(default)]
+    codas: Vec<WeightedValue>,
+    #[serde(default = "default_syllables")]
+    min_syllables: u8,
+    #[serde(default = "default_syllables")]
+    max_syllables: u8,
+    #[serde(default)]
+    forbidden_clusters: Vec<String>,
+}
+
+fn default_syllables() -> u8 {
+    2
+}
+
+impl Phonology {
+    // every pattern is made of `C` and `V` slots, each one with values to draw from
+    fn check(&self) -> Result<(), String> {
+        check_values("patterns", &self.patterns)?;
+        for pattern in self.patterns.iter().map(|pattern| pattern.value()) {
+            if pattern.is_empty() {
+                return Err(String::from("a pattern is empty"));
+            }
+            let mut after_nucleus = false;
+            for slot in pattern.chars() {
+                let (name, values) = match slot.to_ascii_uppercase() {
+                    'V' => {
+                        after_nucleus = true;
+                        ("nuclei", &self.nuclei)
+                    }
+                    'C' if after_nucleus => ("codas", &self.codas),
+                    'C' => ("onsets", &self.onsets),
+                    _ => return Err(format!("pattern {} has the unknown slot '{}', only C and V are allowed", pattern, slot)),
+                };
+                check_values(name, values).map_err(|e| format!("pattern {} can't be built, {}", pattern, e))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// the values can be drawn: at least one of them has a positive weight and none a negative one
+fn check_values(name: &str, values: &[WeightedValue]) -> Result<(), String> {
+    if values.iter().any(|value| !value.weight().is_finite() || value.weight() < 0.0) {
+        return Err(format!("{} have invalid weights", name));
+    }
+    if !values.iter().any(|value| value.weight() > 0.0) {
+        return Err(format!("{} are empty", name));
+    }
+    Ok(())
+}
+
+// SyllableDictionary
+/// A dictionary whose terms are built on the fly from a syllable inventory.
+///
+/// Every syllable follows one of the configured patterns: each `C` before the `V` is drawn from the
+/// onsets, the `V` from the nuclei and each `C` after it from the codas. Names containing one of the
+/// forbidden clusters are discarded and built again.
+#[derive(Clone)]
+pub struct SyllableDictionary {
+    name: String,
+    phonology: Phonology,
+    removed: HashSet<String>,
+}
+
+impl SyllableDictionary {
+    /// create a new dictionary from a syllable inventory file, panicking if it can't be read
+    pub fn new(filename: &str) -> Self {
+        SyllableDictionary::from_file(filename).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// create a new dictionary from a syllable inventory file, returning an error if it can't be read or
+    /// some of its patterns can't be built from the inventory
+    pub fn from_file(filename: &str) -> Result<Self, String> {
+        let name = Path::new(filename).file_name()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| format!("Trying to create dictionary from non existing file: {}", filename))?;
+        let phonology = parse_phonology(filename)?;
+        phonology.check().map_err(|e| format!("Invalid syllable inventory {}: {}", filename, e))?;
+        Ok(SyllableDictionary { name: name.to_owned(), phonology, removed: HashSet::new() })
+    }
+
+    /// build a name made of a random number of syllables, retrying while it contains forbidden clusters
+    fn build_name(&self, rng: &mut dyn RngCore) -> Option<String> {
+        let min = self.phonology.min_syllables.min(self.phonology.max_syllables);
+        let max = self.phonology.max_syllables.max(min);
+
+        for _ in 0..MAX_ATTEMPTS {
+            let syllables = rng.gen_range(min..=max);
+            let mut name = String::new();
+            for _ in 0..syllables {
+                name.push_str(&self.build_syllable(rng)?);
+            }
+            if !name.is_empty() && !self.contains_forbidden_cluster(&name) {
+                return Some(capitalize(&name));
+            }
+        }
+        None
+    }
+
+    /// build a single syllable following a random pattern
+    fn build_syllable(&self, rng: &mut dyn RngCore) -> Option<String> {
+        let pattern = choose_weighted(&self.phonology.patterns, rng)?;
+        let mut syllable = String::new();
+        let mut after_nucleus = false;
+        for slot in pattern.chars() {
+            match slot.to_ascii_uppercase() {
+                'V' => {
+                    syllable.push_str(choose_weighted(&self.phonology.nuclei, rng)?);
+                    after_nucleus = true;
+                }
+                'C' if after_nucleus => syllable.push_str(choose_weighted(&self.phonology.codas, rng)?),
+                'C' => syllable.push_str(choose_weighted(&self.phonology.onsets, rng)?),
+                _ => unreachable!("the patterns of dictionary {} are checked when it's read", self.name),
+            }
+        }
+        Some(syllable)
+    }
+
+    fn contains_forbidden_cluster(&self, name: &str) -> bool {
+        let name = name.to_lowercase();
+        self.phonology.forbidden_clusters.iter()
+            .any(|cluster| name.contains(&cluster.to_lowercase()))
+    }
+}
+
+impl Dictionary for SyllableDictionary {
+    /// build a name never returned before by this method and return it
+    fn choose_and_remove_with(&mut self, rng: &mut dyn RngCore) -> Option<String> {
+        for _ in 0..MAX_ATTEMPTS {
+            let name = self.build_name(rng)?;
+            if self.removed.insert(name.clone()) {
+                return Some(name);
+            }
+        }
+        None
+    }
+
+    /// build a name and return it
+    fn choose_with(&self, rng: &mut dyn RngCore) -> Option<String> {
+        self.build_name(rng)
+    }
+}
+
+fn choose_weighted<'a>(values: &'a [WeightedValue], rng: &mut dyn RngCore) -> Option<&'a str> {
+    values.choose_weighted(rng, |v| v.weight())
+        .ok()
+        .map(|v| v.value())
+}
+
+fn capitalize(name: &str) -> String {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+fn parse_phonology(filename: &str) -> Result<Phonology, String> {
+    let json = read_to_string(filename).map_err(|_| format!("Dictionary not found: {}", filename))?;
+    serde_json::from_str(json.as_str()).map_err(|e| format!("Invalid syllable inventory {}: {}", filename, e))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    const ONSETS: [&str; 4] = ["k", "th", "r", "z"];
+    const NUCLEI: [&str; 3] = ["a", "o", "ae"];
+    const CODAS: [&str; 2] = ["n", "sh"];
+
+    fn get_test_syllables_filename() -> String {
+        let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        d.push("resources/test/test_dictionary.syllables.json");
+        d.into_os_string().into_string().unwrap()
+    }
+
+    // split a generated name back into the inventory pieces, returning false if it can't be done
+    fn is_made_of_inventory(name: &str) -> bool {
+        let pieces: Vec<&str> = ONSETS.iter().chain(NUCLEI.iter()).chain(CODAS.iter()).cloned().collect();
+        let mut reachable = vec![false; name.len() + 1];
+        reachable[0] = true;
+        for i in 0..name.len() {
+            if !reachable[i] {
+                continue;
+            }
+            for piece in pieces.iter() {
+                if name[i..].starts_with(piece) {
+                    reachable[i + piece.len()] = true;
+                }
+            }
+        }
+        reachable[name.len()]
+    }
+
+    #[test]
+    fn should_parse_syllables_file() {
+        let dict = SyllableDictionary::new(get_test_syllables_filename().as_str());
+        assert_eq!(dict.name, "test_dictionary.syllables.json");
+        assert_eq!(dict.phonology.patterns.len(), 2);
+        assert_eq!(dict.phonology.patterns[0].value(), "CV");
+        assert_eq!(dict.phonology.patterns[0].weight(), 3.0);
+        assert_eq!(dict.phonology.onsets.len(), ONSETS.len());
+        assert_eq!(dict.phonology.onsets[0].value(), "k");
+        assert_eq!(dict.phonology.onsets[0].weight(), 1.0);
+        assert_eq!(dict.phonology.min_syllables, 2);
+        assert_eq!(dict.phonology.max_syllables, 3);
+        assert_eq!(dict.phonology.forbidden_clusters, vec!["aeae", "nk"]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn should_panic_while_creating_dictionary_from_non_existing_file() {
+        SyllableDictionary::new("resources/test/not_existing.syllables.json");
+    }
+
+    #[test]
+    fn should_reject_patterns_that_cant_be_built() {
+        let mut phonology = SyllableDictionary::new(get_test_syllables_filename().as_str()).phonology;
+        phonology.patterns = vec![WeightedValue::Plain(String::from("CVX"))];
+        assert_eq!(phonology.check(), Err(String::from("pattern CVX has the unknown slot 'X', only C and V are allowed")));
+        phonology.patterns = vec![WeightedValue::Plain(String::from("VC"))];
+        phonology.codas = vec![];
+        assert_eq!(phonology.check(), Err(String::from("pattern VC can't be built, codas are empty")));
+        phonology.patterns = vec![];
+        assert_eq!(phonology.check(), Err(String::from("patterns are empty")));
+    }
+
+    #[test]
+    fn should_return_an_error_for_invalid_inventories() {
+        let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        d.push("resources/test/test_invalid_dictionary.syllables.json");
+        let error = SyllableDictionary::from_file(d.to_str().unwrap()).err().unwrap();
+        assert!(error.ends_with("pattern CVC can't be built, codas are empty"), "{}", error);
+    }
+
+    #[test]
+    fn should_build_names_from_the_inventory() {
+        let dict = SyllableDictionary::new(get_test_syllables_filename().as_str());
+        for _ in 0..1000 {
+            let name = dict.choose().unwrap();
+            let first = name.chars().next().unwrap();
+            assert!(first.is_uppercase());
+            assert!(is_made_of_inventory(&name.to_lowercase()), "unexpected name {}", name);
+        }
+    }
+
+    #[test]
+    fn should_never_build_names_with_forbidden_clusters() {
+        let dict = SyllableDictionary::new(get_test_syllables_filename().as_str());
+        for _ in 0..1000 {
+            let name = dict.choose().unwrap().to_lowercase();
+            assert!(!name.contains("aeae"));
+            assert!(!name.contains("nk"));
+        }
+    }
+
+    #[test]
+    fn should_follow_the_syllable_patterns() {
+        let mut dict = SyllableDictionary::new(get_test_syllables_filename().as_str());
+        dict.phonology.patterns = vec![WeightedValue::Plain(String::from("VC"))];
+        dict.phonology.min_syllables = 1;
+        dict.phonology.max_syllables = 1;
+        dict.phonology.forbidden_clusters = vec![];
+
+        for _ in 0..100 {
+            let name = dict.choose().unwrap().to_lowercase();
+            assert!(NUCLEI.iter().any(|n| name.starts_with(n)));
+            assert!(CODAS.iter().any(|c| name.ends_with(c)));
+        }
+    }
+
+    #[test]
+    fn should_choose_and_remove_distinct_names() {
+        let mut dict = SyllableDictionary::new(get_test_syllables_filename().as_str());
+        let mut names = HashSet::new();
+        for _ in 0..100 {
+            let name = dict.choose_and_remove().unwrap();
+            assert!(names.insert(name));
+        }
+        assert_eq!(dict.removed.len(), 100);
+    }
+
+    #[test]
+    fn should_return_none_when_all_names_were_removed() {
+        let mut dict = SyllableDictionary::new(get_test_syllables_filename().as_str());
+        dict.phonology.patterns = vec![WeightedValue::Plain(String::from("V"))];
+        dict.phonology.min_syllables = 1;
+        dict.phonology.max_syllables = 1;
+
+        let mut names = HashSet::new();
+        for _ in 0..NUCLEI.len() {
+            names.insert(dict.choose_and_remove().unwrap());
+        }
+        assert_eq!(names.len(), NUCLEI.len());
+        assert!(dict.choose_and_remove().is_none());
+    }
+}
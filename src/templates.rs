@@ -0,0 +1,152 @@
+use chrono::{Datelike, TimeZone, Utc};
+use serde_json::Value;
+use toml::Value as TomlValue;
+
+use crate::character::{Level, Property, Stat};
+use crate::config::{Config, RangeConfig, ValuesConfig};
+use crate::legendaries::LockedFeatures;
+
+/// the config generating the characters of a template: the archetypes that can't have the values of the
+/// template are left out, so the features generated along with them agree with the locked ones
+pub fn template_config(config: &Config, template: &LockedFeatures) -> Result<Config, String> {
+    if config.archetypes.is_empty() {
+        check_template(&config.values_conf, template)?;
+        return to_config(config, None);
+    }
+
+    let mut compatible = vec![];
+    let mut problems = vec![];
+    for (i, archetype) in config.archetypes.iter().enumerate() {
+        if template.get("archetype").is_some_and(|locked| locked != archetype.name.as_str()) {
+            continue;
+        }
+        match check_template(&config.archetype_config(i)?.values_conf, template) {
+            Ok(()) => compatible.push(i),
+            Err(problem) => problems.push(format!("archetype {}: {}", archetype.name, problem)),
+        }
+    }
+    if compatible.is_empty() {
+        return Err(match template.get("archetype") {
+            Some(archetype) if problems.is_empty() => format!("archetype {} is not declared", archetype.as_str().unwrap_or_default()),
+            _ => format!("no archetype can have the values of the template ({})", problems.join("; ")),
+        });
+    }
+    to_config(config, Some(&compatible))
+}
+
+// a copy of the config, keeping only the given archetypes
+fn to_config(config: &Config, archetypes: Option<&[usize]>) -> Result<Config, String> {
+    let mut value = TomlValue::try_from(config).map_err(|e| e.to_string())?;
+    if let (Some(archetypes), Some(TomlValue::Array(declared))) = (archetypes, value.get_mut("archetypes")) {
+        *declared = std::mem::take(declared).into_iter().enumerate()
+            .filter(|(i, _)| archetypes.contains(i))
+            .map(|(_, archetype)| archetype)
+            .collect();
+    }
+    value.try_into().map_err(|e| e.to_string())
+}
+
+/// check the locked features of a template against the values the config generates: allowed professions,
+/// property kinds, stats and levels ranges and birthdate years
+pub fn check_template(values_conf: &ValuesConfig, template: &LockedFeatures) -> Result<(), String> {
+    for (name, value) in template.iter() {
+        match name.as_str() {
+            "profession" => {
+                let profession = value.as_str().unwrap_or_default();
+                let allowed = &values_conf.allowed_professions;
+                if !allowed.is_empty() && !allowed.iter().any(|p| p == profession) {
+                    return Err(format!("profession {} is not one of {}", profession, allowed.join(", ")));
+                }
+            }
+            "props" => {
+                let kinds = &values_conf.property_kinds;
+                for prop in parse::<Vec<Property>>(name, value)? {
+                    if !kinds.is_empty() && !kinds.contains(&prop.prop_type) {
+                        return Err(format!("prop {} is of kind {}, not one of {}", prop.name, prop.prop_type, kinds.join(", ")));
+                    }
+                }
+            }
+            "stats" => {
+                let stats: Vec<Stat> = parse(name, value)?;
+                check_range(name, stats.iter().map(|stat| (&stat.name, stat.value)), &values_conf.stats_range)?;
+            }
+            "levels" => {
+                let levels: Vec<Level> = parse(name, value)?;
+                check_range(name, levels.iter().map(|level| (&level.name, level.value)), &values_conf.levels_range)?;
+            }
+            "birthdate" => {
+                let year = value.as_str().and_then(|date| date.parse::<i64>().ok())
+                    .and_then(|timestamp| Utc.timestamp_opt(timestamp, 0).single())
+                    .map(|date| date.year())
+                    .ok_or_else(|| format!("birthdate {} is not a timestamp", value))?;
+                if year < values_conf.birthdate_min_year as i32 || year > values_conf.birthdate_max_year as i32 {
+                    return Err(format!("birthdate year {} is out of {}-{}", year, values_conf.birthdate_min_year, values_conf.birthdate_max_year));
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn parse<T: serde::de::DeserializeOwned>(name: &str, value: &Value) -> Result<T, String> {
+    serde_json::from_value(value.clone()).map_err(|e| format!("invalid {}: {}", name, e))
+}
+
+fn check_range<'a>(feature: &str, values: impl Iterator<Item = (&'a String, i32)>, range: &RangeConfig) -> Result<(), String> {
+    for (name, value) in values {
+        if value < range.min || value > range.max {
+            return Err(format!("{} {} is {}, out of {}-{}", feature, name, value, range.min, range.max));
+        }
+    }
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use serde_json::json;
+
+    use crate::config::parse_config;
+
+    use super::*;
+
+    fn get_archetypes_config() -> Config {
+        let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        d.push("resources/test/test_archetypes_config.toml");
+        parse_config(d.to_str().unwrap())
+    }
+
+    fn template(value: Value) -> LockedFeatures {
+        value.as_object().unwrap().clone()
+    }
+
+    #[test]
+    fn should_keep_the_archetypes_agreeing_with_the_template() {
+        let config = template_config(&get_archetypes_config(), &template(json!({ "profession": "Librarian" }))).unwrap();
+        let names: Vec<&str> = config.archetypes.iter().map(|archetype| archetype.name.as_str()).collect();
+        assert_eq!(names, vec!["Scholar"]);
+
+        let config = template_config(&get_archetypes_config(), &template(json!({ "name": "Conan" }))).unwrap();
+        assert_eq!(config.archetypes.len(), 2);
+    }
+
+    #[test]
+    fn should_reject_templates_no_archetype_can_have() {
+        let config = get_archetypes_config();
+        assert_eq!(template_config(&config, &template(json!({ "archetype": "Bard" }))).err().unwrap(), "archetype Bard is not declared");
+        let message = template_config(&config, &template(json!({ "archetype": "Brute", "profession": "Librarian" }))).err().unwrap();
+        assert_eq!(message, "no archetype can have the values of the template (archetype Brute: profession Librarian is not one of Blacksmith, Guard)");
+    }
+
+    #[test]
+    fn should_check_the_locked_values_against_the_config() {
+        let values_conf = get_archetypes_config().values_conf;
+        let stats = json!({ "stats": [{ "name": "Strength", "value": 120, "max_value": 100 }] });
+        assert_eq!(check_template(&values_conf, &template(stats)), Err(String::from("stats Strength is 120, out of 1-100")));
+        assert!(check_template(&values_conf, &template(json!({ "birthdate": "1893456000" }))).is_err());
+        assert!(check_template(&values_conf, &template(json!({ "birthdate": "631152000", "profession": "Cook" }))).is_ok());
+    }
+}
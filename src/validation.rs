@@ -0,0 +1,760 @@
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fs::read_to_string;
+use std::path::Path;
+
+use serde::Serialize;
+use serde_path_to_error::Segment;
+
+use crate::config::{AttributeKind, Config, CONFIG_VERSION, config_files, env_override_key, PROFILE_VARIABLE, read_config_value, ValuesConfig};
+use crate::dictionary::SimpleDictionary;
+use crate::formats::DictionaryFormat;
+use crate::legendaries::check_legendaries;
+use crate::lint::Severity;
+use crate::quotas::check_quotas;
+use crate::syllables::SYLLABLES_FILE_SUFFIX;
+
+#[derive(Debug, Serialize)]
+pub struct ConfigProblem {
+    /// dotted path of the config key, with the index of the `[[attributes]]` tables
+    pub key: String,
+    /// 1-based line of the key in the config file, or in the file of its layer, when it's written there
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line: Option<usize>,
+    /// the file extended by the config or the environment variable setting the key, when it's not the config file
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub layer: Option<String>,
+    pub severity: Severity,
+    pub message: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ValidationReport {
+    pub config_file: String,
+    pub errors: usize,
+    pub warnings: usize,
+    pub problems: Vec<ConfigProblem>,
+}
+
+impl ValidationReport {
+    fn new(config_file: &str, problems: Vec<ConfigProblem>) -> Self {
+        let errors = problems.iter().filter(|problem| problem.severity == Severity::Error).count();
+        ValidationReport { config_file: config_file.to_owned(), errors, warnings: problems.len() - errors, problems }
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.errors > 0
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+
+    /// one line per problem followed by a summary line
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        for problem in self.problems.iter() {
+            let file = problem.layer.as_ref().unwrap_or(&self.config_file);
+            let location = match problem.line {
+                Some(line) => format!("{}:{}", file, line),
+                None => file.clone(),
+            };
+            let severity = match problem.severity {
+                Severity::Error => "error",
+                Severity::Warning => "warning",
+            };
+            text.push_str(&format!("{}: {} [{}] {}\n", location, severity, problem.key, problem.message));
+        }
+        text.push_str(&format!("{} errors, {} warnings\n", self.errors, self.warnings));
+        text
+    }
+}
+
+/// validate a config file with the profile selected by `CHARUSTER_PROFILE`, if any
+pub fn validate_config_file(config_filename: &str) -> ValidationReport {
+    validate_config_file_with_profile(config_filename, None)
+}
+
+/// parse a config file with its layers and check it all at once: unknown keys, inverted ranges, missing files
+/// and enabled features without a source are reported with the layer and the line of the key they refer to
+pub fn validate_config_file_with_profile(config_filename: &str, profile: Option<&str>) -> ValidationReport {
+    let source = match read_to_string(config_filename) {
+        Ok(source) => source,
+        Err(e) => return ValidationReport::new(config_filename, vec![
+            problem(Severity::Error, "", format!("could not read the config file: {}", e)),
+        ]),
+    };
+
+    let value: toml::Value = match toml::from_str(&source) {
+        Ok(value) => value,
+        Err(e) => {
+            let mut invalid = problem(Severity::Error, "", format!("invalid config: {}", e));
+            invalid.line = e.line_col().map(|(line, _)| line + 1);
+            return ValidationReport::new(config_filename, vec![invalid]);
+        }
+    };
+    let version = value.get("config_version").and_then(|v| v.as_integer()).unwrap_or(1);
+    if version > CONFIG_VERSION {
+        let mut newer = problem(Severity::Error, "config_version", format!("version {} is not supported, the latest version is {}", version, CONFIG_VERSION));
+        newer.line = find_line(&source, "config_version");
+        return ValidationReport::new(config_filename, vec![newer]);
+    }
+    let (value, notes) = match read_config_value(config_filename, profile) {
+        Ok(layers) => layers,
+        Err(e) => return ValidationReport::new(config_filename, vec![problem(Severity::Error, "", e)]),
+    };
+
+    let mut unknown_keys = vec![];
+    let mut ignored = |path: serde_ignored::Path| unknown_keys.push(path.to_string());
+    let parsed: Result<Config, _> = serde_path_to_error::deserialize(serde_ignored::Deserializer::new(value, &mut ignored));
+    let mut problems = match parsed {
+        Ok(config) => validate_config(&config),
+        Err(e) => vec![problem(Severity::Error, &dotted_key(e.path()), format!("invalid config: {}", e.inner()))],
+    };
+    for note in notes {
+        problems.insert(0, problem(Severity::Warning, "config_version", note));
+    }
+    for key in unknown_keys {
+        problems.insert(0, problem(Severity::Warning, &key, String::from("unknown key, it is ignored")));
+    }
+
+    let overrides = env_overrides(env::vars(), &mut problems);
+    let profile = profile.map(str::to_owned).or_else(|| env::var(PROFILE_VARIABLE).ok());
+    let bases: Vec<(String, String)> = config_files(config_filename).unwrap_or_default().into_iter().skip(1)
+        .filter_map(|file| read_to_string(&file).ok().map(|source| (file.display().to_string(), source)))
+        .collect();
+    for problem in problems.iter_mut().filter(|problem| problem.line.is_none() && problem.layer.is_none()) {
+        locate(problem, &source, profile.as_deref(), &bases, &overrides);
+    }
+    problems.sort_by_key(|problem| (problem.layer.clone(), problem.line));
+    ValidationReport::new(config_filename, problems)
+}
+
+// the keys overridden by the environment variables, along with their names. The variables outside the sections
+// are ignored by the config and reported as warnings
+fn env_overrides<I>(vars: I, problems: &mut Vec<ConfigProblem>) -> Vec<(String, String)>
+where I: IntoIterator<Item = (String, String)> {
+    let mut overrides = vec![];
+    for (name, _) in vars {
+        match env_override_key(&name) {
+            Some(Ok(key)) => overrides.push((name, key)),
+            Some(Err(message)) => {
+                let mut ignored = problem(Severity::Warning, "", format!("{}, it is ignored", message));
+                ignored.layer = Some(name);
+                problems.push(ignored);
+            }
+            None => {}
+        }
+    }
+    overrides
+}
+
+// point a problem at the layer setting its key, from the one taking precedence: an environment variable,
+// the selected profile, the config file and then the files it extends
+fn locate(problem: &mut ConfigProblem, source: &str, profile: Option<&str>, bases: &[(String, String)], overrides: &[(String, String)]) {
+    let key = problem.key.as_str();
+    if let Some((name, _)) = overrides.iter().find(|(_, overridden)| key == overridden || key.starts_with(&format!("{}.", overridden))) {
+        problem.layer = Some(name.clone());
+        return;
+    }
+    problem.line = profile.and_then(|profile| find_line(source, &format!("profiles.{}.{}", profile, key)))
+        .or_else(|| key_line(source, key));
+    if problem.line.is_none() {
+        if let Some((file, line)) = bases.iter().find_map(|(file, source)| key_line(source, key).map(|line| (file, line))) {
+            problem.layer = Some(file.clone());
+            problem.line = Some(line);
+        }
+    }
+}
+
+fn key_line(source: &str, key: &str) -> Option<usize> {
+    find_line(source, key).or_else(|| collection_line(source, key))
+}
+
+/// check the values of a parsed config, the problems have no line
+pub fn validate_config(config: &Config) -> Vec<ConfigProblem> {
+    let mut problems = vec![];
+    let char_conf = &config.char_conf;
+    let values = &config.values_conf;
+
+    if config.execution_conf.charusters_nums == 0 {
+        problems.push(problem(Severity::Warning, "execution_conf.charusters_nums", String::from("no characters will be generated")));
+    }
+    if values.birthdate_min_year > values.birthdate_max_year {
+        problems.push(problem(Severity::Error, "values_conf.birthdate_min_year",
+                              format!("birthdate_min_year ({}) is greater than birthdate_max_year ({})", values.birthdate_min_year, values.birthdate_max_year)));
+    }
+
+    let sources = [
+        ("names_file", &values.names_file, char_conf.gen_name, "gen_name"),
+        ("surnames_file", &values.surnames_file, char_conf.gen_surname, "gen_surname"),
+        ("nicknames_file", &values.nicknames_file, char_conf.gen_nickname, "gen_nickname"),
+        ("birthplaces_file", &values.birthplaces_file, char_conf.gen_birthplace, "gen_birthplace"),
+        ("description_files", &values.description_files, char_conf.gen_description, "gen_description"),
+        ("images_folder", &values.images_folder, char_conf.gen_image, "gen_image"),
+        ("professions_file", &values.professions_file, char_conf.gen_profession && values.allowed_professions.is_empty(), "gen_profession"),
+        ("hobbies_file", &values.hobbies_file, char_conf.gen_hobbies, "gen_hobbies"),
+        ("props_file", &values.props_file, char_conf.gen_props, "gen_props"),
+        ("levels_file", &values.levels_file, char_conf.gen_levels, "gen_levels"),
+        ("stats_file", &values.stats_file, char_conf.gen_stats, "gen_stats"),
+    ];
+    for (key, filename, enabled, toggle) in sources {
+        if !enabled {
+            continue;
+        }
+        let key = format!("values_conf.{}", key);
+        if filename.is_empty() {
+            problems.push(problem(Severity::Warning, &format!("char_conf.{}", toggle),
+                                  format!("the feature is enabled but {} is empty, it won't be generated", key)));
+        } else if let Some(message) = check_path(filename, key.ends_with("images_folder")) {
+            problems.push(problem(Severity::Error, &key, message));
+        } else if !key.ends_with("images_folder") && !key.ends_with("props_file") {
+            sanitation_problems(&key, filename, values, &mut problems);
+        }
+    }
+    for (key, range) in [("stats_range", &values.stats_range), ("levels_range", &values.levels_range)] {
+        if range.min > range.max {
+            problems.push(problem(Severity::Error, &format!("values_conf.{}.min", key),
+                                  format!("min ({}) is greater than max ({}) in {}", range.min, range.max, key)));
+        }
+    }
+    let unsupported: Vec<&str> = [("deduplicate", values.sanitation.deduplicate), ("normalize_unicode", values.sanitation.normalize_unicode)]
+        .into_iter().filter(|(_, enabled)| *enabled).map(|(step, _)| step).collect();
+    if values.lazy_dictionaries && !unsupported.is_empty() {
+        problems.push(problem(Severity::Warning, "values_conf.lazy_dictionaries",
+                              format!("lazy dictionaries are only trimmed and skip comments and blank lines, sanitation.{} not applied to them",
+                                      unsupported.join(" and sanitation.") + if unsupported.len() > 1 { " are" } else { " is" })));
+    }
+    if char_conf.gen_collection && values.collection_name.is_empty() {
+        problems.push(problem(Severity::Warning, "values_conf.collection_name", String::from("the collection is enabled but its name is empty")));
+    }
+
+    let mut attribute_names = HashSet::new();
+    for (i, attribute) in config.attributes.iter().enumerate() {
+        let key = |field: &str| format!("attributes.{}.{}", i, field);
+        if !attribute_names.insert(attribute.name.as_str()) {
+            problems.push(problem(Severity::Error, &key("name"), format!("attribute {} is declared more than once", attribute.name)));
+        }
+        match attribute.kind {
+            AttributeKind::Choose | AttributeKind::ChooseAndRemove | AttributeKind::Vector => {
+                if attribute.source.is_empty() {
+                    problems.push(problem(Severity::Error, &key("kind"), format!("attribute {} has no source", attribute.name)));
+                } else if let Some(message) = check_path(&attribute.source, false) {
+                    problems.push(problem(Severity::Error, &key("source"), message));
+                } else {
+                    sanitation_problems(&key("source"), &attribute.source, values, &mut problems);
+                }
+                if attribute.kind == AttributeKind::Vector && attribute.count == 0 {
+                    problems.push(problem(Severity::Warning, &key("count"), format!("attribute {} will always be empty", attribute.name)));
+                }
+            }
+            AttributeKind::Number | AttributeKind::Date => {
+                if let (Some(min), Some(max)) = (attribute.min, attribute.max) {
+                    if min > max {
+                        problems.push(problem(Severity::Error, &key("min"), format!("min ({}) is greater than max ({}) in attribute {}", min, max, attribute.name)));
+                    }
+                }
+            }
+        }
+    }
+
+    let features = feature_names(config);
+    for (feature, dependencies) in char_conf.dependencies.iter() {
+        for dependency in dependencies.iter().filter(|dependency| !features.contains(dependency.as_str())) {
+            problems.push(problem(Severity::Warning, &format!("char_conf.dependencies.{}", feature),
+                                  format!("{} depends on {}, which is not a built-in feature nor an attribute", feature, dependency)));
+        }
+    }
+
+    for (i, message) in check_quotas(config) {
+        problems.push(problem(Severity::Error, &format!("quotas.{}", i), message));
+    }
+    for (i, message) in check_legendaries(config) {
+        let key = match i {
+            Some(i) => format!("legendaries.{}", i),
+            None => String::from("legendaries"),
+        };
+        problems.push(problem(Severity::Error, &key, message));
+    }
+
+    // the collections are checked with their own sections, reporting only what differs from the config
+    let mut collection_names = HashSet::new();
+    for (i, collection) in config.collections.iter().enumerate() {
+        let key = format!("collections.{}", i);
+        if collection.name.is_empty() {
+            problems.push(problem(Severity::Error, &format!("{}.name", key), String::from("the collection has no name")));
+        } else if !collection_names.insert(collection.name.as_str()) {
+            problems.push(problem(Severity::Error, &format!("{}.name", key), format!("collection {} is declared more than once", collection.name)));
+        }
+        for unknown in collection.unknown_keys() {
+            problems.push(problem(Severity::Warning, &format!("{}.{}", key, unknown), String::from("unknown key, it is ignored")));
+        }
+        derived_problems(&key, config.collection_config(i), &mut problems);
+    }
+
+    let mut archetype_names = HashSet::new();
+    for (i, archetype) in config.archetypes.iter().enumerate() {
+        let key = format!("archetypes.{}", i);
+        if archetype.name.is_empty() {
+            problems.push(problem(Severity::Error, &format!("{}.name", key), String::from("the archetype has no name")));
+        } else if !archetype_names.insert(archetype.name.as_str()) {
+            problems.push(problem(Severity::Error, &format!("{}.name", key), format!("archetype {} is declared more than once", archetype.name)));
+        }
+        if archetype.weight.is_nan() || archetype.weight <= 0.0 {
+            problems.push(problem(Severity::Error, &format!("{}.weight", key), format!("the weight of archetype {} must be greater than 0", archetype.name)));
+        }
+        for unknown in archetype.unknown_keys() {
+            problems.push(problem(Severity::Warning, &format!("{}.values_conf.{}", key, unknown), String::from("unknown key, it is ignored")));
+        }
+        for feature in archetype.features().into_iter().filter(|feature| !char_conf.is_enabled(feature)) {
+            problems.push(problem(Severity::Warning, &format!("{}.values_conf", key),
+                                  format!("archetype {} overrides {}, which is not generated", archetype.name, feature)));
+        }
+        derived_problems(&key, config.archetype_config(i), &mut problems);
+    }
+
+    problems
+}
+
+// the problems of a config derived from the config (a collection or an archetype) that the config doesn't
+// have, under the key of the derived config
+fn derived_problems(key: &str, derived: Result<Config, String>, problems: &mut Vec<ConfigProblem>) {
+    match derived {
+        Ok(derived) => {
+            let derived_problems: Vec<ConfigProblem> = validate_config(&derived).into_iter()
+                .filter(|p| !problems.iter().any(|known| known.key == p.key && known.message == p.message))
+                .collect();
+            for derived_problem in derived_problems {
+                problems.push(problem(derived_problem.severity, &format!("{}.{}", key, derived_problem.key), derived_problem.message));
+            }
+        }
+        Err(message) => problems.push(problem(Severity::Error, key, message)),
+    }
+}
+
+// names of the built-in features and of the attributes; custom features can't be known before the generation
+fn feature_names(config: &Config) -> HashSet<&str> {
+    let mut features: HashSet<&str> = ["name", "surname", "nickname", "birthdate", "birthplace", "description", "image",
+        "collection", "profession", "hobbies", "props", "levels", "stats"].into_iter().collect();
+    features.extend(config.attributes.iter().map(|attribute| attribute.name.as_str()));
+    features.extend(config.char_conf.custom_features.iter().map(|feature| feature.as_str()));
+    features
+}
+
+fn check_path(filename: &str, folder: bool) -> Option<String> {
+    let (_, path) = DictionaryFormat::from_filename(filename);
+    let path = Path::new(path);
+    if !path.exists() {
+        Some(format!("{} not found", path.display()))
+    } else if folder && !path.is_dir() {
+        Some(format!("{} is not a folder", path.display()))
+    } else {
+        None
+    }
+}
+
+// the changes the sanitation makes to the terms of a dictionary while it's loaded, as warnings. Syllable
+// inventories are not sanitized and lazy dictionaries are not loaded up front
+fn sanitation_problems(key: &str, filename: &str, values: &ValuesConfig, problems: &mut Vec<ConfigProblem>) {
+    let (format, _) = DictionaryFormat::from_filename(filename);
+    if filename.ends_with(SYLLABLES_FILE_SUFFIX) || (values.lazy_dictionaries && format == DictionaryFormat::Text) {
+        return;
+    }
+    match SimpleDictionary::try_new_sanitized(filename, &values.sanitation) {
+        Ok((_, report)) => for change in report.changes() {
+            problems.push(problem(Severity::Warning, key, format!("sanitizing {}: {}", filename, change)));
+        },
+        Err(e) => problems.push(problem(Severity::Error, key, e)),
+    }
+}
+
+// the dotted key of the value a deserialization error comes from, with the index of the array elements
+fn dotted_key(path: &serde_path_to_error::Path) -> String {
+    let parts: Vec<String> = path.iter().filter_map(|segment| match segment {
+        Segment::Map { key } => Some(key.clone()),
+        Segment::Seq { index } => Some(index.to_string()),
+        _ => None,
+    }).collect();
+    parts.join(".")
+}
+
+fn problem(severity: Severity, key: &str, message: String) -> ConfigProblem {
+    ConfigProblem { key: key.to_owned(), line: None, layer: None, severity, message }
+}
+
+// the problems of a collection or an archetype coming from the sections of the config point at its table
+fn collection_line(source: &str, key: &str) -> Option<usize> {
+    let mut parts = key.splitn(3, '.');
+    match (parts.next(), parts.next()) {
+        (Some(array @ ("collections" | "archetypes")), Some(index)) => find_line(source, &format!("{}.{}", array, index)),
+        _ => None,
+    }
+}
+
+// line of a dotted key in the TOML source, following the `[table]` and `[[array]]` headers.
+// The key of a table is found at its header
+fn find_line(source: &str, key: &str) -> Option<usize> {
+    if key.is_empty() {
+        return None;
+    }
+    let key: Vec<&str> = key.split('.').collect();
+    let mut table: Vec<String> = vec![];
+    let mut array_lengths: HashMap<String, usize> = HashMap::new();
+
+    for (i, line) in source.lines().enumerate() {
+        let line = line.trim();
+        if let Some(header) = line.strip_prefix("[[").and_then(|l| l.split("]]").next()) {
+            let header = header.trim();
+            let index = array_lengths.entry(header.to_owned()).or_insert(0);
+            table = header.split('.').map(|part| part.trim().to_owned()).collect();
+            table.push(index.to_string());
+            *index += 1;
+        } else if let Some(header) = line.strip_prefix('[').and_then(|l| l.split(']').next()) {
+            table = header.split('.').map(|part| part.trim().to_owned()).collect();
+            // a table in an array of tables belongs to its last element
+            if let Some(end) = (1..table.len()).rev().find(|end| array_lengths.contains_key(&table[..*end].join("."))) {
+                let index = array_lengths[&table[..end].join(".")] - 1;
+                table.insert(end, index.to_string());
+            }
+        } else if let Some((name, _)) = line.split_once('=') {
+            let name = name.trim().trim_matches('"');
+            if table.len() + 1 == key.len() && key[..table.len()] == table[..] && key[table.len()] == name {
+                return Some(i + 1);
+            }
+            continue;
+        } else {
+            continue;
+        }
+        if table[..] == key[..] {
+            return Some(i + 1);
+        }
+    }
+    None
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::config::{LegendaryConfig, QuotaAmount};
+
+    use super::*;
+
+    fn get_resource_filename(name: &str) -> String {
+        let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        d.push("resources/".to_owned() + name);
+        d.into_os_string().into_string().unwrap()
+    }
+
+    fn problems_of<'a>(report: &'a ValidationReport, key: &str) -> Vec<&'a ConfigProblem> {
+        report.problems.iter().filter(|problem| problem.key == key).collect()
+    }
+
+    // the problems other than the changes of the sanitation, which the shipped dictionaries need
+    fn without_sanitation<'a, I>(problems: I) -> Vec<&'a ConfigProblem>
+    where I: IntoIterator<Item = &'a ConfigProblem> {
+        problems.into_iter().filter(|problem| !problem.message.starts_with("sanitizing ")).collect()
+    }
+
+    const SOURCE: &str = "[execution_conf]
+    charusters_nums = 5
+
+[char_conf]
+    gen_name = true
+
+[[attributes]]
+    name = \"luck\"
+
+[[attributes]]
+    name = \"titles\"
+    count = 2
+
+[[collections]]
+    name = \"Zombies\"
+[collections.values_conf]
+    names_file = \"zombies\"
+";
+
+    #[test]
+    fn should_find_the_line_of_a_key() {
+        assert_eq!(find_line(SOURCE, "execution_conf.charusters_nums"), Some(2));
+        assert_eq!(find_line(SOURCE, "char_conf"), Some(4));
+        assert_eq!(find_line(SOURCE, "char_conf.gen_name"), Some(5));
+        assert_eq!(find_line(SOURCE, "attributes.0.name"), Some(8));
+        assert_eq!(find_line(SOURCE, "attributes.1"), Some(10));
+        assert_eq!(find_line(SOURCE, "attributes.1.count"), Some(12));
+        assert_eq!(find_line(SOURCE, "attributes.0.count"), None);
+        assert_eq!(find_line(SOURCE, "collections.0.values_conf.names_file"), Some(17));
+        assert_eq!(collection_line(SOURCE, "collections.0.char_conf.gen_name"), Some(14));
+        assert_eq!(find_line(SOURCE, "gen_name"), None);
+    }
+
+    #[test]
+    fn should_report_all_the_problems_of_the_config() {
+        let report = validate_config_file(get_resource_filename("test/test_invalid_config.toml").as_str());
+
+        let problems: Vec<(&str, Option<usize>, Severity)> = report.problems.iter()
+            .map(|problem| (problem.key.as_str(), problem.line, problem.severity))
+            .collect();
+        assert_eq!(problems, vec![
+            ("execution_conf.unknown_key", Some(5), Severity::Warning),
+            ("char_conf.gen_birthplace", Some(12), Severity::Warning),
+            ("char_conf.gen_hobbies", Some(19), Severity::Warning),
+            ("values_conf.names_file", Some(26), Severity::Error),
+            ("values_conf.images_folder", Some(35), Severity::Error),
+            ("values_conf.birthdate_min_year", Some(37), Severity::Error),
+            ("attributes.0.source", Some(43), Severity::Error),
+            ("attributes.1.min", Some(48), Severity::Error),
+            ("attributes.2.name", Some(52), Severity::Error),
+        ]);
+        assert_eq!(report.errors, 6);
+        assert_eq!(report.warnings, 3);
+        assert!(report.to_text().contains("test_invalid_config.toml:37: error [values_conf.birthdate_min_year] birthdate_min_year (2010) is greater than birthdate_max_year (1920)\n"));
+    }
+
+    #[test]
+    fn should_report_the_line_of_syntax_errors() {
+        let report = validate_config_file(get_resource_filename("test/test_broken_config.toml").as_str());
+        assert_eq!(report.errors, 1);
+        assert_eq!(report.problems[0].line, Some(3));
+        assert!(report.problems[0].message.starts_with("invalid config"));
+    }
+
+    #[test]
+    fn should_report_the_line_of_mistyped_values() {
+        let report = validate_config_file(get_resource_filename("test/test_mistyped_config.toml").as_str());
+        assert_eq!(report.errors, 1);
+        assert_eq!(report.problems[0].key, "attributes.1.max");
+        assert_eq!(report.problems[0].line, Some(12));
+        assert!(report.problems[0].message.starts_with("invalid config: invalid type: string"), "{}", report.problems[0].message);
+    }
+
+    #[test]
+    fn should_report_the_layer_of_the_problems() {
+        let filename = get_resource_filename("test/test_invalid_layered_config.toml");
+        let report = validate_config_file_with_profile(filename.as_str(), Some("broken"));
+
+        let problems: Vec<(&str, Option<usize>, Option<&str>)> = without_sanitation(&report.problems).into_iter()
+            .map(|problem| (problem.key.as_str(), problem.line, problem.layer.as_deref()))
+            .collect();
+        let base = get_resource_filename("test/layers/test_invalid_base_config.toml");
+        assert_eq!(problems, vec![
+            ("values_conf.surnames_file", Some(8), None),
+            ("values_conf.names_file", Some(12), Some(base.as_str())),
+        ], "{}", report.to_text());
+        assert!(report.to_text().contains(&format!("{}:12: error [values_conf.names_file]", base)));
+    }
+
+    #[test]
+    fn should_report_the_environment_variables_overriding_keys() {
+        let mut problems = vec![problem(Severity::Error, "values_conf.sanitation.trim", String::from("invalid"))];
+        let overrides = env_overrides(vec![
+            (String::from("CHARUSTER_VALUES_CONF__SANITATION"), String::from("{ trim = 1 }")),
+            (String::from("CHARUSTER_FOO__BAR"), String::from("1")),
+            (String::from("HOME"), String::from("/root")),
+        ], &mut problems);
+        locate(&mut problems[0], SOURCE, None, &[], &overrides);
+
+        assert_eq!(problems[0].layer.as_deref(), Some("CHARUSTER_VALUES_CONF__SANITATION"));
+        assert_eq!(problems[0].line, None);
+        assert_eq!(problems[1].layer.as_deref(), Some("CHARUSTER_FOO__BAR"));
+        assert_eq!(problems[1].severity, Severity::Warning);
+        assert_eq!(problems[1].message, "CHARUSTER_FOO__BAR does not override a key of execution_conf, char_conf or values_conf, it is ignored");
+    }
+
+    #[test]
+    fn should_report_the_migrations_of_older_configs() {
+        let report = validate_config_file(get_resource_filename("test/test_config.toml").as_str());
+        let versions = problems_of(&report, "config_version");
+        assert_eq!(versions.len(), 1);
+        assert_eq!(versions[0].severity, Severity::Warning);
+        assert_eq!(versions[0].message, "the config is read as version 1 and upgraded: char_conf.gen_birthplace set to true like gen_birthdate");
+    }
+
+    #[test]
+    fn should_report_unreadable_config_files() {
+        let report = validate_config_file("resources/test/not_existing.toml");
+        assert!(report.has_errors());
+        assert_eq!(report.problems[0].line, None);
+    }
+
+    #[test]
+    fn should_accept_the_shipped_config() {
+        let report = validate_config_file(get_resource_filename("config.toml").as_str());
+        assert!(!report.has_errors(), "{}", report.to_text());
+    }
+
+    #[test]
+    fn should_report_dependencies_on_unknown_features() {
+        let mut config = crate::config::parse_config(get_resource_filename("test/test_config.toml").as_str());
+        config.char_conf.dependencies.insert(String::from("name"), vec![String::from("luck"), String::from("mood")]);
+
+        let problems = validate_config(&config);
+        let dependencies: Vec<&str> = problems.iter()
+            .filter(|problem| problem.key == "char_conf.dependencies.name")
+            .map(|problem| problem.message.as_str())
+            .collect();
+        assert_eq!(dependencies, vec!["name depends on mood, which is not a built-in feature nor an attribute"]);
+        assert!(problems_of(&ValidationReport::new("", problems), "char_conf.dependencies.luck").is_empty());
+    }
+
+    #[test]
+    fn should_report_the_problems_of_the_collections() {
+        let report = validate_config_file(get_resource_filename("test/test_collections_config.toml").as_str());
+
+        let problems: Vec<(&str, Option<usize>, Severity)> = without_sanitation(&report.problems).into_iter()
+            .map(|problem| (problem.key.as_str(), problem.line, problem.severity))
+            .collect();
+        assert_eq!(problems, vec![
+            ("collections.1.values_conf.names_file", Some(21), Severity::Error),
+            ("collections.2.name", Some(24), Severity::Error),
+            ("collections.2.values_conf.birthdate_min_year", Some(26), Severity::Error),
+            ("collections.2.execution_conf.charusters_num", Some(30), Severity::Warning),
+            ("collections.2.execution_cof", Some(31), Severity::Warning),
+        ]);
+    }
+
+    #[test]
+    fn should_report_the_problems_of_the_archetypes() {
+        let mut config = crate::config::parse_config(get_resource_filename("test/test_archetypes_config.toml").as_str());
+        config.archetypes[0].weight = 0.0;
+        config.archetypes[0].values_conf.insert(String::from("names"), toml::Value::from("brutes"));
+        config.archetypes[1].name = String::from("Brute");
+        config.archetypes[1].values_conf.insert(String::from("names_file"), toml::Value::from("not_existing"));
+        config.char_conf.gen_levels = false;
+        config.values_conf.stats_range.min = 101;
+
+        let problems = validate_config(&config);
+        let problems: Vec<(&str, Severity)> = without_sanitation(&problems).into_iter()
+            .map(|problem| (problem.key.as_str(), problem.severity))
+            .collect();
+        assert_eq!(problems, vec![
+            ("values_conf.stats_range.min", Severity::Error),
+            ("archetypes.0.weight", Severity::Error),
+            ("archetypes.0.values_conf.names", Severity::Warning),
+            ("archetypes.1.name", Severity::Error),
+            ("archetypes.1.values_conf", Severity::Warning),
+            ("archetypes.1.values_conf.names_file", Severity::Error),
+        ]);
+    }
+
+    #[test]
+    fn should_report_infeasible_quotas() {
+        let mut config = crate::config::parse_config(get_resource_filename("test/test_quotas_config.toml").as_str());
+        config.quotas[1].value = String::from("sub_term_1_2");
+        config.quotas[1].max = None;
+        config.quotas[1].min = Some(QuotaAmount::Count(45));
+        config.quotas[2].feature = String::from("birthdate");
+        config.quotas[3].min = Some(QuotaAmount::Count(1));
+
+        let problems = validate_config(&config);
+        let problems: Vec<(&str, &str)> = without_sanitation(&problems).into_iter()
+            .map(|problem| (problem.key.as_str(), problem.message.as_str()))
+            .collect();
+        assert_eq!(problems, vec![
+            ("quotas.0", "quotas on props of kind term_1 need 55 characters, the collection has 50"),
+            ("quotas.2", "quotas are not supported on birthdate"),
+            ("quotas.3", "exactly can't be set along with min or max"),
+        ]);
+    }
+
+    #[test]
+    fn should_report_quotas_the_dictionaries_cant_meet() {
+        let mut config = crate::config::parse_config(get_resource_filename("test/test_quotas_config.toml").as_str());
+        config.quotas[2].value = String::from("Famer");
+        let problems: Vec<(String, String)> = without_sanitation(&validate_config(&config)).into_iter()
+            .map(|problem| (problem.key.clone(), problem.message.clone()))
+            .collect();
+        let professions_file = &config.values_conf.professions_file;
+        assert_eq!(problems, vec![(String::from("quotas.2"), format!("Famer is not a term of {}", professions_file))]);
+
+        config.quotas[2].value = String::from("Farmer");
+        config.quotas[2].min = None;
+        config.quotas[2].max = Some(QuotaAmount::Count(10));
+        config.values_conf.allowed_professions = vec![String::from("Farmer"), String::from("Acrobat")];
+        let problems: Vec<(String, String)> = without_sanitation(&validate_config(&config)).into_iter()
+            .map(|problem| (problem.key.clone(), problem.message.clone()))
+            .collect();
+        assert_eq!(problems, vec![(String::from("quotas.2"),
+                                   String::from("quotas on profession allow 10 characters of the 2 terms of allowed_professions, the collection has 50"))]);
+
+        config.values_conf.allowed_professions = vec![String::from("Farmer"); 1000];
+        config.values_conf.allowed_professions.push(String::from("Guard"));
+        let problems: Vec<(String, String)> = without_sanitation(&validate_config(&config)).into_iter()
+            .map(|problem| (problem.key.clone(), problem.message.clone()))
+            .collect();
+        assert_eq!(problems, vec![
+            (String::from("quotas.2"), String::from("the characters without Farmer would rarely get another term of allowed_professions")),
+            (String::from("quotas.3"), String::from("Acrobat is not a term of allowed_professions")),
+        ]);
+    }
+
+    #[test]
+    fn should_report_the_changes_the_sanitation_makes_to_the_dictionaries() {
+        let mut config = crate::config::parse_config(get_resource_filename("test/test_quotas_config.toml").as_str());
+        config.quotas.clear();
+        config.values_conf.names_file = get_resource_filename("test/test_dirty_dictionary");
+        let names_file = config.values_conf.names_file.clone();
+        let problems: Vec<(String, Severity, String)> = validate_config(&config).into_iter()
+            .filter(|problem| problem.key == "values_conf.names_file")
+            .map(|problem| (problem.key, problem.severity, problem.message))
+            .collect();
+        assert_eq!(problems, vec![
+            (String::from("values_conf.names_file"), Severity::Warning, format!("sanitizing {}: trimmed whitespace from 1 terms", names_file)),
+            (String::from("values_conf.names_file"), Severity::Warning, format!("sanitizing {}: skipped 1 comment lines", names_file)),
+            (String::from("values_conf.names_file"), Severity::Warning, format!("sanitizing {}: skipped 1 blank lines", names_file)),
+            (String::from("values_conf.names_file"), Severity::Warning, format!("sanitizing {}: removed 1 duplicated terms (TERM_1)", names_file)),
+        ]);
+
+        config.values_conf.lazy_dictionaries = true;
+        assert!(validate_config(&config).iter().all(|problem| problem.key != "values_conf.names_file"));
+    }
+
+    #[test]
+    fn should_warn_about_the_sanitation_lazy_dictionaries_skip() {
+        let mut config = crate::config::parse_config(get_resource_filename("test/test_quotas_config.toml").as_str());
+        config.quotas.clear();
+        config.values_conf.lazy_dictionaries = true;
+        let messages = |config: &Config| -> Vec<String> {
+            validate_config(config).into_iter()
+                .filter(|problem| problem.key == "values_conf.lazy_dictionaries")
+                .map(|problem| problem.message)
+                .collect()
+        };
+        assert_eq!(messages(&config), vec![String::from(
+            "lazy dictionaries are only trimmed and skip comments and blank lines, sanitation.deduplicate and sanitation.normalize_unicode are not applied to them")]);
+
+        config.values_conf.sanitation.deduplicate = false;
+        assert_eq!(messages(&config), vec![String::from(
+            "lazy dictionaries are only trimmed and skip comments and blank lines, sanitation.normalize_unicode is not applied to them")]);
+
+        config.values_conf.sanitation.normalize_unicode = false;
+        assert!(messages(&config).is_empty());
+    }
+
+    #[test]
+    fn should_report_the_legendaries_that_cant_be_placed() {
+        let mut config = crate::config::parse_config(get_resource_filename("test/test_legendaries_config.toml").as_str());
+        config.legendaries[1].position = Some(3);
+        config.legendaries.push(LegendaryConfig { file: get_resource_filename("test/test_dictionary"), position: None });
+
+        let problems = validate_config(&config);
+        let problems: Vec<(&str, &str)> = without_sanitation(&problems).into_iter()
+            .map(|problem| (problem.key.as_str(), problem.message.as_str()))
+            .collect();
+        assert_eq!(problems.len(), 2);
+        assert_eq!(problems[0], ("legendaries.1", "position 3 is out of the collection of 3 characters"));
+        assert_eq!(problems[1].0, "legendaries.2");
+        assert!(problems[1].1.contains("is not valid JSON"));
+
+        config.legendaries[1].position = None;
+        config.legendaries[2].file = config.legendaries[0].file.clone();
+        config.legendaries.push(LegendaryConfig { file: config.legendaries[0].file.clone(), position: None });
+        let problems = validate_config(&config);
+        let problems: Vec<(&str, &str)> = without_sanitation(&problems).into_iter()
+            .map(|problem| (problem.key.as_str(), problem.message.as_str()))
+            .collect();
+        assert_eq!(problems, vec![("legendaries", "4 legendary characters, the collection has 3")]);
+    }
+}